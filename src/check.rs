@@ -0,0 +1,240 @@
+use chrono::prelude::*;
+use std::fmt;
+
+use crate::clock::WorkDuration;
+use crate::holidays::HolidayCalendar;
+use crate::jobcan::{is_day_off_label, AttendanceDay};
+use crate::reconcile::Problem;
+use crate::schedule::Schedule;
+use crate::status::Punch;
+
+/// Working hours for the suggested fixes when JC_SCHEDULE has none for the weekday.
+const DEFAULT_START: (u32, u32) = (9, 0);
+const DEFAULT_END: (u32, u32) = (18, 0);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Issue {
+    /// A working day without any punches.
+    NoPunches,
+    MissingStart,
+    MissingEnd,
+    /// Clocked in and out, but then clocked in again without clocking out.
+    OddPunches(usize),
+    /// Longer than the given minutes.
+    TooLong(u32),
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::NoPunches => write!(f, "no punches on a working day"),
+            Issue::MissingStart => write!(f, "no clock-in"),
+            Issue::MissingEnd => write!(f, "clocked in, but never clocked out"),
+            Issue::OddPunches(count) => write!(f, "odd number of punches ({})", count),
+            Issue::TooLong(minutes) => write!(
+                f,
+                "implausibly long shift ({})",
+                WorkDuration::from_minutes(*minutes)
+            ),
+        }
+    }
+}
+
+/// A `revise-clock` call that would fix an issue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    pub date: NaiveDate,
+    /// hhmm
+    pub time: String,
+    pub message: String,
+}
+
+impl Fix {
+    pub fn command_line(&self) -> String {
+        format!(
+            "jobcan-bot revise-clock --date {} --time {} --message \"{}\"",
+            self.date.format("%Y-%m-%d"),
+            self.time,
+            self.message
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub date: NaiveDate,
+    pub issue: Issue,
+    pub fixes: Vec<Fix>,
+}
+
+/// What is wrong with the day, judging by the attendance list alone.
+pub fn find_issue(
+    day: &AttendanceDay,
+    calendar: &HolidayCalendar,
+    max_shift_minutes: u32,
+) -> Option<Issue> {
+    let record = &day.record;
+    if record.start.trim().is_empty() && record.end.trim().is_empty() {
+        let is_day_off = is_day_off_label(&day.holiday) || !calendar.is_working_day(day.date);
+        return if is_day_off {
+            None
+        } else {
+            Some(Issue::NoPunches)
+        };
+    }
+
+    match record.worked_minutes() {
+        Ok(minutes) if minutes > max_shift_minutes => Some(Issue::TooLong(minutes)),
+        Ok(_) => None,
+        Err(Problem::MissingStart) => Some(Issue::MissingStart),
+        // NOTE(dkg): Only past days are checked, so 勤務中 means the clock-out was forgotten.
+        Err(Problem::MissingEnd) | Err(Problem::StillWorking) => Some(Issue::MissingEnd),
+        Err(Problem::LongerThanADay(minutes)) => Some(Issue::TooLong(minutes)),
+    }
+}
+
+/// Tell a forgotten clock-out apart from a second clock-in, using the punches of the day.
+pub fn refine_with_punches(issue: Issue, punches: &[Punch]) -> Issue {
+    let count = punches.iter().filter(|p| !p.is_break()).count();
+    match issue {
+        Issue::MissingEnd if count > 1 && count % 2 == 1 => Issue::OddPunches(count),
+        _ => issue,
+    }
+}
+
+/// The entries that would fix the issue, based on the working hours of the schedule.
+/// Issues that need a human to decide (e.g. a shift that is too long) have no fixes.
+pub fn suggest_fixes(issue: Issue, date: NaiveDate, schedule: &Schedule) -> Vec<Fix> {
+    let (start, end) = schedule.hours_for(date.weekday()).unwrap_or_else(|| {
+        (
+            NaiveTime::from_hms(DEFAULT_START.0, DEFAULT_START.1, 0),
+            NaiveTime::from_hms(DEFAULT_END.0, DEFAULT_END.1, 0),
+        )
+    });
+    let fix = |time: NaiveTime, message: &str| Fix {
+        date,
+        time: time.format("%H%M").to_string(),
+        message: message.to_string(),
+    };
+
+    match issue {
+        Issue::NoPunches => vec![fix(start, "work start"), fix(end, "work end")],
+        Issue::MissingStart => vec![fix(start, "work start")],
+        Issue::MissingEnd | Issue::OddPunches(_) => vec![fix(end, "work end")],
+        Issue::TooLong(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reconcile::DayRecord;
+
+    fn day(date: &str, holiday: &str, start: &str, end: &str) -> AttendanceDay {
+        AttendanceDay {
+            date: NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            holiday: holiday.into(),
+            record: DayRecord {
+                date: date.into(),
+                start: start.into(),
+                end: end.into(),
+                break_time: String::new(),
+            },
+        }
+    }
+
+    fn punch(time: &str, kind: &str) -> Punch {
+        Punch {
+            time: time.into(),
+            kind: kind.into(),
+            notice: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_find_issue() {
+        let calendar = HolidayCalendar::default();
+        let max = 16 * 60;
+
+        // 2021-10-04 is a Monday
+        assert_eq!(
+            None,
+            find_issue(&day("2021-10-04", "", "09:00", "18:00"), &calendar, max)
+        );
+        assert_eq!(
+            Some(Issue::NoPunches),
+            find_issue(&day("2021-10-04", "", "", ""), &calendar, max)
+        );
+        assert_eq!(
+            Some(Issue::MissingEnd),
+            find_issue(&day("2021-10-04", "", "09:00", "勤務中"), &calendar, max)
+        );
+        assert_eq!(
+            Some(Issue::MissingStart),
+            find_issue(&day("2021-10-04", "", "", "18:00"), &calendar, max)
+        );
+        assert_eq!(
+            Some(Issue::TooLong(17 * 60)),
+            find_issue(&day("2021-10-04", "", "07:00", "24:00"), &calendar, max)
+        );
+    }
+
+    #[test]
+    fn test_find_issue_ignores_days_off() {
+        let calendar = HolidayCalendar::default();
+
+        // Saturday, a national holiday and paid leave
+        assert_eq!(
+            None,
+            find_issue(&day("2021-10-09", "", "", ""), &calendar, 16 * 60)
+        );
+        assert_eq!(
+            None,
+            find_issue(&day("2021-11-03", "", "", ""), &calendar, 16 * 60)
+        );
+        assert_eq!(
+            None,
+            find_issue(&day("2021-10-05", "有休", "", ""), &calendar, 16 * 60)
+        );
+    }
+
+    #[test]
+    fn test_refine_with_punches() {
+        let punches = vec![
+            punch("09:00", "出勤"),
+            punch("12:00", "休憩開始"),
+            punch("13:00", "休憩終了"),
+            punch("15:00", "退勤"),
+            punch("16:00", "出勤"),
+        ];
+
+        assert_eq!(
+            Issue::OddPunches(3),
+            refine_with_punches(Issue::MissingEnd, &punches)
+        );
+        assert_eq!(
+            Issue::MissingEnd,
+            refine_with_punches(Issue::MissingEnd, &punches[..1])
+        );
+    }
+
+    #[test]
+    fn test_suggest_fixes() {
+        let schedule = Schedule::parse("mon-fri 08:30-17:30").unwrap();
+        let monday = NaiveDate::from_ymd(2021, 10, 4);
+
+        let fixes = suggest_fixes(Issue::NoPunches, monday, &schedule);
+        assert_eq!(2, fixes.len());
+        assert_eq!(
+            "jobcan-bot revise-clock --date 2021-10-04 --time 0830 --message \"work start\"",
+            fixes[0].command_line()
+        );
+        assert_eq!("1730", fixes[1].time);
+
+        // No schedule: 09:00-18:00
+        let fixes = suggest_fixes(Issue::MissingEnd, monday, &Schedule::default());
+        assert_eq!("1800", fixes[0].time);
+
+        assert!(suggest_fixes(Issue::TooLong(20 * 60), monday, &schedule).is_empty());
+    }
+}
@@ -0,0 +1,269 @@
+//! Times of day and durations as Jobcan shows them.
+//!
+//! Jobcan counts the times of a working day past midnight, e.g. 26:00 is 2am of the next day.
+//! Both types accept "9:05", "09:05", "0905" and full-width digits like "０９：０５".
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// The latest time of a working day, 47:59.
+const MAX_CLOCK_MINUTES: u32 = 2 * MINUTES_PER_DAY - 1;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ParseTimeError {
+    #[error("'{0}' is not a time, expected hh:mm or hhmm")]
+    Format(String),
+    #[error("'{0}' is out of range")]
+    Range(String),
+}
+
+/// A time of the working day in minutes since midnight. Can be 24:00 or later for times after midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ClockTime(u32);
+
+impl ClockTime {
+    pub fn from_minutes(minutes: u32) -> Option<Self> {
+        if minutes > MAX_CLOCK_MINUTES {
+            None
+        } else {
+            Some(ClockTime(minutes))
+        }
+    }
+
+    pub fn from_hm(hours: u32, minutes: u32) -> Option<Self> {
+        if minutes >= 60 {
+            return None;
+        }
+        ClockTime::from_minutes(hours.checked_mul(60)?.checked_add(minutes)?)
+    }
+
+    pub fn minutes(self) -> u32 {
+        self.0
+    }
+
+    /// The time as Jobcan's "revise clocking data" form expects it, e.g. "0905" or "2600".
+    pub fn to_jobcan(self) -> String {
+        format!("{:02}{:02}", self.0 / 60, self.0 % 60)
+    }
+
+    /// The time from `self` until `end`. An `end` before `self` is taken as the next day (overnight shift).
+    pub fn until(self, end: ClockTime) -> WorkDuration {
+        if end.0 >= self.0 {
+            return WorkDuration(end.0 - self.0);
+        }
+        // NOTE(dkg): `self` can be after midnight already (e.g. 25:00), so it's not always just one day.
+        match (self.0 - end.0) % MINUTES_PER_DAY {
+            0 => WorkDuration(0),
+            behind => WorkDuration(MINUTES_PER_DAY - behind),
+        }
+    }
+}
+
+impl FromStr for ClockTime {
+    type Err = ParseTimeError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (hours, minutes) = split_hours_minutes(input, 2)?;
+        ClockTime::from_hm(hours, minutes).ok_or_else(|| ParseTimeError::Range(input.to_string()))
+    }
+}
+
+impl fmt::Display for ClockTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.0 / 60, self.0 % 60)
+    }
+}
+
+/// An amount of time, e.g. a break or the total of a month like "160:00".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct WorkDuration(u32);
+
+impl WorkDuration {
+    pub fn from_minutes(minutes: u32) -> Self {
+        WorkDuration(minutes)
+    }
+
+    pub fn minutes(self) -> u32 {
+        self.0
+    }
+}
+
+impl FromStr for WorkDuration {
+    type Err = ParseTimeError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        // NOTE(dkg): 6 digits for the hours is plenty, and keeps the minutes from overflowing.
+        let (hours, minutes) = split_hours_minutes(input, 6)?;
+        if minutes >= 60 {
+            return Err(ParseTimeError::Range(input.to_string()));
+        }
+        Ok(WorkDuration(hours * 60 + minutes))
+    }
+}
+
+impl fmt::Display for WorkDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.0 / 60, self.0 % 60)
+    }
+}
+
+/// "h:mm" (with up to `max_hour_digits` digits for the hours) or "hhmm".
+fn split_hours_minutes(input: &str, max_hour_digits: usize) -> Result<(u32, u32), ParseTimeError> {
+    let format_error = || ParseTimeError::Format(input.to_string());
+    let normalized = normalize(input);
+    let (hours, minutes) = match normalized.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if normalized.len() == 4 && normalized.is_ascii() => normalized.split_at(2),
+        None => return Err(format_error()),
+    };
+
+    let is_number = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !is_number(hours)
+        || hours.len() > max_hour_digits
+        || !is_number(minutes)
+        || minutes.len() != 2
+    {
+        return Err(format_error());
+    }
+
+    Ok((
+        hours.parse().map_err(|_| format_error())?,
+        minutes.parse().map_err(|_| format_error())?,
+    ))
+}
+
+/// Turn full-width digits and colons into ASCII and drop surrounding whitespace.
+fn normalize(input: &str) -> String {
+    input
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or(c),
+            '：' => ':',
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn minutes(input: &str) -> Option<u32> {
+        input.parse::<ClockTime>().ok().map(ClockTime::minutes)
+    }
+
+    #[test]
+    fn test_parse_clock_time() {
+        assert_eq!(Some(9 * 60 + 51), minutes("09:51"));
+        assert_eq!(Some(23 * 60 + 59), minutes("23:59"));
+        assert_eq!(Some(1), minutes("00:01"));
+        assert_eq!(Some(9 * 60 + 5), minutes("9:05"));
+        assert_eq!(Some(26 * 60), minutes("26:00"));
+        assert_eq!(Some(9 * 60 + 5), minutes("0905"));
+        assert_eq!(Some(9 * 60 + 5), minutes("０９：０５"));
+        assert_eq!(Some(18 * 60), minutes(" 18:00 "));
+    }
+
+    #[test]
+    fn test_parse_clock_time_failures() {
+        assert_eq!(None, minutes("勤務中"));
+        assert_eq!(None, minutes("11:mm"));
+        assert_eq!(None, minutes("mm:11"));
+        assert_eq!(None, minutes(":"));
+        assert_eq!(None, minutes("0:0"));
+        assert_eq!(None, minutes(""));
+        assert_eq!(None, minutes("9:60"));
+        assert_eq!(None, minutes("48:00"));
+        assert_eq!(None, minutes("905"));
+        assert_eq!(None, minutes("+9:05"));
+    }
+
+    #[test]
+    fn test_clock_time_to_jobcan() {
+        assert_eq!("0905", ClockTime::from_hm(9, 5).unwrap().to_jobcan());
+        assert_eq!("2600", ClockTime::from_hm(26, 0).unwrap().to_jobcan());
+    }
+
+    #[test]
+    fn test_until_overnight() {
+        let time = |s: &str| s.parse::<ClockTime>().unwrap();
+
+        assert_eq!(9 * 60, time("09:00").until(time("18:00")).minutes());
+        assert_eq!(8 * 60, time("22:00").until(time("06:00")).minutes());
+        assert_eq!(8 * 60, time("22:00").until(time("30:00")).minutes());
+        assert_eq!(0, time("09:00").until(time("09:00")).minutes());
+    }
+
+    #[test]
+    fn test_parse_work_duration() {
+        let duration = |s: &str| s.parse::<WorkDuration>().map(WorkDuration::minutes);
+
+        assert_eq!(Ok(160 * 60), duration("160:00"));
+        assert_eq!(Ok(8 * 60 + 30), duration("08:30"));
+        assert_eq!(Ok(0), duration("0:00"));
+        assert_eq!(Ok(90), duration("０１：３０"));
+        assert!(duration("08:75").is_err());
+        assert!(duration("").is_err());
+        assert!(duration("1234567:00").is_err());
+    }
+
+    fn full_width(input: &str) -> String {
+        input
+            .chars()
+            .map(|c| match c {
+                '0'..='9' => char::from_u32(c as u32 - '0' as u32 + '０' as u32).unwrap(),
+                ':' => '：',
+                _ => c,
+            })
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn prop_clock_time_roundtrip(minutes in 0..=MAX_CLOCK_MINUTES) {
+            let time = ClockTime::from_minutes(minutes).unwrap();
+
+            prop_assert_eq!(Ok(time), time.to_string().parse::<ClockTime>());
+            prop_assert_eq!(Ok(time), time.to_jobcan().parse::<ClockTime>());
+            prop_assert_eq!(Ok(time), full_width(&time.to_string()).parse::<ClockTime>());
+        }
+
+        #[test]
+        fn prop_clock_time_without_leading_zero(hours in 0..10u32, minutes in 0..60u32) {
+            let input = format!("{}:{:02}", hours, minutes);
+
+            prop_assert_eq!(Some(hours * 60 + minutes), input.parse::<ClockTime>().ok().map(ClockTime::minutes));
+        }
+
+        #[test]
+        fn prop_until_is_a_shift(start in 0..=MAX_CLOCK_MINUTES, end in 0..=MAX_CLOCK_MINUTES) {
+            let start = ClockTime::from_minutes(start).unwrap();
+            let end = ClockTime::from_minutes(end).unwrap();
+            let shift = start.until(end).minutes();
+
+            if end >= start {
+                prop_assert_eq!(end.minutes() - start.minutes(), shift);
+            } else {
+                prop_assert!(shift < MINUTES_PER_DAY);
+                prop_assert_eq!((start.minutes() + shift) % MINUTES_PER_DAY, end.minutes() % MINUTES_PER_DAY);
+            }
+        }
+
+        #[test]
+        fn prop_parse_never_panics(input in "\\PC*") {
+            let _ = input.parse::<ClockTime>();
+            let _ = input.parse::<WorkDuration>();
+        }
+
+        #[test]
+        fn prop_work_duration_roundtrip(minutes in 0..1_000_000u32) {
+            let duration = WorkDuration::from_minutes(minutes);
+
+            prop_assert_eq!(Ok(duration), duration.to_string().parse::<WorkDuration>());
+        }
+    }
+}
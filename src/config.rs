@@ -1,16 +1,110 @@
+use color_eyre::eyre::WrapErr;
 use std::env;
+use std::path::PathBuf;
+
+use crate::email::EmailSettings;
+use crate::endpoints::{Endpoints, DEFAULT_ID_BASE_URL, DEFAULT_SSL_BASE_URL};
+use crate::holidays::HolidayCalendar;
+use crate::notifier::Webhook;
+use crate::overtime::OvertimeLimits;
+use crate::slack::StatusSettings;
+use crate::template::SlackTemplates;
+use crate::timezone::JobcanTimezone;
 
 pub const ENVVAR_NAME_LOGIN: &str = "JC_LOGIN";
 pub const ENVVAR_NAME_PASSWORD: &str = "JC_PASSWORD";
 pub const ENVVAR_SLACK_TOKEN: &str = "SLACK_TOKEN";
 pub const ENVVAR_SLACK_USER_NAME: &str = "SLACK_USER_NAME";
+pub const ENVVAR_SLACK_USER_ID: &str = "SLACK_USER_ID";
+pub const ENVVAR_SLACK_SIGNING_SECRET: &str = "SLACK_SIGNING_SECRET";
+pub const ENVVAR_SLACK_THREAD_CLOCK_OUT: &str = "SLACK_THREAD_CLOCK_OUT";
+pub const ENVVAR_SLACK_TOTAL_ON_CLOCK_IN: &str = "SLACK_TOTAL_ON_CLOCK_IN";
+pub const ENVVAR_SLACK_STATUS_CLOCK_IN: &str = "SLACK_STATUS_CLOCK_IN";
+pub const ENVVAR_SLACK_STATUS_CLOCK_OUT: &str = "SLACK_STATUS_CLOCK_OUT";
+pub const ENVVAR_SLACK_PRESENCE: &str = "SLACK_PRESENCE";
+pub const ENVVAR_SLACK_DND_AFTER_WORK: &str = "SLACK_DND_AFTER_WORK";
+pub const ENVVAR_SLACK_TEMPLATE_CLOCK_IN: &str = "SLACK_TEMPLATE_CLOCK_IN";
+pub const ENVVAR_SLACK_TEMPLATE_CLOCK_OUT: &str = "SLACK_TEMPLATE_CLOCK_OUT";
+pub const ENVVAR_SLACK_TEMPLATE_BREAK_START: &str = "SLACK_TEMPLATE_BREAK_START";
+pub const ENVVAR_SLACK_TEMPLATE_BREAK_END: &str = "SLACK_TEMPLATE_BREAK_END";
+pub const ENVVAR_SLACK_TEMPLATE_REVISE: &str = "SLACK_TEMPLATE_REVISE";
+pub const ENVVAR_ID_BASE_URL: &str = "JC_ID_BASE_URL";
+pub const ENVVAR_SSL_BASE_URL: &str = "JC_SSL_BASE_URL";
+pub const ENVVAR_SIGN_IN_URL: &str = "JC_SIGN_IN_URL";
+pub const ENVVAR_OAUTH_LOGIN_URL: &str = "JC_OAUTH_LOGIN_URL";
+pub const ENVVAR_EMPLOYEE_URL: &str = "JC_EMPLOYEE_URL";
+pub const ENVVAR_ADIT_MODIFY_URL: &str = "JC_ADIT_MODIFY_URL";
+pub const ENVVAR_ATTENDANCE_URL: &str = "JC_ATTENDANCE_URL";
+
+pub const ENVVAR_STATE_DIR: &str = "JC_STATE_DIR";
+pub const ENVVAR_GROUP: &str = "JC_GROUP";
+pub const ENVVAR_SCHEDULE: &str = "JC_SCHEDULE";
+pub const ENVVAR_SCHEDULE_JITTER: &str = "JC_SCHEDULE_JITTER";
+pub const ENVVAR_DAYS_OFF: &str = "JC_DAYS_OFF";
+pub const ENVVAR_DAILY_HOURS: &str = "JC_DAILY_HOURS";
+pub const ENVVAR_OVERTIME_MONTHLY_LIMIT: &str = "JC_OVERTIME_MONTHLY_LIMIT";
+pub const ENVVAR_OVERTIME_YEARLY_LIMIT: &str = "JC_OVERTIME_YEARLY_LIMIT";
+pub const ENVVAR_OVERTIME_SPECIAL_MONTHLY_LIMIT: &str = "JC_OVERTIME_SPECIAL_MONTHLY_LIMIT";
+pub const ENVVAR_OVERTIME_AVERAGE_LIMIT: &str = "JC_OVERTIME_AVERAGE_LIMIT";
+pub const ENVVAR_OVERTIME_WARN_PERCENT: &str = "JC_OVERTIME_WARN_PERCENT";
+pub const ENVVAR_OVERTIME_YEAR_START: &str = "JC_OVERTIME_YEAR_START";
+pub const ENVVAR_TIMEZONE: &str = "JC_TIMEZONE";
+pub const ENVVAR_WEBHOOKS: &str = "JC_WEBHOOKS";
+pub const ENVVAR_DESKTOP_NOTIFICATIONS: &str = "JC_DESKTOP_NOTIFICATIONS";
+pub const ENVVAR_SMTP_HOST: &str = "SMTP_HOST";
+pub const ENVVAR_SMTP_PORT: &str = "SMTP_PORT";
+pub const ENVVAR_SMTP_USER: &str = "SMTP_USER";
+pub const ENVVAR_SMTP_PASSWORD: &str = "SMTP_PASSWORD";
+pub const ENVVAR_SMTP_TLS: &str = "SMTP_TLS";
+pub const ENVVAR_EMAIL_FROM: &str = "EMAIL_FROM";
+pub const ENVVAR_EMAIL_TO: &str = "EMAIL_TO";
+pub const ENVVAR_EMAIL_REPORT_DAY: &str = "EMAIL_REPORT_DAY";
+
+/// Regular working time per day when JC_DAILY_HOURS is not set.
+pub const DEFAULT_DAILY_MINUTES: u32 = 8 * 60;
 
-#[derive(Default)]
+/// Directory (relative to the home directory) for data the bot keeps between runs, e.g. the cached session.
+pub const DEFAULT_STATE_DIR: &str = ".jobcan-bot";
+
+#[derive(Clone, Default)]
 pub struct Configuration {
     pub login: String,
     pub password: String,
     pub slack_token: String,
     pub slack_user_name: String,
+    /// Slack member ID, e.g. "U0123ABCD". Saves looking up SLACK_USER_NAME in the workspace.
+    pub slack_user_id: String,
+    /// Templates for the Slack messages, from the SLACK_TEMPLATE_* environment variables.
+    pub slack_templates: SlackTemplates,
+    /// Slack status, presence and do not disturb changes when clocking in or out.
+    pub slack_status: StatusSettings,
+    /// Signing secret of the Slack app, to check that requests to `serve-slack` come from Slack.
+    pub slack_signing_secret: String,
+    /// Post the clock-out as a reply in the thread of the day's clock-in message.
+    pub slack_thread_clock_out: bool,
+    /// Add the hours worked to the day's clock-in message when clocking out.
+    pub slack_total_on_clock_in: bool,
+    pub endpoints: Endpoints,
+    pub state_dir: PathBuf,
+    /// Name or id of the Jobcan group to select when punching. Empty means to keep what Jobcan preselects.
+    pub default_group: String,
+    /// Weekly working hours for the daemon, e.g. "mon-fri 09:00-18:00".
+    pub schedule: String,
+    /// Random offset in minutes (+/-) applied to every scheduled punch.
+    pub schedule_jitter_minutes: u32,
+    /// Company days off on top of the national holidays, e.g. "12-29,12-30,2021-08-13".
+    pub days_off: String,
+    /// Regular working time per day, used to calculate the expected hours.
+    pub daily_minutes: u32,
+    pub overtime_limits: OvertimeLimits,
+    /// Timezone of the Jobcan tenant, e.g. "Asia/Tokyo". Empty means Asia/Tokyo.
+    pub timezone: String,
+    /// Incoming webhooks (Teams, Discord, Mattermost, JSON) to notify besides Slack, see `notifier`.
+    pub webhooks: String,
+    /// Show a desktop notification for every punch and failed punch.
+    pub desktop_notifications: bool,
+    /// SMTP server and addresses for the monthly report.
+    pub email: EmailSettings,
 }
 
 impl std::fmt::Debug for Configuration {
@@ -18,22 +112,155 @@ impl std::fmt::Debug for Configuration {
         f.debug_struct("Configuration")
             .field("login", &self.login)
             .field("password", &String::from("******"))
+            .field("endpoints", &self.endpoints)
+            .field("state_dir", &self.state_dir)
+            .field("default_group", &self.default_group)
+            .field("schedule", &self.schedule)
+            .field("schedule_jitter_minutes", &self.schedule_jitter_minutes)
+            .field("days_off", &self.days_off)
+            .field("daily_minutes", &self.daily_minutes)
+            .field("overtime_limits", &self.overtime_limits)
+            .field("timezone", &self.timezone)
             .finish()
     }
 }
 
+/// "1", "true" or "yes" turn a setting on, anything else (or nothing) leaves it off.
+pub fn env_flag(name: &str) -> bool {
+    matches!(
+        env::var(name).unwrap_or_default().to_lowercase().as_str(),
+        "1" | "true" | "yes"
+    )
+}
+
 impl Configuration {
     pub fn from_env() -> Self {
         let login = env::var(ENVVAR_NAME_LOGIN).unwrap_or_default();
         let password = env::var(ENVVAR_NAME_PASSWORD).unwrap_or_default();
         let slack_token = env::var(ENVVAR_SLACK_TOKEN).unwrap_or_default();
         let slack_user_name = env::var(ENVVAR_SLACK_USER_NAME).unwrap_or_default();
+        let slack_user_id = env::var(ENVVAR_SLACK_USER_ID).unwrap_or_default();
+        let slack_templates = SlackTemplates {
+            clock_in: env::var(ENVVAR_SLACK_TEMPLATE_CLOCK_IN).unwrap_or_default(),
+            clock_out: env::var(ENVVAR_SLACK_TEMPLATE_CLOCK_OUT).unwrap_or_default(),
+            break_start: env::var(ENVVAR_SLACK_TEMPLATE_BREAK_START).unwrap_or_default(),
+            break_end: env::var(ENVVAR_SLACK_TEMPLATE_BREAK_END).unwrap_or_default(),
+            revise: env::var(ENVVAR_SLACK_TEMPLATE_REVISE).unwrap_or_default(),
+        };
+        let slack_status = StatusSettings {
+            clock_in: env::var(ENVVAR_SLACK_STATUS_CLOCK_IN).unwrap_or_default(),
+            clock_out: env::var(ENVVAR_SLACK_STATUS_CLOCK_OUT).unwrap_or_default(),
+            presence: env_flag(ENVVAR_SLACK_PRESENCE),
+            dnd_after_work: env::var(ENVVAR_SLACK_DND_AFTER_WORK).unwrap_or_default(),
+        };
+        let slack_signing_secret = env::var(ENVVAR_SLACK_SIGNING_SECRET).unwrap_or_default();
+        let slack_thread_clock_out = env_flag(ENVVAR_SLACK_THREAD_CLOCK_OUT);
+        let slack_total_on_clock_in = env_flag(ENVVAR_SLACK_TOTAL_ON_CLOCK_IN);
+        // NOTE(dkg): The URLs are built from the two hosts, but each one can be overridden on its own.
+        let url = |name| {
+            env::var(name)
+                .ok()
+                .filter(|url| !url.is_empty())
+                .map(|url| url.trim_end_matches('/').to_string())
+        };
+        let default_endpoints = Endpoints::new(
+            &url(ENVVAR_ID_BASE_URL).unwrap_or_else(|| DEFAULT_ID_BASE_URL.into()),
+            &url(ENVVAR_SSL_BASE_URL).unwrap_or_else(|| DEFAULT_SSL_BASE_URL.into()),
+        );
+        let endpoints = Endpoints {
+            sign_in: url(ENVVAR_SIGN_IN_URL).unwrap_or(default_endpoints.sign_in),
+            oauth_login: url(ENVVAR_OAUTH_LOGIN_URL).unwrap_or(default_endpoints.oauth_login),
+            employee: url(ENVVAR_EMPLOYEE_URL).unwrap_or(default_endpoints.employee),
+            adit_modify: url(ENVVAR_ADIT_MODIFY_URL).unwrap_or(default_endpoints.adit_modify),
+            attendance: url(ENVVAR_ATTENDANCE_URL).unwrap_or(default_endpoints.attendance),
+        };
+        let default_group = env::var(ENVVAR_GROUP).unwrap_or_default();
+        let schedule = env::var(ENVVAR_SCHEDULE).unwrap_or_default();
+        let schedule_jitter_minutes = env::var(ENVVAR_SCHEDULE_JITTER)
+            .unwrap_or_default()
+            .parse::<u32>()
+            .unwrap_or(0);
+        let days_off = env::var(ENVVAR_DAYS_OFF).unwrap_or_default();
+        // NOTE(dkg): Hours, but allow fractions like "7.5".
+        let daily_minutes = env::var(ENVVAR_DAILY_HOURS)
+            .unwrap_or_default()
+            .parse::<f32>()
+            .ok()
+            .filter(|hours| *hours > 0.0 && *hours <= 24.0)
+            .map(|hours| (hours * 60.0).round() as u32)
+            .unwrap_or(DEFAULT_DAILY_MINUTES);
+        // NOTE(dkg): The limits are given in hours, anything invalid keeps the default.
+        let overtime_hours = |name| {
+            env::var(name)
+                .ok()
+                .and_then(|value| value.trim().parse::<u32>().ok())
+        };
+        let default_limits = OvertimeLimits::default();
+        let overtime_limits = OvertimeLimits {
+            monthly: overtime_hours(ENVVAR_OVERTIME_MONTHLY_LIMIT)
+                .map(|hours| hours * 60)
+                .unwrap_or(default_limits.monthly),
+            yearly: overtime_hours(ENVVAR_OVERTIME_YEARLY_LIMIT)
+                .map(|hours| hours * 60)
+                .unwrap_or(default_limits.yearly),
+            special_monthly: overtime_hours(ENVVAR_OVERTIME_SPECIAL_MONTHLY_LIMIT)
+                .map(|hours| hours * 60)
+                .unwrap_or(default_limits.special_monthly),
+            average: overtime_hours(ENVVAR_OVERTIME_AVERAGE_LIMIT)
+                .map(|hours| hours * 60)
+                .unwrap_or(default_limits.average),
+            warn_percent: overtime_hours(ENVVAR_OVERTIME_WARN_PERCENT)
+                .filter(|percent| (1..=100).contains(percent))
+                .unwrap_or(default_limits.warn_percent),
+            year_start_month: overtime_hours(ENVVAR_OVERTIME_YEAR_START)
+                .filter(|month| (1..=12).contains(month))
+                .unwrap_or(default_limits.year_start_month),
+        };
+        let timezone = env::var(ENVVAR_TIMEZONE).unwrap_or_default();
+        let webhooks = env::var(ENVVAR_WEBHOOKS).unwrap_or_default();
+        let desktop_notifications = env_flag(ENVVAR_DESKTOP_NOTIFICATIONS);
+        let email = EmailSettings {
+            host: env::var(ENVVAR_SMTP_HOST).unwrap_or_default(),
+            port: env::var(ENVVAR_SMTP_PORT).unwrap_or_default(),
+            user: env::var(ENVVAR_SMTP_USER).unwrap_or_default(),
+            password: env::var(ENVVAR_SMTP_PASSWORD).unwrap_or_default(),
+            tls: env::var(ENVVAR_SMTP_TLS).unwrap_or_default(),
+            from: env::var(ENVVAR_EMAIL_FROM).unwrap_or_default(),
+            to: env::var(ENVVAR_EMAIL_TO).unwrap_or_default(),
+            report_day: env::var(ENVVAR_EMAIL_REPORT_DAY).unwrap_or_default(),
+        };
+        let state_dir = env::var(ENVVAR_STATE_DIR)
+            .ok()
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| {
+                let home = env::var("HOME").unwrap_or_default();
+                PathBuf::from(home).join(DEFAULT_STATE_DIR)
+            });
 
         Configuration {
             login,
             password,
             slack_token,
             slack_user_name,
+            slack_user_id,
+            slack_templates,
+            slack_status,
+            slack_signing_secret,
+            slack_thread_clock_out,
+            slack_total_on_clock_in,
+            endpoints,
+            state_dir,
+            default_group,
+            schedule,
+            schedule_jitter_minutes,
+            days_off,
+            daily_minutes,
+            overtime_limits,
+            timezone,
+            webhooks,
+            desktop_notifications,
+            email,
         }
     }
 
@@ -41,7 +268,40 @@ impl Configuration {
         !self.login.is_empty() && !self.password.is_empty()
     }
 
+    /// The group to use when none was given on the command line.
+    pub fn default_group(&self) -> Option<&str> {
+        if self.default_group.is_empty() {
+            None
+        } else {
+            Some(&self.default_group)
+        }
+    }
+
+    /// National holidays plus the company days off from JC_DAYS_OFF.
+    pub fn holiday_calendar(&self) -> color_eyre::Result<HolidayCalendar> {
+        HolidayCalendar::new(&self.days_off)
+            .wrap_err_with(|| format!("Please check {}.", ENVVAR_DAYS_OFF))
+    }
+
+    /// The timezone Jobcan's dates and times are in, from JC_TIMEZONE.
+    pub fn jobcan_timezone(&self) -> color_eyre::Result<JobcanTimezone> {
+        JobcanTimezone::new(&self.timezone)
+            .wrap_err_with(|| format!("Please check {}.", ENVVAR_TIMEZONE))
+    }
+
+    /// The webhooks from JC_WEBHOOKS.
+    pub fn webhooks(&self) -> color_eyre::Result<Vec<Webhook>> {
+        Webhook::parse_all(&self.webhooks)
+            .wrap_err_with(|| format!("Please check {}.", ENVVAR_WEBHOOKS))
+    }
+
+    /// Whether punches are announced anywhere: on Slack, a webhook or the desktop.
+    pub fn has_notifiers(&self) -> bool {
+        self.can_post_to_slack() || !self.webhooks.trim().is_empty() || self.desktop_notifications
+    }
+
     pub fn can_post_to_slack(&self) -> bool {
-        !self.slack_token.is_empty() && !self.slack_user_name.is_empty()
+        !self.slack_token.is_empty()
+            && (!self.slack_user_name.is_empty() || !self.slack_user_id.is_empty())
     }
 }
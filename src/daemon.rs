@@ -0,0 +1,246 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use color_eyre::eyre::{bail, eyre};
+use log::{error, info, warn};
+use rand::Rng;
+use thirtyfour::prelude::*;
+
+use crate::config::{Configuration, ENVVAR_SCHEDULE};
+use crate::jobcan::{self, PunchKind};
+use crate::notifier::{self, Notification, SlackNotifier};
+use crate::report;
+use crate::schedule::{Schedule, ScheduledPunch};
+use crate::status::WorkingStatus;
+use crate::template::{PunchDetails, RenderedMessage};
+use crate::timezone::JobcanTimezone;
+use crate::{flush_outbox, push_and_post, Daemon, PushIt};
+
+/// How often the daemon wakes up while waiting. Keeps the schedule accurate after the machine was suspended.
+const MAX_SLEEP_SECONDS: i64 = 60;
+
+pub async fn run(config: &Configuration, daemon: &Daemon, visible: bool) -> color_eyre::Result<()> {
+    let schedule = Schedule::parse(&config.schedule)?;
+    if schedule.is_empty() {
+        bail!(
+            "No schedule configured. Set {}, e.g. \"mon-fri 09:00-18:00\".",
+            ENVVAR_SCHEDULE
+        );
+    }
+    let calendar = config.holiday_calendar()?;
+    let timezone = config.jobcan_timezone()?;
+    if !config.email.is_empty() {
+        config.email.validate()?;
+    }
+    let jitter = Duration::minutes(config.schedule_jitter_minutes as i64);
+
+    let mut last_event = timezone.now();
+    loop {
+        // NOTE(dkg): Never go back further than the jitter, so that we don't replay punches we slept through.
+        let after = last_event.max(timezone.now() - jitter);
+        let (at, punch) = schedule
+            .next_event(after)
+            .ok_or_else(|| eyre!("The schedule has no upcoming punches."))?;
+        last_event = at;
+
+        if let Some(name) = calendar.holiday_name(at.date()) {
+            info!(
+                "Skipping the automatic {} on {}: {}.",
+                punch,
+                at.date(),
+                name
+            );
+            continue;
+        }
+
+        let run_at = with_jitter(at, config.schedule_jitter_minutes);
+        info!("Next automatic {} at {}.", punch, timezone.describe(run_at));
+        sleep_until(&timezone, run_at).await;
+        flush_outbox(config).await;
+        report::send_if_due(config, &timezone, visible).await;
+
+        if let Err(err) =
+            run_scheduled_punch(config, &timezone, daemon, visible, punch, at.date()).await
+        {
+            error!("The automatic {} failed: {}", punch, err);
+            notify(
+                config,
+                &timezone,
+                &daemon.slack_channel,
+                &format!("The automatic Jobcan {} failed: {}", punch, err),
+            )
+            .await;
+        }
+    }
+}
+
+fn with_jitter(at: NaiveDateTime, jitter_minutes: u32) -> NaiveDateTime {
+    if jitter_minutes == 0 {
+        return at;
+    }
+    let jitter = jitter_minutes as i64 * 60;
+    let offset = rand::thread_rng().gen_range(-jitter..=jitter);
+    at + Duration::seconds(offset)
+}
+
+pub async fn sleep_until(timezone: &JobcanTimezone, at: NaiveDateTime) {
+    loop {
+        let remaining = at - timezone.now();
+        if remaining <= Duration::zero() {
+            break;
+        }
+        let seconds = remaining.num_seconds().clamp(1, MAX_SLEEP_SECONDS) as u64;
+        tokio::time::sleep(std::time::Duration::from_secs(seconds)).await;
+    }
+}
+
+async fn run_scheduled_punch(
+    config: &Configuration,
+    timezone: &JobcanTimezone,
+    daemon: &Daemon,
+    visible: bool,
+    punch: ScheduledPunch,
+    date: NaiveDate,
+) -> color_eyre::Result<()> {
+    let driver = jobcan::start_webdriver(visible).await?;
+    let result = scheduled_punch(&driver, config, timezone, daemon, punch, date).await;
+    if let Err(err) = driver.quit().await {
+        warn!("Could not close the browser: {}", err);
+    }
+
+    result
+}
+
+/// What the daemon does for a scheduled punch, depending on the current status.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Punch,
+    /// Clocking out only works while working, so the break is ended first.
+    EndBreakAndPunch,
+    /// E.g. no clock-in when already working.
+    Skip,
+}
+
+fn action_for(punch: ScheduledPunch, status: WorkingStatus) -> Action {
+    match (punch, status) {
+        (ScheduledPunch::ClockIn, WorkingStatus::NotStarted)
+        | (ScheduledPunch::ClockOut, WorkingStatus::Working) => Action::Punch,
+        (ScheduledPunch::ClockOut, WorkingStatus::OnBreak) => Action::EndBreakAndPunch,
+        _ => Action::Skip,
+    }
+}
+
+async fn scheduled_punch(
+    driver: &WebDriver,
+    config: &Configuration,
+    timezone: &JobcanTimezone,
+    daemon: &Daemon,
+    punch: ScheduledPunch,
+    date: NaiveDate,
+) -> color_eyre::Result<()> {
+    jobcan::login(driver, config).await?;
+
+    if let Some(label) = jobcan::read_day_off(driver, config, date).await? {
+        info!(
+            "Skipping the automatic {}: {} is {} in Jobcan.",
+            punch, date, label
+        );
+        return Ok(());
+    }
+
+    let working_status = jobcan::read_working_status(driver, config).await?;
+    match action_for(punch, working_status) {
+        Action::Skip => {
+            info!(
+                "Skipping the automatic {}: the current status is '{}'.",
+                punch, working_status
+            );
+            return Ok(());
+        }
+        Action::EndBreakAndPunch => {
+            info!("Ending the break before the automatic clock-out.");
+            jobcan::push(
+                driver,
+                config,
+                PunchKind::BreakEnd,
+                "",
+                config.default_group(),
+            )
+            .await?;
+            // NOTE(dkg): push_and_post reads the status right away. While Jobcan still shows the break, the
+            // clock-out would be taken for done as soon as the break end shows up.
+            jobcan::verify_punch(driver, config, WorkingStatus::OnBreak).await?;
+        }
+        Action::Punch => (),
+    }
+    let message = match punch {
+        ScheduledPunch::ClockIn => &daemon.clock_in_message,
+        ScheduledPunch::ClockOut => &daemon.clock_out_message,
+    };
+
+    let push_it = PushIt {
+        message: message.clone(),
+        slack_message: String::new(),
+        slack_channel: daemon.slack_channel.clone(),
+        group: None,
+        force: false,
+        at: None,
+        minutes_ago: None,
+    };
+    push_and_post(driver, config, timezone, &push_it, None).await?;
+    info!("Automatic {} done.", punch);
+
+    Ok(())
+}
+
+/// Tell Slack and the webhooks that want errors about a problem.
+async fn notify(config: &Configuration, timezone: &JobcanTimezone, channel: &str, message: &str) {
+    if !config.has_notifiers() {
+        return;
+    }
+    let webhooks = config.webhooks().unwrap_or_else(|err| {
+        warn!("{}", err);
+        vec![]
+    });
+    let slack = Some(SlackNotifier {
+        config,
+        channel,
+        thread: None,
+    })
+    .filter(|_| config.can_post_to_slack());
+    let notifiers =
+        notifier::notifiers_for(&webhooks, None, false, slack, config.desktop_notifications);
+
+    let now = timezone.now();
+    let details = PunchDetails {
+        name: config.login.clone(),
+        date: now.format("%Y-%m-%d").to_string(),
+        memo: message.to_string(),
+        ..Default::default()
+    };
+    let message = RenderedMessage::text(message);
+    let notification = Notification {
+        details: &details,
+        message: &message,
+        at: now.format("%Y-%m-%d %H:%M").to_string(),
+    };
+    notifier::notify_all(&notifiers, &notification).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_for() {
+        use ScheduledPunch::{ClockIn, ClockOut};
+        use WorkingStatus::{Finished, NotStarted, OnBreak, Working};
+
+        assert_eq!(Action::Punch, action_for(ClockIn, NotStarted));
+        assert_eq!(Action::Punch, action_for(ClockOut, Working));
+        assert_eq!(Action::EndBreakAndPunch, action_for(ClockOut, OnBreak));
+        assert_eq!(Action::Skip, action_for(ClockIn, Working));
+        assert_eq!(Action::Skip, action_for(ClockIn, OnBreak));
+        assert_eq!(Action::Skip, action_for(ClockOut, NotStarted));
+        assert_eq!(Action::Skip, action_for(ClockOut, Finished));
+    }
+}
@@ -0,0 +1,297 @@
+//! Emails over SMTP, e.g. the monthly report for a manager.
+use color_eyre::eyre::{bail, eyre, WrapErr};
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::{
+    ENVVAR_EMAIL_FROM, ENVVAR_EMAIL_REPORT_DAY, ENVVAR_EMAIL_TO, ENVVAR_SMTP_HOST,
+};
+
+/// How to talk to the SMTP server.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmtpTls {
+    /// Plain connection upgraded with STARTTLS, usually on port 587.
+    StartTls,
+    /// TLS from the start, usually on port 465.
+    Tls,
+    /// No encryption at all. Only for a server on the same machine, e.g. a local SMTP sink for testing.
+    None,
+}
+
+impl std::str::FromStr for SmtpTls {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "starttls" => Ok(SmtpTls::StartTls),
+            "tls" => Ok(SmtpTls::Tls),
+            "none" => Ok(SmtpTls::None),
+            other => Err(eyre!(
+                "Unknown TLS mode '{}'. Use starttls, tls or none.",
+                other
+            )),
+        }
+    }
+}
+
+/// The SMTP server and addresses, from the SMTP_* and EMAIL_* environment variables. No SMTP_HOST means no emails.
+#[derive(Clone, Default, PartialEq)]
+pub struct EmailSettings {
+    pub host: String,
+    /// Empty means the default port of the TLS mode.
+    pub port: String,
+    pub user: String,
+    pub password: String,
+    /// starttls (default), tls or none.
+    pub tls: String,
+    pub from: String,
+    /// Comma separated, e.g. "manager@example.com, lead@example.com".
+    pub to: String,
+    /// Day of the month the daemon sends the report of the previous month on. Empty means never.
+    pub report_day: String,
+}
+
+impl std::fmt::Debug for EmailSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmailSettings")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("user", &self.user)
+            .field("password", &String::from("******"))
+            .field("tls", &self.tls)
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .field("report_day", &self.report_day)
+            .finish()
+    }
+}
+
+impl EmailSettings {
+    pub fn is_empty(&self) -> bool {
+        self.host.trim().is_empty()
+    }
+
+    pub fn report_day(&self) -> color_eyre::Result<Option<u32>> {
+        if self.report_day.trim().is_empty() {
+            return Ok(None);
+        }
+        match self.report_day.trim().parse::<u32>() {
+            Ok(day) if (1..=28).contains(&day) => Ok(Some(day)),
+            _ => bail!(
+                "'{}' is not a day of the month between 1 and 28.",
+                self.report_day
+            ),
+        }
+    }
+
+    fn transport(&self) -> color_eyre::Result<AsyncSmtpTransport<Tokio1Executor>> {
+        let host = self.host.trim();
+        let mut builder = match self.tls.parse::<SmtpTls>()? {
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        if !self.port.trim().is_empty() {
+            let port = self
+                .port
+                .trim()
+                .parse::<u16>()
+                .map_err(|_| eyre!("'{}' is not a port.", self.port))?;
+            builder = builder.port(port);
+        }
+        if !self.user.is_empty() {
+            builder =
+                builder.credentials(Credentials::new(self.user.clone(), self.password.clone()));
+        }
+
+        Ok(builder.build())
+    }
+
+    /// The email with a plain text and an HTML version of the same content.
+    fn message(&self, subject: &str, text: &str, html: &str) -> color_eyre::Result<Message> {
+        let from = self
+            .from
+            .trim()
+            .parse::<Mailbox>()
+            .map_err(|err| eyre!("'{}' is not a valid sender: {}", self.from, err))?;
+        let mut builder = Message::builder().from(from).subject(subject);
+        let mut has_recipient = false;
+        for to in self
+            .to
+            .split(',')
+            .map(str::trim)
+            .filter(|to| !to.is_empty())
+        {
+            let to = to
+                .parse::<Mailbox>()
+                .map_err(|err| eyre!("'{}' is not a valid recipient: {}", to, err))?;
+            builder = builder.to(to);
+            has_recipient = true;
+        }
+        if !has_recipient {
+            bail!("No recipients, please set {}.", ENVVAR_EMAIL_TO);
+        }
+
+        Ok(builder.multipart(MultiPart::alternative_plain_html(
+            text.to_string(),
+            html.to_string(),
+        ))?)
+    }
+
+    /// Make sure the settings can be used, so a typo shows up before Jobcan is read.
+    pub fn validate(&self) -> color_eyre::Result<()> {
+        self.transport()
+            .wrap_err_with(|| format!("Please check {}.", ENVVAR_SMTP_HOST))?;
+        self.message("", "", "").wrap_err_with(|| {
+            format!(
+                "Please check {} and {}.",
+                ENVVAR_EMAIL_FROM, ENVVAR_EMAIL_TO
+            )
+        })?;
+        self.report_day()
+            .wrap_err_with(|| format!("Please check {}.", ENVVAR_EMAIL_REPORT_DAY))?;
+        Ok(())
+    }
+}
+
+pub async fn send(
+    settings: &EmailSettings,
+    subject: &str,
+    text: &str,
+    html: &str,
+) -> color_eyre::Result<()> {
+    if settings.is_empty() {
+        bail!("Email is not configured, please set {}.", ENVVAR_SMTP_HOST);
+    }
+    let message = settings.message(subject, text, html)?;
+    settings
+        .transport()?
+        .send(message)
+        .await
+        .wrap_err_with(|| format!("Could not send the email via {}", settings.host))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+
+    /// A local SMTP server that accepts one email and hands over what was sent after DATA.
+    async fn smtp_sink() -> (u16, oneshot::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = oneshot::channel();
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = socket.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+            let mut data = String::new();
+            let mut in_data = false;
+            while let Ok(Some(line)) = lines.next_line().await {
+                if in_data {
+                    if line == "." {
+                        in_data = false;
+                        writer.write_all(b"250 Queued\r\n").await.unwrap();
+                    } else {
+                        data.push_str(&line);
+                        data.push('\n');
+                    }
+                    continue;
+                }
+                let command = line.to_uppercase();
+                let reply: &[u8] = if command.starts_with("DATA") {
+                    in_data = true;
+                    b"354 Go ahead\r\n"
+                } else if command.starts_with("QUIT") {
+                    b"221 Bye\r\n"
+                } else {
+                    b"250 OK\r\n"
+                };
+                writer.write_all(reply).await.unwrap();
+                if command.starts_with("QUIT") {
+                    break;
+                }
+            }
+            sender.send(data).unwrap();
+        });
+        (port, receiver)
+    }
+
+    fn settings(port: u16) -> EmailSettings {
+        EmailSettings {
+            host: "127.0.0.1".into(),
+            port: port.to_string(),
+            tls: "none".into(),
+            from: "Jobcan Bot <bot@example.com>".into(),
+            to: "manager@example.com, lead@example.com".into(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let (port, received) = smtp_sink().await;
+
+        send(
+            &settings(port),
+            "Jobcan report 2021-10",
+            "Worked: 160:00",
+            "<p>Worked: 160:00</p>",
+        )
+        .await
+        .unwrap();
+
+        let data = received.await.unwrap();
+        assert!(data.contains("Subject: Jobcan report 2021-10"));
+        assert!(data.contains("To: manager@example.com, lead@example.com"));
+        assert!(data.contains("multipart/alternative"));
+        assert!(data.contains("text/plain"));
+        assert!(data.contains("Worked: 160:00"));
+        assert!(data.contains("text/html"));
+        assert!(data.contains("<p>Worked: 160:00</p>"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(settings(25).validate().is_ok());
+        assert!(EmailSettings {
+            tls: "ssl".into(),
+            ..settings(25)
+        }
+        .validate()
+        .is_err());
+        assert!(EmailSettings {
+            port: "smtp".into(),
+            ..settings(25)
+        }
+        .validate()
+        .is_err());
+        assert!(EmailSettings {
+            to: "".into(),
+            ..settings(25)
+        }
+        .validate()
+        .is_err());
+        assert!(EmailSettings {
+            from: "not an address".into(),
+            ..settings(25)
+        }
+        .validate()
+        .is_err());
+
+        let with_day = |day: &str| EmailSettings {
+            report_day: day.into(),
+            ..settings(25)
+        };
+        assert_eq!(Some(1), with_day("1").report_day().unwrap());
+        assert_eq!(None, with_day("").report_day().unwrap());
+        assert!(with_day("31").report_day().is_err());
+    }
+}
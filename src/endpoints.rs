@@ -0,0 +1,132 @@
+use chrono::prelude::*;
+
+pub const DEFAULT_ID_BASE_URL: &str = "https://id.jobcan.jp";
+pub const DEFAULT_SSL_BASE_URL: &str = "https://ssl.jobcan.jp";
+
+pub const PATH_SIGN_IN: &str = "/users/sign_in";
+pub const PATH_OAUTH_LOGIN: &str = "/jbcoauth/login";
+pub const PATH_EMPLOYEE: &str = "/employee";
+pub const PATH_ADIT_MODIFY: &str = "/employee/adit/modify";
+pub const PATH_ATTENDANCE: &str = "/employee/attendance";
+pub const PATH_RATE_LIMIT: &str = "/error/partial-rate-limit";
+
+/// All Jobcan URLs the bot navigates to.
+///
+/// By default they are built from the two Jobcan hosts (`JC_ID_BASE_URL` for the login, `JC_SSL_BASE_URL`
+/// for everything else). Every URL can also be overridden on its own, e.g. for a tenant with a custom login page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Endpoints {
+    /// The login form. Default: https://id.jobcan.jp/users/sign_in
+    pub sign_in: String,
+    /// Hands the login over from the ID site to the attendance site. Default: https://ssl.jobcan.jp/jbcoauth/login
+    pub oauth_login: String,
+    /// The page with the big "PUSH" button. Default: https://ssl.jobcan.jp/employee
+    pub employee: String,
+    /// The "revise clocking data" page. Default: https://ssl.jobcan.jp/employee/adit/modify
+    pub adit_modify: String,
+    /// The monthly attendance list. Default: https://ssl.jobcan.jp/employee/attendance
+    pub attendance: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Endpoints::new(DEFAULT_ID_BASE_URL, DEFAULT_SSL_BASE_URL)
+    }
+}
+
+impl Endpoints {
+    pub fn new(id_base_url: &str, ssl_base_url: &str) -> Self {
+        let id_base_url = id_base_url.trim_end_matches('/');
+        let ssl_base_url = ssl_base_url.trim_end_matches('/');
+
+        Endpoints {
+            sign_in: format!("{}{}", id_base_url, PATH_SIGN_IN),
+            oauth_login: format!("{}{}", ssl_base_url, PATH_OAUTH_LOGIN),
+            employee: format!("{}{}", ssl_base_url, PATH_EMPLOYEE),
+            adit_modify: format!("{}{}", ssl_base_url, PATH_ADIT_MODIFY),
+            attendance: format!("{}{}", ssl_base_url, PATH_ATTENDANCE),
+        }
+    }
+
+    /// The "revise clocking data" page for the given day.
+    pub fn adit_modify_for(&self, date: NaiveDate) -> String {
+        format!(
+            "{}?year={}&month={}&day={}",
+            self.adit_modify,
+            date.year(),
+            date.month(),
+            date.day()
+        )
+    }
+
+    /// The attendance list for the month of the given date.
+    pub fn attendance_for(&self, date: NaiveDate) -> String {
+        format!(
+            "{}?list_type=normal&search_type=month&year={}&month={}",
+            self.attendance,
+            date.year(),
+            date.month()
+        )
+    }
+
+    /// Jobcan redirects to an error page when we hit their rate limit.
+    pub fn is_rate_limited(&self, url: &str) -> bool {
+        url.contains(PATH_RATE_LIMIT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_endpoints() {
+        let endpoints = Endpoints::default();
+
+        assert_eq!("https://id.jobcan.jp/users/sign_in", endpoints.sign_in);
+        assert_eq!(
+            "https://ssl.jobcan.jp/jbcoauth/login",
+            endpoints.oauth_login
+        );
+        assert_eq!("https://ssl.jobcan.jp/employee", endpoints.employee);
+        assert_eq!(
+            "https://ssl.jobcan.jp/employee/adit/modify",
+            endpoints.adit_modify
+        );
+        assert_eq!(
+            "https://ssl.jobcan.jp/employee/attendance",
+            endpoints.attendance
+        );
+    }
+
+    #[test]
+    fn test_endpoints_ignore_trailing_slash() {
+        let endpoints = Endpoints::new("http://localhost:4567/", "http://localhost:4567/");
+
+        assert_eq!("http://localhost:4567/users/sign_in", endpoints.sign_in);
+        assert_eq!("http://localhost:4567/employee", endpoints.employee);
+    }
+
+    #[test]
+    fn test_endpoints_with_date() {
+        let endpoints = Endpoints::default();
+        let date = NaiveDate::from_ymd(2021, 10, 4);
+
+        assert_eq!(
+            "https://ssl.jobcan.jp/employee/adit/modify?year=2021&month=10&day=4",
+            endpoints.adit_modify_for(date)
+        );
+        assert_eq!(
+            "https://ssl.jobcan.jp/employee/attendance?list_type=normal&search_type=month&year=2021&month=10",
+            endpoints.attendance_for(date)
+        );
+    }
+
+    #[test]
+    fn test_is_rate_limited() {
+        let endpoints = Endpoints::default();
+
+        assert!(endpoints.is_rate_limited("https://ssl.jobcan.jp/error/partial-rate-limit"));
+        assert!(!endpoints.is_rate_limited("https://ssl.jobcan.jp/employee/attendance"));
+    }
+}
@@ -0,0 +1,201 @@
+use chrono::prelude::*;
+use log::info;
+use serde::Serialize;
+
+use crate::holidays::{last_day_of_month, HolidayCalendar};
+
+/// How the month is going: what is still required and where the current pace leads to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Forecast {
+    /// yyyy-mm
+    pub month: String,
+    pub required_minutes: u32,
+    pub worked_minutes: u32,
+    pub remaining_minutes: u32,
+    pub working_days: u32,
+    /// Working days before today.
+    pub elapsed_working_days: u32,
+    /// Working days from today (inclusive) until the end of the month.
+    pub remaining_working_days: u32,
+    /// `None` when there are no working days left.
+    pub daily_minutes_needed: Option<u32>,
+    pub projected_minutes: u32,
+    /// Negative when the current pace falls short of the required hours.
+    pub projected_overtime_minutes: i64,
+}
+
+impl Forecast {
+    /// `month` is any day of the month to forecast. The pace is the average worked time per elapsed working day,
+    /// or `daily_minutes` before the first working day is over.
+    pub fn new(
+        calendar: &HolidayCalendar,
+        month: NaiveDate,
+        today: NaiveDate,
+        worked_minutes: u32,
+        required_minutes: u32,
+        daily_minutes: u32,
+    ) -> Self {
+        let first_day = month.with_day(1).unwrap();
+        let last_day = last_day_of_month(month);
+        let working_days = calendar.working_days_between(first_day, last_day);
+        let remaining_working_days = if today > last_day {
+            0
+        } else {
+            calendar.working_days_between(today.max(first_day), last_day)
+        };
+        let elapsed_working_days = working_days - remaining_working_days;
+
+        let remaining_minutes = required_minutes.saturating_sub(worked_minutes);
+        let daily_minutes_needed = if remaining_working_days > 0 {
+            Some((remaining_minutes as f64 / remaining_working_days as f64).ceil() as u32)
+        } else {
+            None
+        };
+
+        let pace = if elapsed_working_days > 0 {
+            worked_minutes as f64 / elapsed_working_days as f64
+        } else {
+            daily_minutes as f64
+        };
+        let projected_minutes =
+            worked_minutes + (pace * remaining_working_days as f64).round() as u32;
+
+        Forecast {
+            month: first_day.format("%Y-%m").to_string(),
+            required_minutes,
+            worked_minutes,
+            remaining_minutes,
+            working_days,
+            elapsed_working_days,
+            remaining_working_days,
+            daily_minutes_needed,
+            projected_minutes,
+            projected_overtime_minutes: projected_minutes as i64 - required_minutes as i64,
+        }
+    }
+
+    pub fn print(&self) {
+        info!("Forecast for {}", self.month);
+        info!(
+            "Required      : {}",
+            format_minutes(self.required_minutes as i64)
+        );
+        info!(
+            "Worked        : {}",
+            format_minutes(self.worked_minutes as i64)
+        );
+        info!(
+            "Still required: {}",
+            format_minutes(self.remaining_minutes as i64)
+        );
+        info!(
+            "Working days  : {} left of {}",
+            self.remaining_working_days, self.working_days
+        );
+        match self.daily_minutes_needed {
+            Some(minutes) => info!("Needed per day: {}", format_minutes(minutes as i64)),
+            None => info!("Needed per day: -"),
+        }
+        info!(
+            "Projected     : {} (overtime: {})",
+            format_minutes(self.projected_minutes as i64),
+            format_minutes(self.projected_overtime_minutes)
+        );
+    }
+}
+
+/// hh:mm, with a leading "-" for negative values.
+pub fn format_minutes(minutes: i64) -> String {
+    let sign = if minutes < 0 { "-" } else { "" };
+    let minutes = minutes.abs();
+    format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_format_minutes() {
+        assert_eq!("160:00", format_minutes(160 * 60));
+        assert_eq!("00:05", format_minutes(5));
+        assert_eq!("-01:30", format_minutes(-90));
+    }
+
+    #[test]
+    fn test_forecast_mid_month() {
+        // November 2021 has 20 working days. Today is the 15th, a Monday: 9 working days are over.
+        let forecast = Forecast::new(
+            &HolidayCalendar::default(),
+            date("2021-11-01"),
+            date("2021-11-15"),
+            9 * 9 * 60,
+            160 * 60,
+            8 * 60,
+        );
+
+        assert_eq!("2021-11", forecast.month);
+        assert_eq!(20, forecast.working_days);
+        assert_eq!(9, forecast.elapsed_working_days);
+        assert_eq!(11, forecast.remaining_working_days);
+        assert_eq!(79 * 60, forecast.remaining_minutes);
+        assert_eq!(Some(431), forecast.daily_minutes_needed);
+        // 9 hours a day for 20 days
+        assert_eq!(180 * 60, forecast.projected_minutes);
+        assert_eq!(20 * 60, forecast.projected_overtime_minutes);
+    }
+
+    #[test]
+    fn test_forecast_start_of_month_uses_daily_minutes() {
+        let forecast = Forecast::new(
+            &HolidayCalendar::default(),
+            date("2021-11-01"),
+            date("2021-10-20"),
+            0,
+            160 * 60,
+            7 * 60,
+        );
+
+        assert_eq!(0, forecast.elapsed_working_days);
+        assert_eq!(20, forecast.remaining_working_days);
+        assert_eq!(140 * 60, forecast.projected_minutes);
+        assert_eq!(-20 * 60, forecast.projected_overtime_minutes);
+    }
+
+    #[test]
+    fn test_forecast_past_month() {
+        let forecast = Forecast::new(
+            &HolidayCalendar::default(),
+            date("2021-11-01"),
+            date("2021-12-03"),
+            150 * 60,
+            160 * 60,
+            8 * 60,
+        );
+
+        assert_eq!(0, forecast.remaining_working_days);
+        assert_eq!(None, forecast.daily_minutes_needed);
+        assert_eq!(-10 * 60, forecast.projected_overtime_minutes);
+    }
+
+    #[test]
+    fn test_forecast_as_json() {
+        let forecast = Forecast::new(
+            &HolidayCalendar::default(),
+            date("2021-11-01"),
+            date("2021-12-01"),
+            160 * 60,
+            160 * 60,
+            8 * 60,
+        );
+
+        assert_eq!(
+            r#"{"month":"2021-11","required_minutes":9600,"worked_minutes":9600,"remaining_minutes":0,"working_days":20,"elapsed_working_days":20,"remaining_working_days":0,"daily_minutes_needed":null,"projected_minutes":9600,"projected_overtime_minutes":0}"#,
+            serde_json::to_string(&forecast).unwrap()
+        );
+    }
+}
@@ -0,0 +1,453 @@
+//! Offline calendar of Japanese national holidays (国民の祝日) plus company-specific days off.
+//!
+//! The rules follow the "Act on National Holidays" as amended up to 2021: the holidays moved to Mondays in 2000
+//! and 2003 (Happy Monday), the substitute holiday rule of 2007, the imperial one-off days of 1989, 1990, 1993
+//! and 2019 and the changes for the Tokyo Olympics in 2020/2021. The equinox days are calculated with the usual
+//! approximation, which is accurate for 1980 to 2099, so that is the range the calendar is correct for.
+use chrono::prelude::*;
+use chrono::Duration;
+use color_eyre::eyre::eyre;
+
+pub const SUBSTITUTE_HOLIDAY: &str = "振替休日";
+pub const CITIZENS_HOLIDAY: &str = "国民の休日";
+pub const COMPANY_HOLIDAY: &str = "会社休日";
+
+/// A company-specific day off, either every year ("12-29") or on a specific date ("2021-12-29").
+#[derive(Debug, Clone, PartialEq)]
+enum ExtraDayOff {
+    Yearly(u32, u32),
+    Date(NaiveDate),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HolidayCalendar {
+    extra_days_off: Vec<ExtraDayOff>,
+}
+
+impl HolidayCalendar {
+    /// Create a calendar with additional days off, given as a comma separated list of
+    /// "yyyy-mm-dd" (once) or "mm-dd" (every year) entries.
+    pub fn new(extra_days_off: &str) -> color_eyre::Result<Self> {
+        let mut calendar = HolidayCalendar::default();
+        for entry in extra_days_off
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            let day_off = if let Ok(date) = NaiveDate::parse_from_str(entry, "%Y-%m-%d") {
+                ExtraDayOff::Date(date)
+            } else {
+                let (month, day) = entry
+                    .split_once('-')
+                    .and_then(|(m, d)| Some((m.parse::<u32>().ok()?, d.parse::<u32>().ok()?)))
+                    .filter(|(m, d)| NaiveDate::from_ymd_opt(2020, *m, *d).is_some())
+                    .ok_or_else(|| {
+                        eyre!(
+                            "The day off '{}' has a wrong format. Use yyyy-mm-dd or mm-dd.",
+                            entry
+                        )
+                    })?;
+                ExtraDayOff::Yearly(month, day)
+            };
+            calendar.extra_days_off.push(day_off);
+        }
+
+        Ok(calendar)
+    }
+
+    /// The name of the national holiday or company day off on the given date.
+    pub fn holiday_name(&self, date: NaiveDate) -> Option<&'static str> {
+        national_holiday(date).or_else(|| {
+            self.extra_days_off
+                .iter()
+                .any(|day_off| match day_off {
+                    ExtraDayOff::Yearly(month, day) => date.month() == *month && date.day() == *day,
+                    ExtraDayOff::Date(day_off) => *day_off == date,
+                })
+                .then_some(COMPANY_HOLIDAY)
+        })
+    }
+
+    pub fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.holiday_name(date).is_some()
+    }
+
+    /// Monday to Friday, and not a holiday.
+    pub fn is_working_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.is_holiday(date)
+    }
+
+    /// Number of working days in `from..=to`.
+    pub fn working_days_between(&self, from: NaiveDate, to: NaiveDate) -> u32 {
+        let mut count = 0;
+        let mut date = from;
+        while date <= to {
+            if self.is_working_day(date) {
+                count += 1;
+            }
+            date = date.succ();
+        }
+        count
+    }
+
+    /// Holidays and company days off in `from..=to` that fall on a weekday.
+    pub fn weekday_holidays_between(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Vec<(NaiveDate, &'static str)> {
+        let mut holidays = vec![];
+        let mut date = from;
+        while date <= to {
+            if !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+                if let Some(name) = self.holiday_name(date) {
+                    holidays.push((date, name));
+                }
+            }
+            date = date.succ();
+        }
+        holidays
+    }
+}
+
+pub fn last_day_of_month(date: NaiveDate) -> NaiveDate {
+    let (year, month) = if date.month() == 12 {
+        (date.year() + 1, 1)
+    } else {
+        (date.year(), date.month() + 1)
+    };
+    NaiveDate::from_ymd(year, month, 1).pred()
+}
+
+/// The name of the Japanese national holiday on the given date, if any.
+pub fn national_holiday(date: NaiveDate) -> Option<&'static str> {
+    national_holidays(date.year())
+        .into_iter()
+        .find(|(holiday, _)| *holiday == date)
+        .map(|(_, name)| name)
+}
+
+/// All national holidays of the given year, including substitute holidays and citizens' holidays.
+pub fn national_holidays(year: i32) -> Vec<(NaiveDate, &'static str)> {
+    let mut holidays = fixed_holidays(year);
+    holidays.sort_by_key(|(date, _)| *date);
+
+    // NOTE(dkg): Until 2006 only the Monday after a Sunday holiday was a substitute, and it took precedence over
+    // a citizens' holiday (e.g. 1998-05-04). Since 2007 it is the next day that is not a holiday at all.
+    if year >= 2007 {
+        add_citizens_holidays(&mut holidays);
+        add_substitute_holidays(&mut holidays);
+    } else {
+        add_substitute_holidays(&mut holidays);
+        if year >= 1986 {
+            add_citizens_holidays(&mut holidays);
+        }
+    }
+
+    holidays
+}
+
+/// 国民の休日: a weekday sandwiched between two holidays.
+fn add_citizens_holidays(holidays: &mut Vec<(NaiveDate, &'static str)>) {
+    let mut citizens_holidays = vec![];
+    for pair in holidays.windows(2) {
+        let between = pair[0].0 + Duration::days(1);
+        if pair[1].0 - pair[0].0 == Duration::days(2) && between.weekday() != Weekday::Sun {
+            citizens_holidays.push((between, CITIZENS_HOLIDAY));
+        }
+    }
+    holidays.extend(citizens_holidays);
+    holidays.sort_by_key(|(date, _)| *date);
+}
+
+/// 振替休日: a holiday on a Sunday moves to the next day that is not a holiday.
+fn add_substitute_holidays(holidays: &mut Vec<(NaiveDate, &'static str)>) {
+    let mut substitute_holidays = vec![];
+    for (date, _) in holidays.iter().filter(|(d, _)| d.weekday() == Weekday::Sun) {
+        let mut substitute = *date + Duration::days(1);
+        while holidays.iter().any(|(d, _)| *d == substitute) {
+            substitute += Duration::days(1);
+        }
+        substitute_holidays.push((substitute, SUBSTITUTE_HOLIDAY));
+    }
+    holidays.extend(substitute_holidays);
+    holidays.sort_by_key(|(date, _)| *date);
+}
+
+fn fixed_holidays(year: i32) -> Vec<(NaiveDate, &'static str)> {
+    let ymd = |month, day| NaiveDate::from_ymd(year, month, day);
+    let nth_monday = |month, n| NaiveDate::from_weekday_of_month(year, month, Weekday::Mon, n);
+
+    // NOTE(dkg): Happy Monday: 成人の日 and 体育の日 moved to Mondays in 2000, 海の日 and 敬老の日 in 2003.
+    let mut holidays = vec![
+        (ymd(1, 1), "元日"),
+        (
+            if year >= 2000 {
+                nth_monday(1, 2)
+            } else {
+                ymd(1, 15)
+            },
+            "成人の日",
+        ),
+        (ymd(2, 11), "建国記念の日"),
+        (ymd(3, vernal_equinox_day(year)), "春分の日"),
+        (ymd(5, 3), "憲法記念日"),
+        (ymd(5, 5), "こどもの日"),
+        (
+            if year >= 2003 {
+                nth_monday(9, 3)
+            } else {
+                ymd(9, 15)
+            },
+            "敬老の日",
+        ),
+        (ymd(9, autumnal_equinox_day(year)), "秋分の日"),
+        (ymd(11, 3), "文化の日"),
+        (ymd(11, 23), "勤労感謝の日"),
+    ];
+
+    match year {
+        y if y >= 2007 => {
+            holidays.push((ymd(4, 29), "昭和の日"));
+            holidays.push((ymd(5, 4), "みどりの日"));
+        }
+        y if y >= 1989 => holidays.push((ymd(4, 29), "みどりの日")),
+        _ => holidays.push((ymd(4, 29), "天皇誕生日")),
+    }
+
+    match year {
+        2020 => {
+            holidays.push((ymd(7, 23), "海の日"));
+            holidays.push((ymd(7, 24), "スポーツの日"));
+            holidays.push((ymd(8, 10), "山の日"));
+        }
+        2021 => {
+            holidays.push((ymd(7, 22), "海の日"));
+            holidays.push((ymd(7, 23), "スポーツの日"));
+            holidays.push((ymd(8, 8), "山の日"));
+        }
+        _ => {
+            match year {
+                y if y >= 2003 => holidays.push((nth_monday(7, 3), "海の日")),
+                y if y >= 1996 => holidays.push((ymd(7, 20), "海の日")),
+                _ => (),
+            }
+            if year >= 2016 {
+                holidays.push((ymd(8, 11), "山の日"));
+            }
+            match year {
+                y if y >= 2020 => holidays.push((nth_monday(10, 2), "スポーツの日")),
+                y if y >= 2000 => holidays.push((nth_monday(10, 2), "体育の日")),
+                _ => holidays.push((ymd(10, 10), "体育の日")),
+            }
+        }
+    }
+
+    match year {
+        1989 => {
+            holidays.push((ymd(2, 24), "昭和天皇の大喪の礼"));
+            holidays.push((ymd(12, 23), "天皇誕生日"));
+        }
+        1990 => {
+            holidays.push((ymd(11, 12), "即位礼正殿の儀"));
+            holidays.push((ymd(12, 23), "天皇誕生日"));
+        }
+        1993 => {
+            holidays.push((ymd(6, 9), "皇太子徳仁親王の結婚の儀"));
+            holidays.push((ymd(12, 23), "天皇誕生日"));
+        }
+        2019 => {
+            holidays.push((ymd(5, 1), "即位の日"));
+            holidays.push((ymd(10, 22), "即位礼正殿の儀の行われる日"));
+        }
+        y if y >= 2020 => holidays.push((ymd(2, 23), "天皇誕生日")),
+        y if y >= 1989 => holidays.push((ymd(12, 23), "天皇誕生日")),
+        _ => (),
+    }
+
+    holidays
+}
+
+fn equinox_day(year: i32, base: f64) -> u32 {
+    let years = (year - 1980) as f64;
+    (base + 0.242194 * years - (years / 4.0).floor()).floor() as u32
+}
+
+fn vernal_equinox_day(year: i32) -> u32 {
+    equinox_day(year, 20.8431)
+}
+
+fn autumnal_equinox_day(year: i32) -> u32 {
+    equinox_day(year, 23.2488)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn holiday_dates(year: i32) -> Vec<String> {
+        national_holidays(year)
+            .into_iter()
+            .map(|(d, _)| d.format("%m-%d").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_holidays_2021() {
+        assert_eq!(
+            vec![
+                "01-01", "01-11", "02-11", "02-23", "03-20", "04-29", "05-03", "05-04", "05-05",
+                "07-22", "07-23", "08-08", "08-09", "09-20", "09-23", "11-03", "11-23"
+            ],
+            holiday_dates(2021)
+        );
+    }
+
+    #[test]
+    fn test_holidays_before_happy_monday() {
+        // 成人の日, 敬老の日 and 体育の日 on fixed dates, 2 substitute holidays
+        assert_eq!(
+            vec![
+                "01-01", "01-15", "02-11", "03-21", "03-22", "04-29", "05-03", "05-04", "05-05",
+                "07-20", "09-15", "09-23", "10-10", "10-11", "11-03", "11-23", "12-23"
+            ],
+            holiday_dates(1999)
+        );
+        assert_eq!(Some("成人の日"), national_holiday(date("2000-01-10")));
+        assert_eq!(Some("体育の日"), national_holiday(date("2000-10-09")));
+        assert_eq!(Some("海の日"), national_holiday(date("2002-07-20")));
+        assert_eq!(Some("敬老の日"), national_holiday(date("2002-09-15")));
+        assert_eq!(Some("海の日"), national_holiday(date("2003-07-21")));
+        assert_eq!(Some("敬老の日"), national_holiday(date("2003-09-15")));
+        assert_eq!(None, national_holiday(date("1995-07-20")));
+    }
+
+    #[test]
+    fn test_holidays_before_2007() {
+        // 1998-05-03 憲法記念日 is a Sunday: the Monday is a substitute, not a citizens' holiday
+        assert_eq!(
+            Some(SUBSTITUTE_HOLIDAY),
+            national_holiday(date("1998-05-04"))
+        );
+        assert_eq!(None, national_holiday(date("1998-05-06")));
+        assert_eq!(Some("天皇誕生日"), national_holiday(date("1988-04-29")));
+        assert_eq!(Some("みどりの日"), national_holiday(date("1989-04-29")));
+        assert_eq!(
+            Some("昭和天皇の大喪の礼"),
+            national_holiday(date("1989-02-24"))
+        );
+        assert_eq!(None, national_holiday(date("1988-12-23")));
+        // No citizens' holiday before 1986
+        assert_eq!(None, national_holiday(date("1984-05-04")));
+    }
+
+    #[test]
+    fn test_holidays_2019_enthronement() {
+        assert_eq!(Some("即位の日"), national_holiday(date("2019-05-01")));
+        assert_eq!(Some(CITIZENS_HOLIDAY), national_holiday(date("2019-04-30")));
+        assert_eq!(Some(CITIZENS_HOLIDAY), national_holiday(date("2019-05-02")));
+        assert_eq!(
+            Some(SUBSTITUTE_HOLIDAY),
+            national_holiday(date("2019-05-06"))
+        );
+        assert_eq!(None, national_holiday(date("2019-12-23")));
+        assert_eq!(None, national_holiday(date("2019-02-23")));
+    }
+
+    #[test]
+    fn test_substitute_holidays() {
+        // 2020-02-23 天皇誕生日 is a Sunday
+        assert_eq!(
+            Some(SUBSTITUTE_HOLIDAY),
+            national_holiday(date("2020-02-24"))
+        );
+        // 2024-09-22 秋分の日 is a Sunday
+        assert_eq!(Some("秋分の日"), national_holiday(date("2024-09-22")));
+        assert_eq!(
+            Some(SUBSTITUTE_HOLIDAY),
+            national_holiday(date("2024-09-23"))
+        );
+        // 2020-05-03 憲法記念日 is a Sunday, 05-04 and 05-05 are holidays as well
+        assert_eq!(
+            Some(SUBSTITUTE_HOLIDAY),
+            national_holiday(date("2020-05-06"))
+        );
+    }
+
+    #[test]
+    fn test_citizens_holiday_in_september() {
+        // 2026: 敬老の日 on 09-21, 秋分の日 on 09-23
+        assert_eq!(Some("敬老の日"), national_holiday(date("2026-09-21")));
+        assert_eq!(Some(CITIZENS_HOLIDAY), national_holiday(date("2026-09-22")));
+        assert_eq!(Some("秋分の日"), national_holiday(date("2026-09-23")));
+    }
+
+    #[test]
+    fn test_equinox_days() {
+        assert_eq!(20, vernal_equinox_day(2024));
+        assert_eq!(20, vernal_equinox_day(2025));
+        assert_eq!(21, vernal_equinox_day(2010));
+        assert_eq!(22, autumnal_equinox_day(2024));
+        assert_eq!(23, autumnal_equinox_day(2025));
+        assert_eq!(22, autumnal_equinox_day(2012));
+    }
+
+    #[test]
+    fn test_company_holidays() {
+        let calendar = HolidayCalendar::new("12-29, 12-30,2021-08-13").unwrap();
+
+        assert_eq!(
+            Some(COMPANY_HOLIDAY),
+            calendar.holiday_name(date("2021-12-29"))
+        );
+        assert_eq!(
+            Some(COMPANY_HOLIDAY),
+            calendar.holiday_name(date("2030-12-30"))
+        );
+        assert_eq!(
+            Some(COMPANY_HOLIDAY),
+            calendar.holiday_name(date("2021-08-13"))
+        );
+        assert_eq!(None, calendar.holiday_name(date("2022-08-13")));
+        assert_eq!(Some("元日"), calendar.holiday_name(date("2022-01-01")));
+    }
+
+    #[test]
+    fn test_company_holidays_wrong_format() {
+        assert!(HolidayCalendar::new("12/29").is_err());
+        assert!(HolidayCalendar::new("02-30").is_err());
+        assert!(HolidayCalendar::new("").unwrap().extra_days_off.is_empty());
+    }
+
+    #[test]
+    fn test_working_days() {
+        let calendar = HolidayCalendar::default();
+
+        assert!(calendar.is_working_day(date("2021-10-04")));
+        assert!(!calendar.is_working_day(date("2021-10-09")));
+        assert!(!calendar.is_working_day(date("2021-11-03")));
+        // November 2021: 22 weekdays, minus 文化の日 (Wed) and 勤労感謝の日 (Tue)
+        assert_eq!(
+            20,
+            calendar.working_days_between(date("2021-11-01"), date("2021-11-30"))
+        );
+        assert_eq!(
+            vec![
+                (date("2021-11-03"), "文化の日"),
+                (date("2021-11-23"), "勤労感謝の日")
+            ],
+            calendar.weekday_holidays_between(date("2021-11-01"), date("2021-11-30"))
+        );
+    }
+
+    #[test]
+    fn test_last_day_of_month() {
+        assert_eq!(date("2021-02-28"), last_day_of_month(date("2021-02-01")));
+        assert_eq!(date("2024-02-29"), last_day_of_month(date("2024-02-10")));
+        assert_eq!(date("2021-12-31"), last_day_of_month(date("2021-12-01")));
+    }
+}
@@ -0,0 +1,303 @@
+//! Lenient date and time input for the command line, e.g. "yesterday", "mon", "10/14", "9:30" or "now-15m".
+use chrono::prelude::*;
+use chrono::Duration;
+use color_eyre::eyre::{bail, eyre};
+
+use crate::clock::ClockTime;
+
+/// Jobcan accepts times up to 26:00 (2am of the next day) for a working day.
+pub const LATEST_JOBCAN_MINUTES: u32 = 26 * 60;
+
+/// Relative times can't go further than this from now, Jobcan's working day is at most 26 hours anyway.
+pub const MAX_OFFSET_MINUTES: i64 = 3 * 24 * 60;
+
+/// A time as given on the command line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeInput {
+    /// A time of the day, e.g. "9:30" or "2600".
+    At(ClockTime),
+    /// Minutes relative to now, e.g. "now-15m" is -15.
+    FromNow(i64),
+}
+
+/// Parse a date: "today", "yesterday", a weekday ("mon", "friday", "月"), "mm/dd", "yyyy-mm-dd" or "yyyy/mm/dd".
+/// Weekdays and dates without a year are taken from the past, since only past days can be revised.
+pub fn parse_date(input: &str, today: NaiveDate) -> color_eyre::Result<NaiveDate> {
+    let input = input.trim();
+    let lower = input.to_lowercase();
+
+    match lower.as_str() {
+        "today" | "今日" => return Ok(today),
+        "yesterday" | "昨日" => return Ok(today.pred()),
+        _ => (),
+    }
+
+    if let Some(weekday) = parse_weekday(&lower) {
+        let days_back =
+            (7 + today.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+        return Ok(today - Duration::days(days_back as i64));
+    }
+
+    for format in &["%Y-%m-%d", "%Y/%m/%d", "%Y%m%d"] {
+        if let Ok(date) = NaiveDate::parse_from_str(input, format) {
+            return Ok(date);
+        }
+    }
+
+    if let Some((month, day)) = input.split_once('/') {
+        if let (Ok(month), Ok(day)) = (month.parse::<u32>(), day.parse::<u32>()) {
+            let this_year = NaiveDate::from_ymd_opt(today.year(), month, day);
+            let date = match this_year {
+                Some(date) if date <= today => Some(date),
+                _ => NaiveDate::from_ymd_opt(today.year() - 1, month, day),
+            };
+            if let Some(date) = date {
+                return Ok(date);
+            }
+        }
+    }
+
+    bail!(
+        "Unable to understand the date '{}'. Use e.g. yesterday, mon, 10/14 or 2021-10-14.",
+        input
+    )
+}
+
+fn parse_weekday(input: &str) -> Option<Weekday> {
+    const JAPANESE: [(&str, Weekday); 7] = [
+        ("月", Weekday::Mon),
+        ("火", Weekday::Tue),
+        ("水", Weekday::Wed),
+        ("木", Weekday::Thu),
+        ("金", Weekday::Fri),
+        ("土", Weekday::Sat),
+        ("日", Weekday::Sun),
+    ];
+
+    if let Ok(weekday) = input.parse::<Weekday>() {
+        return Some(weekday);
+    }
+    let kanji = input
+        .strip_suffix("曜日")
+        .or_else(|| input.strip_suffix('曜'))
+        .unwrap_or(input);
+    JAPANESE
+        .iter()
+        .find(|(name, _)| *name == kanji)
+        .map(|(_, weekday)| *weekday)
+}
+
+/// Parse a time: "9:30", "0930", "26:00", "now", "now-15m", "now-1h30m" or "now+5m".
+pub fn parse_time(input: &str) -> color_eyre::Result<TimeInput> {
+    let input = input.trim();
+    let lower = input.to_lowercase();
+
+    if let Some(offset) = lower.strip_prefix("now") {
+        let offset = offset.trim();
+        if offset.is_empty() {
+            return Ok(TimeInput::FromNow(0));
+        }
+        let (sign, amount) = match (offset.strip_prefix('-'), offset.strip_prefix('+')) {
+            (Some(amount), _) => (-1, amount.trim()),
+            (_, Some(amount)) => (1, amount.trim()),
+            _ => bail!("Unable to understand the time '{}'.", input),
+        };
+        // NOTE(dkg): Only digits after the sign, so "now-" and "now--5" are no valid offsets.
+        let minutes = Some(amount)
+            .filter(|amount| amount.starts_with(|c: char| c.is_ascii_digit()))
+            .and_then(parse_offset_minutes)
+            .filter(|minutes| *minutes <= MAX_OFFSET_MINUTES)
+            .ok_or_else(|| {
+                eyre!(
+                    "Unable to understand the time '{}'. Use e.g. now-15m or now-1h30m.",
+                    input
+                )
+            })?;
+        return Ok(TimeInput::FromNow(sign * minutes));
+    }
+
+    input.parse::<ClockTime>().map(TimeInput::At).map_err(|_| {
+        eyre!(
+            "The time has a wrong format. Use e.g. 9:30, 0930, 18:05 or now-15m, got '{}'.",
+            input
+        )
+    })
+}
+
+/// "15m", "1h", "1h30m" or just "15" (minutes). None also when the minutes don't fit into an i64.
+pub fn parse_offset_minutes(input: &str) -> Option<i64> {
+    if let Ok(minutes) = input.parse::<i64>() {
+        return Some(minutes);
+    }
+
+    let mut total: i64 = 0;
+    let mut number = String::new();
+    for c in input.chars() {
+        let minutes = match c {
+            '0'..='9' => {
+                number.push(c);
+                continue;
+            }
+            'h' => number.parse::<i64>().ok()?.checked_mul(60)?,
+            'm' => number.parse::<i64>().ok()?,
+            _ => return None,
+        };
+        total = total.checked_add(minutes)?;
+        number.clear();
+    }
+
+    if number.is_empty() {
+        Some(total)
+    } else {
+        None
+    }
+}
+
+/// The working day and the time on Jobcan's clock for the given input. A time after midnight of the given date
+/// becomes 24:00 or later, e.g. "now" at 1:30am with yesterday's date is 25:30.
+pub fn resolve(
+    date: Option<NaiveDate>,
+    time: TimeInput,
+    now: NaiveDateTime,
+) -> color_eyre::Result<(NaiveDate, ClockTime)> {
+    let (date, minutes) = match time {
+        TimeInput::At(time) => (date.unwrap_or_else(|| now.date()), time.minutes() as i64),
+        TimeInput::FromNow(offset) => {
+            let at = Some(offset)
+                .filter(|offset| (-MAX_OFFSET_MINUTES..=MAX_OFFSET_MINUTES).contains(offset))
+                .and_then(|offset| now.checked_add_signed(Duration::minutes(offset)))
+                .ok_or_else(|| eyre!("The time is too far away from now."))?;
+            let date = date.unwrap_or_else(|| at.date());
+            (date, (at - date.and_hms(0, 0, 0)).num_minutes())
+        }
+    };
+
+    if minutes < 0 || minutes > LATEST_JOBCAN_MINUTES as i64 {
+        bail!(
+            "The time is not on {}. Jobcan accepts times between 00:00 and 26:00 (2am of the next day).",
+            date
+        );
+    }
+    let time = ClockTime::from_minutes(minutes as u32)
+        .ok_or_else(|| eyre!("The time is out of range."))?;
+
+    Ok((date, time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn time(s: &str) -> ClockTime {
+        s.parse().unwrap()
+    }
+
+    // 2021-10-14 is a Thursday
+    const TODAY: &str = "2021-10-14";
+
+    #[test]
+    fn test_parse_date_keywords() {
+        let today = date(TODAY);
+
+        assert_eq!(today, parse_date("today", today).unwrap());
+        assert_eq!(date("2021-10-13"), parse_date("Yesterday", today).unwrap());
+        assert_eq!(date("2021-10-13"), parse_date("昨日", today).unwrap());
+    }
+
+    #[test]
+    fn test_parse_date_weekdays() {
+        let today = date(TODAY);
+
+        assert_eq!(date("2021-10-11"), parse_date("mon", today).unwrap());
+        assert_eq!(date("2021-10-14"), parse_date("thu", today).unwrap());
+        assert_eq!(date("2021-10-08"), parse_date("Friday", today).unwrap());
+        assert_eq!(date("2021-10-11"), parse_date("月", today).unwrap());
+        assert_eq!(date("2021-10-12"), parse_date("火曜日", today).unwrap());
+        assert_eq!(date("2021-10-10"), parse_date("日曜", today).unwrap());
+    }
+
+    #[test]
+    fn test_parse_date_formats() {
+        let today = date(TODAY);
+
+        assert_eq!(date("2021-10-01"), parse_date("2021-10-01", today).unwrap());
+        assert_eq!(date("2021-10-01"), parse_date("2021/10/01", today).unwrap());
+        assert_eq!(date("2021-10-01"), parse_date("20211001", today).unwrap());
+        assert_eq!(date("2021-10-01"), parse_date("10/1", today).unwrap());
+        // Later this year: must be last year
+        assert_eq!(date("2020-12-24"), parse_date("12/24", today).unwrap());
+        assert!(parse_date("someday", today).is_err());
+        assert!(parse_date("13/01", today).is_err());
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(TimeInput::At(time("09:30")), parse_time("9:30").unwrap());
+        assert_eq!(TimeInput::At(time("18:05")), parse_time("1805").unwrap());
+        assert_eq!(TimeInput::FromNow(0), parse_time("now").unwrap());
+        assert_eq!(TimeInput::FromNow(-15), parse_time("now-15m").unwrap());
+        assert_eq!(TimeInput::FromNow(-90), parse_time("now - 1h30m").unwrap());
+        assert_eq!(TimeInput::FromNow(5), parse_time("now+5").unwrap());
+        assert!(parse_time("now*5").is_err());
+        assert!(parse_time("now-5x").is_err());
+        assert!(parse_time("now-").is_err());
+        assert!(parse_time("now--5").is_err());
+        assert!(parse_time("now+-5").is_err());
+        assert!(parse_time("now－15m").is_err());
+        assert!(parse_time("nowあ").is_err());
+        assert!(parse_time("half past nine").is_err());
+        assert!(parse_time("now-99999999999999").is_err());
+        assert!(parse_time("now-1000000000000000000h").is_err());
+        assert!(parse_time("now-153722867280912930").is_err());
+        assert!(parse_time("now-9223372036854775807m1m").is_err());
+        assert!(parse_time("now-4321").is_err());
+        assert_eq!(
+            TimeInput::FromNow(-MAX_OFFSET_MINUTES),
+            parse_time("now-72h").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let now = at("2021-10-14 18:20");
+
+        assert_eq!(
+            (date("2021-10-14"), time("18:05")),
+            resolve(None, TimeInput::FromNow(-15), now).unwrap()
+        );
+        assert_eq!(
+            (date("2021-10-13"), time("09:30")),
+            resolve(Some(date("2021-10-13")), TimeInput::At(time("9:30")), now).unwrap()
+        );
+        assert!(resolve(None, TimeInput::At(time("27:00")), now).is_err());
+        assert!(resolve(None, TimeInput::FromNow(-99999999999999), now).is_err());
+        assert!(resolve(None, TimeInput::FromNow(i64::MIN), now).is_err());
+    }
+
+    #[test]
+    fn test_resolve_after_midnight() {
+        let now = at("2021-10-15 01:30");
+
+        // For yesterday's working day this is 25:30
+        let (date_for_jobcan, time_for_jobcan) =
+            resolve(Some(date("2021-10-14")), TimeInput::FromNow(0), now).unwrap();
+        assert_eq!(date("2021-10-14"), date_for_jobcan);
+        assert_eq!("2530", time_for_jobcan.to_jobcan());
+
+        // Crossing midnight backwards picks the previous day
+        assert_eq!(
+            (date("2021-10-14"), time("23:50")),
+            resolve(None, TimeInput::FromNow(-100), now).unwrap()
+        );
+
+        assert!(resolve(Some(date("2021-10-13")), TimeInput::FromNow(0), now).is_err());
+    }
+}
@@ -0,0 +1,722 @@
+use chrono::prelude::*;
+use color_eyre::eyre::bail;
+use log::{debug, warn};
+use std::io::Write;
+use std::{env, fs, thread, time};
+use thirtyfour::components::select::SelectElement;
+use thirtyfour::prelude::*;
+use thirtyfour::{common::command::Command, extensions::chrome::ChromeDevTools};
+
+use crate::clock::WorkDuration;
+use crate::config::Configuration;
+use crate::endpoints::Endpoints;
+use crate::reconcile::DayRecord;
+use crate::status::{Punch, WorkingStatus};
+
+/// The buttons on the page with the big "PUSH" button.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PunchKind {
+    /// Clock in or out, Jobcan toggles between the two.
+    Work,
+    /// 休憩開始. Only available when the tenant enabled break punching.
+    BreakStart,
+    /// 休憩終了. Only available when the tenant enabled break punching.
+    BreakEnd,
+}
+
+impl PunchKind {
+    fn button_id(&self) -> &'static str {
+        match self {
+            PunchKind::Work => "adit-button-push",
+            PunchKind::BreakStart => "adit-button-rest-start",
+            PunchKind::BreakEnd => "adit-button-rest-end",
+        }
+    }
+}
+
+// Attendance page (出勤簿): the tables are looked up by index.
+pub const INDEX_FOR_TABLE_WITH_WORKING_HOURS: usize = 3;
+pub const INDEX_FOR_TABLE_WITH_VACATION_DATA: usize = 4;
+pub const INDEX_FOR_TABLE_WITH_PUNCHED_DATA: usize = 6;
+pub const COLUMN_DATE: usize = 0;
+pub const COLUMN_HOLIDAY: usize = 1;
+pub const COLUMN_START_TIME: usize = 2;
+pub const COLUMN_END_TIME: usize = 3;
+pub const COLUMN_BREAK_TIME: usize = 4;
+pub const COLUMNS_COUNT: usize = 5;
+
+pub const INDEX_FOR_TABLE_WITH_CURRENT_TOTALS: usize = 3;
+pub const ROW_WITH_WORKED_HOURS_SO_FAR: usize = 0; // 1st row: 実労働時間
+pub const ROW_WITH_WORKED_TIME_EXPECTED: usize = 1; // 2nd row: 月規定労働時間
+pub const ROW_WITH_OVERTIME: usize = 2; // 3rd row: 残業時間
+
+/// The group (e.g. office, remote, client site) select box next to the "PUSH" button.
+const ID_SELECT_GROUP: &str = "adit_group_id";
+
+/// File in the state directory that holds the cookies of the last successful login.
+const SESSION_FILE: &str = "session.json";
+
+// "Revise clocking data" page: the table with the punches of the selected day.
+const ID_TABLE_WITH_PUNCHES: &str = "logs-table";
+const COLUMN_PUNCH_TIME: usize = 0;
+const COLUMN_PUNCH_KIND: usize = 1;
+const COLUMN_PUNCH_NOTICE: usize = 2;
+const PUNCH_COLUMNS_COUNT: usize = 3;
+
+/// How long to wait for Jobcan to show the new working status after a punch.
+const VERIFY_PUNCH_ATTEMPTS: u32 = 15;
+const VERIFY_PUNCH_INTERVAL_MILLIS: u64 = 2000;
+
+pub async fn start_webdriver(visible: bool) -> color_eyre::Result<WebDriver> {
+    debug!("Starting WebDriver ...");
+
+    let mut caps = DesiredCapabilities::chrome();
+    if !visible {
+        caps.set_headless()?;
+    }
+
+    // TODO(dkg): consider starting up Chromedriver manually here in a separate thread
+    let chromedriver_host =
+        env::var("CHROMEDRIVER_HOST").unwrap_or_else(|_| "http://localhost".into());
+    let chromedriver_port = env::var("CHROMEDRIVER_PORT")
+        .unwrap_or_else(|_| "4444".into())
+        .parse::<i32>()
+        .unwrap_or(4444);
+    let chromedriver_url = format!("{}:{}", chromedriver_host, chromedriver_port);
+    let driver = WebDriver::new(&chromedriver_url, &caps).await?;
+
+    let dev_tools = ChromeDevTools::new(driver.session());
+    let version_info = dev_tools.execute_cdp("Browser.getVersion").await?;
+
+    debug!("Using Chrome Version: {:?}", version_info);
+
+    Ok(driver)
+}
+
+/// Log into Jobcan. Reuses the cookies of the last login if they are still valid, otherwise
+/// goes through the login form and stores the new session for the next run.
+pub async fn login(driver: &WebDriver, config: &Configuration) -> color_eyre::Result<()> {
+    match restore_session(driver, config).await {
+        Ok(true) => {
+            debug!("Reusing the cached Jobcan session.");
+            return Ok(());
+        }
+        Ok(false) => debug!("No valid cached Jobcan session, logging in ..."),
+        Err(err) => warn!("Could not restore the cached Jobcan session: {}", err),
+    }
+
+    // Login via https://id.jobcan.jp/users/sign_in (or whatever JC_SIGN_IN_URL points to)
+    driver.get(&config.endpoints.sign_in).await?;
+
+    let elem_form = driver.find_element(By::ClassName("form")).await?;
+
+    // Find login input box and type in the user's login
+    let elem_login = elem_form.find_element(By::Id("user_email")).await?;
+    elem_login.send_keys(&config.login).await?;
+
+    // Find password input box and type in the user's password
+    let elem_password = elem_form.find_element(By::Id("user_password")).await?;
+    elem_password.send_keys(&config.password).await?;
+
+    // Click the login button
+    let elem_button = elem_form.find_element(By::ClassName("form__login")).await?;
+    elem_button.click().await?;
+
+    thread::sleep(time::Duration::from_millis(1500));
+
+    // NOTE(dkg): Directly opening the edit URL or navigating there won't work and we will be prompted to login again.
+    driver
+        .cmd(Command::NavigateTo(config.endpoints.oauth_login.clone()))
+        .await?;
+
+    debug!("Waiting to avoid rate limit trigger ...");
+
+    thread::sleep(time::Duration::from_millis(3000));
+
+    if let Err(err) = save_session(driver, config).await {
+        warn!("Could not cache the Jobcan session: {}", err);
+    }
+
+    Ok(())
+}
+
+async fn restore_session(driver: &WebDriver, config: &Configuration) -> color_eyre::Result<bool> {
+    let path = config.state_dir.join(SESSION_FILE);
+    let data = match fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(_) => return Ok(false),
+    };
+    let cookies: Vec<Cookie> = serde_json::from_str(&data)?;
+
+    // NOTE(dkg): WebDriver only accepts cookies for the domain of the page that is currently open.
+    driver.get(&config.endpoints.employee).await?;
+    for cookie in cookies {
+        driver.add_cookie(cookie).await?;
+    }
+    driver.get(&config.endpoints.employee).await?;
+
+    let current_url = driver.current_url().await?;
+    Ok(current_url.starts_with(&config.endpoints.employee))
+}
+
+async fn save_session(driver: &WebDriver, config: &Configuration) -> color_eyre::Result<()> {
+    let cookies = driver.get_cookies().await?;
+    fs::create_dir_all(&config.state_dir)?;
+    let path = config.state_dir.join(SESSION_FILE);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // NOTE(dkg): The cookies are as good as the password, so the file is never readable by others.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&path)?
+        .write_all(serde_json::to_string(&cookies)?.as_bytes())?;
+
+    Ok(())
+}
+
+pub async fn wait_for_page_to_load(
+    driver: &WebDriver,
+    endpoints: &Endpoints,
+) -> color_eyre::Result<()> {
+    let mut i = 1;
+    let success = loop {
+        if i == 10 {
+            break false;
+        }
+        let right_url = driver.current_url().await?;
+        if endpoints.is_rate_limited(&right_url) {
+            warn!("We are being rate limited. Try {} of 10.", i);
+            driver.back().await?;
+            thread::sleep(time::Duration::from_millis(5000));
+        } else {
+            break true;
+        }
+        i += 1;
+    };
+
+    if !success {
+        bail!("Rate limit could not be circumvented. Aborting.");
+    }
+
+    Ok(())
+}
+
+/// Press one of the buttons on the page with the big "PUSH" button, with the given memo/note.
+/// If `group` is set, the matching option (by name or id) is selected in the group select box first.
+pub async fn push(
+    driver: &WebDriver,
+    config: &Configuration,
+    kind: PunchKind,
+    message: &str,
+    group: Option<&str>,
+) -> color_eyre::Result<()> {
+    driver
+        .cmd(Command::NavigateTo(config.endpoints.employee.clone()))
+        .await?;
+    wait_for_page_to_load(driver, &config.endpoints).await?;
+
+    if let Some(group) = group {
+        select_group(driver, group).await?;
+    }
+
+    let elem_push_button = match driver.find_element(By::Id(kind.button_id())).await {
+        Ok(elem) => elem,
+        Err(_) if kind != PunchKind::Work => {
+            bail!("The break button could not be found. Break punching (休憩打刻) is probably not enabled for your company in Jobcan.");
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let elem_note_field = driver.find_element(By::Id("notice_value")).await?;
+    elem_note_field.send_keys(message).await?;
+
+    elem_push_button.click().await?;
+
+    Ok(())
+}
+
+async fn select_group(driver: &WebDriver, group: &str) -> color_eyre::Result<()> {
+    let elem_select = match driver.find_element(By::Id(ID_SELECT_GROUP)).await {
+        Ok(elem) => elem,
+        Err(_) => bail!(
+            "The group '{}' was requested, but Jobcan does not show a group selection for your account.",
+            group
+        ),
+    };
+    let select = SelectElement::new(&elem_select).await?;
+
+    let mut groups = vec![];
+    for option in select.options().await? {
+        let value = option.value().await?.unwrap_or_default();
+        let name = option.text().await?;
+        groups.push((value, name.trim().to_string()));
+    }
+
+    match find_group(&groups, group) {
+        Some((value, name)) => {
+            debug!("Selecting group '{}' ({}).", name, value);
+            select.select_by_value(value).await?;
+            Ok(())
+        }
+        None => {
+            let valid_groups: Vec<String> = groups
+                .iter()
+                .filter(|(value, _)| !value.is_empty())
+                .map(|(value, name)| format!("{} ({})", name, value))
+                .collect();
+            bail!(
+                "The group '{}' does not exist. Valid groups are: {}",
+                group,
+                valid_groups.join(", ")
+            );
+        }
+    }
+}
+
+/// Find the group option by its id (the option value) or its name. Names are compared case-insensitively,
+/// and a unique partial match is accepted as well, e.g. "remote" for "Remote work".
+pub fn find_group<'a>(
+    groups: &'a [(String, String)],
+    wanted: &str,
+) -> Option<&'a (String, String)> {
+    let wanted = wanted.trim();
+    let wanted_lower = wanted.to_lowercase();
+    if wanted.is_empty() {
+        return None;
+    }
+
+    if let Some(group) = groups.iter().find(|(value, _)| value == wanted) {
+        return Some(group);
+    }
+    if let Some(group) = groups
+        .iter()
+        .find(|(_, name)| name.to_lowercase() == wanted_lower)
+    {
+        return Some(group);
+    }
+
+    let mut partial_matches = groups
+        .iter()
+        .filter(|(_, name)| name.to_lowercase().contains(&wanted_lower));
+    match (partial_matches.next(), partial_matches.next()) {
+        (Some(group), None) => Some(group),
+        _ => None,
+    }
+}
+
+/// Read the current working status from the page with the "PUSH" button.
+pub async fn read_working_status(
+    driver: &WebDriver,
+    config: &Configuration,
+) -> color_eyre::Result<WorkingStatus> {
+    driver
+        .cmd(Command::NavigateTo(config.endpoints.employee.clone()))
+        .await?;
+    wait_for_page_to_load(driver, &config.endpoints).await?;
+
+    let elem_status = driver.find_element(By::Id("working_status")).await?;
+    Ok(WorkingStatus::from_label(&elem_status.text().await?))
+}
+
+/// Wait until Jobcan shows a working status other than `before`, i.e. until the punch went through.
+pub async fn verify_punch(
+    driver: &WebDriver,
+    config: &Configuration,
+    before: WorkingStatus,
+) -> color_eyre::Result<WorkingStatus> {
+    for attempt in 1..=VERIFY_PUNCH_ATTEMPTS {
+        let status = read_working_status(driver, config).await?;
+        if status != before {
+            debug!(
+                "The punch went through after {} check(s): {}",
+                attempt, status
+            );
+            return Ok(status);
+        }
+        thread::sleep(time::Duration::from_millis(VERIFY_PUNCH_INTERVAL_MILLIS));
+    }
+    bail!(
+        "Jobcan still shows '{}' after the punch. Please check with the status command whether it went through.",
+        before
+    );
+}
+
+/// Look up the given day in the attendance list and return the holiday/vacation label Jobcan shows
+/// for it (e.g. 祝日 or 有休), or `None` for a regular working day.
+pub async fn read_day_off(
+    driver: &WebDriver,
+    config: &Configuration,
+    date: NaiveDate,
+) -> color_eyre::Result<Option<String>> {
+    driver
+        .cmd(Command::NavigateTo(config.endpoints.attendance_for(date)))
+        .await?;
+    wait_for_page_to_load(driver, &config.endpoints).await?;
+
+    let tables = driver.find_elements(By::Tag("table")).await?;
+    if tables.len() <= INDEX_FOR_TABLE_WITH_PUNCHED_DATA {
+        bail!("The attendance list for {} could not be found.", date);
+    }
+    let body = tables[INDEX_FOR_TABLE_WITH_PUNCHED_DATA]
+        .find_element(By::Tag("tbody"))
+        .await?;
+    // "06/01(木)"
+    let day_prefix = date.format("%m/%d").to_string();
+    for tr in body.find_elements(By::Tag("tr")).await? {
+        let columns = tr.find_elements(By::Tag("td")).await?;
+        if columns.len() < COLUMNS_COUNT {
+            continue;
+        }
+        if columns[COLUMN_DATE].text().await?.starts_with(&day_prefix) {
+            let label = columns[COLUMN_HOLIDAY].text().await?;
+            return Ok(if is_day_off_label(&label) {
+                Some(label.trim().to_string())
+            } else {
+                None
+            });
+        }
+    }
+
+    Ok(None)
+}
+
+/// Add an entry via the "revise clocking data" page, for today when no date is given.
+/// Returns false when Jobcan rejected the time.
+pub async fn revise(
+    driver: &WebDriver,
+    config: &Configuration,
+    date: Option<NaiveDate>,
+    time: &str,
+    message: &str,
+) -> color_eyre::Result<bool> {
+    let url = match date {
+        Some(date) => config.endpoints.adit_modify_for(date),
+        None => config.endpoints.adit_modify.clone(),
+    };
+    driver.cmd(Command::NavigateTo(url)).await?;
+
+    let elem_note_time = driver.find_element(By::Id("ter_time")).await?;
+    elem_note_time.send_keys(time).await?;
+
+    let elem_note_field = driver
+        .find_element(By::Css("textarea[name='notice']"))
+        .await?;
+    elem_note_field.send_keys(message).await?;
+
+    let elem_insert_button = driver.find_element(By::Id("insert_button")).await?;
+    elem_insert_button.click().await?;
+
+    // Check for date or time errors
+    let elem_time_error = driver.find_element(By::Id("time_error")).await;
+    if let Ok(elem) = elem_time_error {
+        if elem.find_element(By::ClassName("alert")).await.is_ok() {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// The totals of a month from the attendance page, in minutes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MonthlyTotals {
+    /// 実労働時間
+    pub worked: Option<u32>,
+    /// 月規定労働時間
+    pub required: Option<u32>,
+    /// 残業時間
+    pub overtime: Option<u32>,
+}
+
+impl MonthlyTotals {
+    /// Negative once the required hours are done.
+    pub fn remaining(&self) -> Option<i64> {
+        Some(self.required? as i64 - self.worked? as i64)
+    }
+}
+
+/// The vacation balances (休暇残数) of the attendance page, as Jobcan shows them, e.g. "10.0日".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VacationBalances {
+    /// 有給休暇
+    pub paid: String,
+    /// 代休
+    pub compensatory: String,
+    /// 振替休日
+    pub substitution: String,
+    /// 特別休暇
+    pub special: String,
+}
+
+/// The values of the given single column table of the attendance page of the month, e.g. the working hours.
+async fn read_totals_table(
+    driver: &WebDriver,
+    config: &Configuration,
+    month: NaiveDate,
+    index: usize,
+    rows: usize,
+) -> color_eyre::Result<Vec<String>> {
+    driver
+        .cmd(Command::NavigateTo(config.endpoints.attendance_for(month)))
+        .await?;
+    wait_for_page_to_load(driver, &config.endpoints).await?;
+
+    // NOTE(dkg): The totals are collapsed by default, and hidden elements have no text.
+    if let Ok(toggle_button) = driver
+        .find_element(By::Css("[data-toggle=\"collapse\"]"))
+        .await
+    {
+        toggle_button.click().await?;
+        thread::sleep(time::Duration::from_millis(500));
+    }
+
+    let tables = driver.find_elements(By::Tag("table")).await?;
+    if tables.len() <= index {
+        bail!(
+            "The totals for {} could not be found.",
+            month.format("%Y-%m")
+        );
+    }
+    let body = tables[index].find_element(By::Tag("tbody")).await?;
+    let mut values = vec![];
+    for row in body.find_elements(By::Tag("tr")).await?.iter().take(rows) {
+        values.push(row.find_element(By::Tag("td")).await?.text().await?);
+    }
+
+    Ok(values)
+}
+
+/// Read the totals (worked, required, overtime) of the given month from the attendance page.
+pub async fn read_monthly_totals(
+    driver: &WebDriver,
+    config: &Configuration,
+    month: NaiveDate,
+) -> color_eyre::Result<MonthlyTotals> {
+    let values = read_totals_table(
+        driver,
+        config,
+        month,
+        INDEX_FOR_TABLE_WITH_WORKING_HOURS,
+        ROW_WITH_OVERTIME + 1,
+    )
+    .await?;
+    let row = |index: usize| {
+        values
+            .get(index)?
+            .parse::<WorkDuration>()
+            .ok()
+            .map(WorkDuration::minutes)
+    };
+
+    Ok(MonthlyTotals {
+        worked: row(ROW_WITH_WORKED_HOURS_SO_FAR),
+        required: row(ROW_WITH_WORKED_TIME_EXPECTED),
+        overtime: row(ROW_WITH_OVERTIME),
+    })
+}
+
+/// Read the overtime (残業時間) of the given month from the attendance page.
+pub async fn read_overtime(
+    driver: &WebDriver,
+    config: &Configuration,
+    month: NaiveDate,
+) -> color_eyre::Result<Option<u32>> {
+    Ok(read_monthly_totals(driver, config, month).await?.overtime)
+}
+
+/// Read the vacation balances of the given month from the attendance page.
+pub async fn read_vacation_balances(
+    driver: &WebDriver,
+    config: &Configuration,
+    month: NaiveDate,
+) -> color_eyre::Result<VacationBalances> {
+    let values =
+        read_totals_table(driver, config, month, INDEX_FOR_TABLE_WITH_VACATION_DATA, 4).await?;
+    let row = |index: usize| values.get(index).cloned().unwrap_or_default();
+
+    Ok(VacationBalances {
+        paid: row(0),
+        compensatory: row(1),
+        substitution: row(2),
+        special: row(3),
+    })
+}
+
+/// A day of the attendance list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttendanceDay {
+    pub date: NaiveDate,
+    /// The holiday column, e.g. 法定休日 or 有休. Empty on regular working days.
+    pub holiday: String,
+    pub record: DayRecord,
+}
+
+/// Read all days of the given month from the attendance page.
+pub async fn read_attendance(
+    driver: &WebDriver,
+    config: &Configuration,
+    month: NaiveDate,
+) -> color_eyre::Result<Vec<AttendanceDay>> {
+    driver
+        .cmd(Command::NavigateTo(config.endpoints.attendance_for(month)))
+        .await?;
+    wait_for_page_to_load(driver, &config.endpoints).await?;
+
+    let tables = driver.find_elements(By::Tag("table")).await?;
+    if tables.len() <= INDEX_FOR_TABLE_WITH_PUNCHED_DATA {
+        bail!(
+            "The attendance list for {} could not be found.",
+            month.format("%Y-%m")
+        );
+    }
+    let body = tables[INDEX_FOR_TABLE_WITH_PUNCHED_DATA]
+        .find_element(By::Tag("tbody"))
+        .await?;
+
+    let mut days = vec![];
+    for tr in body.find_elements(By::Tag("tr")).await? {
+        let columns = tr.find_elements(By::Tag("td")).await?;
+        if columns.len() < COLUMNS_COUNT {
+            continue;
+        }
+        let date_text = columns[COLUMN_DATE].text().await?;
+        // "06/01(木)"
+        let date = match date_text.get(..5).and_then(|day| {
+            NaiveDate::parse_from_str(&format!("{}/{}", month.year(), day), "%Y/%m/%d").ok()
+        }) {
+            Some(date) => date,
+            None => {
+                warn!("Unexpected date in the attendance list: {}", date_text);
+                continue;
+            }
+        };
+
+        days.push(AttendanceDay {
+            date,
+            holiday: columns[COLUMN_HOLIDAY].text().await?.trim().to_string(),
+            record: DayRecord {
+                date: date_text,
+                start: columns[COLUMN_START_TIME].text().await?,
+                end: columns[COLUMN_END_TIME].text().await?,
+                break_time: columns[COLUMN_BREAK_TIME].text().await?,
+            },
+        });
+    }
+
+    Ok(days)
+}
+
+/// Jobcan leaves the holiday column empty for regular working days (or calls them 平日).
+pub fn is_day_off_label(label: &str) -> bool {
+    let label = label.trim();
+    !label.is_empty() && label != "平日" && !label.eq_ignore_ascii_case("weekday")
+}
+
+/// Read all punches of the given day from the "revise clocking data" page.
+pub async fn read_punches(
+    driver: &WebDriver,
+    config: &Configuration,
+    date: NaiveDate,
+) -> color_eyre::Result<Vec<Punch>> {
+    driver
+        .cmd(Command::NavigateTo(config.endpoints.adit_modify_for(date)))
+        .await?;
+    wait_for_page_to_load(driver, &config.endpoints).await?;
+
+    let mut punches = vec![];
+    let table = match driver.find_element(By::Id(ID_TABLE_WITH_PUNCHES)).await {
+        Ok(table) => table,
+        // NOTE(dkg): Jobcan does not render the table at all when there are no punches yet.
+        Err(_) => return Ok(punches),
+    };
+    let body = table.find_element(By::Tag("tbody")).await?;
+    for tr in body.find_elements(By::Tag("tr")).await? {
+        let columns = tr.find_elements(By::Tag("td")).await?;
+        if columns.len() < PUNCH_COLUMNS_COUNT {
+            continue;
+        }
+        punches.push(Punch {
+            time: columns[COLUMN_PUNCH_TIME].text().await?,
+            kind: columns[COLUMN_PUNCH_KIND].text().await?,
+            notice: columns[COLUMN_PUNCH_NOTICE].text().await?,
+        });
+    }
+
+    Ok(punches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups() -> Vec<(String, String)> {
+        vec![
+            (String::from("1"), String::from("Office")),
+            (String::from("2"), String::from("Remote work")),
+            (String::from("3"), String::from("Client site A")),
+            (String::from("4"), String::from("Client site B")),
+        ]
+    }
+
+    #[test]
+    fn test_monthly_totals_remaining() {
+        let totals = MonthlyTotals {
+            worked: Some(150 * 60),
+            required: Some(160 * 60),
+            overtime: None,
+        };
+        assert_eq!(Some(10 * 60), totals.remaining());
+        assert_eq!(
+            Some(-30),
+            MonthlyTotals {
+                worked: Some(160 * 60 + 30),
+                ..totals
+            }
+            .remaining()
+        );
+        assert_eq!(None, MonthlyTotals::default().remaining());
+    }
+
+    #[test]
+    fn test_is_day_off_label() {
+        assert!(is_day_off_label("祝日"));
+        assert!(is_day_off_label(" 有休 "));
+        assert!(!is_day_off_label(""));
+        assert!(!is_day_off_label("平日"));
+    }
+
+    #[test]
+    fn test_find_group_by_id() {
+        let groups = groups();
+
+        assert_eq!(Some(&groups[1]), find_group(&groups, "2"));
+    }
+
+    #[test]
+    fn test_find_group_by_name() {
+        let groups = groups();
+
+        assert_eq!(Some(&groups[0]), find_group(&groups, "office"));
+        assert_eq!(Some(&groups[2]), find_group(&groups, "Client Site A"));
+    }
+
+    #[test]
+    fn test_find_group_by_unique_partial_name() {
+        let groups = groups();
+
+        assert_eq!(Some(&groups[1]), find_group(&groups, "remote"));
+        assert_eq!(None, find_group(&groups, "client"));
+    }
+
+    #[test]
+    fn test_find_group_unknown() {
+        let groups = groups();
+
+        assert_eq!(None, find_group(&groups, "moon base"));
+        assert_eq!(None, find_group(&groups, ""));
+    }
+}
@@ -1,30 +1,78 @@
 use chrono::prelude::*;
-use clap::Clap;
-use color_eyre::eyre::{bail, WrapErr};
+use clap::{AppSettings, Clap};
+use color_eyre::eyre::{bail, eyre, WrapErr};
 use log::{debug, error, info, trace, warn};
+use std::sync::{Arc, Mutex};
 use std::{env, thread, time};
+use thirtyfour::common::command::Command;
 use thirtyfour::prelude::*;
-use thirtyfour::{common::command::Command, extensions::chrome::ChromeDevTools};
+
+mod check;
+use crate::check::{Finding, Fix};
+
+mod clock;
+use crate::clock::{ClockTime, WorkDuration};
 
 mod config;
 use crate::config::{Configuration, ENVVAR_NAME_LOGIN, ENVVAR_NAME_PASSWORD};
 
+mod daemon;
+
+mod email;
+
+mod endpoints;
+
+mod forecast;
+use crate::forecast::Forecast;
+
+mod holidays;
+use crate::holidays::HolidayCalendar;
+
+mod input;
+
+mod jobcan;
+use crate::jobcan::{
+    wait_for_page_to_load, PunchKind, COLUMNS_COUNT, COLUMN_BREAK_TIME, COLUMN_DATE,
+    COLUMN_END_TIME, COLUMN_HOLIDAY, COLUMN_START_TIME, INDEX_FOR_TABLE_WITH_CURRENT_TOTALS,
+    INDEX_FOR_TABLE_WITH_PUNCHED_DATA, INDEX_FOR_TABLE_WITH_VACATION_DATA,
+    INDEX_FOR_TABLE_WITH_WORKING_HOURS, ROW_WITH_OVERTIME, ROW_WITH_WORKED_HOURS_SO_FAR,
+    ROW_WITH_WORKED_TIME_EXPECTED,
+};
+
+mod mock;
+use crate::mock::MockState;
+
+mod notifier;
+use crate::notifier::{Notification, SlackNotifier};
+
+mod outbox;
+
+mod overtime;
+use crate::overtime::{OvertimeHistory, OvertimeWarning, Severity};
+
+mod reconcile;
+use crate::reconcile::{DayRecord, Reconciliation};
+
+mod remind;
+
+mod report;
+
+mod schedule;
+use crate::schedule::Schedule;
+
 mod slack;
-use crate::slack::post_to_slack;
-
-const INDEX_FOR_TABLE_WITH_WORKING_HOURS: usize = 3;
-const INDEX_FOR_TABLE_WITH_VACATION_DATA: usize = 4;
-const INDEX_FOR_TABLE_WITH_PUNCHED_DATA: usize = 6;
-const COLUMN_DATE: usize = 0;
-const COLUMN_HOLIDAY: usize = 1;
-const COLUMN_START_TIME: usize = 2;
-const COLUMN_END_TIME: usize = 3;
-const COLUMN_BREAK_TIME: usize = 4;
-const COLUMNS_COUNT: usize = 5;
-
-const INDEX_FOR_TABLE_WITH_CURRENT_TOTALS: usize = 3;
-const ROW_WITH_WORKED_HOURS_SO_FAR: usize = 0; // 1st row: 実労働時間
-const ROW_WITH_WORKED_TIME_EXPECTED: usize = 1; // 2nd row: 月規定労働時間
+mod slack_bot;
+use crate::slack::DailyThread;
+use crate::slack_bot::{JobcanAccount, SlackAccounts};
+
+mod status;
+use crate::status::{TodayStatus, WorkingStatus};
+
+mod template;
+use crate::template::{PunchDetails, PunchEvent};
+
+mod timezone;
+use crate::timezone::JobcanTimezone;
 
 /// This doc string acts as a help message when the user runs '--help'
 /// as do all doc strings on fields
@@ -51,6 +99,14 @@ enum SubCommand {
     #[clap(name = "push-it", alias = "clock-in", alias = "clock-out")]
     PushIt(PushIt),
 
+    /// Start a break. Only works when break punching (休憩打刻) is enabled for your company.
+    #[clap(name = "break-start")]
+    BreakStart(Break),
+
+    /// End a break. Only works when break punching (休憩打刻) is enabled for your company.
+    #[clap(name = "break-end")]
+    BreakEnd(Break),
+
     #[clap(name = "revise-clock")]
     ReviseClockingData(ReviseClockingData),
 
@@ -61,6 +117,46 @@ enum SubCommand {
     /// List logged hours for the current month or the given date
     #[clap(name = "list")]
     List(List),
+
+    /// Show whether you are clocked in right now, together with today's punches and worked time
+    #[clap(name = "status")]
+    Status(Status),
+
+    /// Look for missing punches and suggest revise-clock commands that fix them
+    #[clap(name = "check")]
+    Check(Check),
+
+    /// Check the overtime against the limits of the 36 agreement (36協定).
+    /// Exits with 1 when a limit is near and with 2 when a limit is exceeded.
+    #[clap(name = "overtime-check")]
+    OvertimeCheck(OvertimeCheck),
+
+    /// Monthly summary of the worked hours, Jobcan's totals, overtime and vacation balances. Printed, or sent
+    /// by email with --email.
+    #[clap(name = "report")]
+    Report(Report),
+
+    /// Clock in and out automatically according to the schedule in JC_SCHEDULE. Runs until stopped.
+    #[clap(name = "daemon")]
+    Daemon(Daemon),
+
+    /// Remind to clock out with a desktop notification at the end of the working day from JC_SCHEDULE, and again
+    /// when still clocked in some hours later. Runs until stopped.
+    #[clap(name = "remind")]
+    Remind(Remind),
+
+    /// Slack maintenance, e.g. posting the messages that could not be posted earlier
+    #[clap(name = "slack")]
+    Slack(Slack),
+
+    /// Answer the /jobcan slash command and its buttons in Slack. Needs SLACK_SIGNING_SECRET and Slack users
+    /// linked to their Jobcan accounts with `slack link`.
+    #[clap(name = "serve-slack")]
+    ServeSlack(ServeSlack),
+
+    /// Run a local mock of the Jobcan pages the bot uses. Meant for testing only.
+    #[clap(name = "mock-server", setting = AppSettings::Hidden)]
+    MockServer(MockServer),
 }
 
 /// Click on the big orange "PUSH" button.
@@ -70,12 +166,44 @@ struct PushIt {
     #[clap(short, long, default_value = "work start")]
     message: String,
 
-    /// Message for Slack. Only used when SLACK_TOKEN and slack_channel are set.
-    /// If not set no message is posted.
+    /// Message for Slack, instead of the SLACK_TEMPLATE_CLOCK_IN/SLACK_TEMPLATE_CLOCK_OUT template.
+    /// Can use the same placeholders, e.g. "{emoji} {memo}". Only used when SLACK_TOKEN and slack_channel are set.
+    /// Default: the template, or the memo if there is none.
     #[clap(long, default_value = "", name = "slack-message")]
     slack_message: String,
 
-    /// The Slack channel to post to. Only used when SLACK_TOKEN is set. Default: #standup
+    /// The Slack channel to post to, by name ("#standup") or ID ("C0123ABCD"). Only used when SLACK_TOKEN is set.
+    /// Default: #standup
+    #[clap(long, default_value = "#standup", name = "slack-channel")]
+    slack_channel: String,
+
+    /// The Jobcan group (e.g. office, remote) to punch in, by name or id. Default: JC_GROUP, if set.
+    #[clap(short, long)]
+    group: Option<String>,
+
+    /// Punch even if today is a holiday or company day off.
+    #[clap(short, long)]
+    force: bool,
+
+    /// Punch at this time instead of now, e.g. "18:00" or "now-20m". A time in the past is added
+    /// via "revise clocking data", same as revise-clock does.
+    #[clap(long, conflicts_with = "minutes-ago")]
+    at: Option<String>,
+
+    /// Punch the given minutes ago instead of now. Same as --at now-<minutes>m.
+    #[clap(long, name = "minutes-ago")]
+    minutes_ago: Option<u32>,
+}
+
+/// Press the "break start" or "break end" button.
+#[derive(Clap, Debug)]
+struct Break {
+    /// Optional memo/note for the break.
+    #[clap(short, long, default_value = "")]
+    message: String,
+
+    /// The Slack channel to post to. Only used when SLACK_TOKEN and a SLACK_TEMPLATE_BREAK_* template are set.
+    /// Default: #standup
     #[clap(long, default_value = "#standup", name = "slack-channel")]
     slack_channel: String,
 }
@@ -84,15 +212,24 @@ struct PushIt {
 /// TODO(dkg): support removing outdated/wrong entries
 #[derive(Clap, Debug)]
 struct ReviseClockingData {
-    /// The date that should be revised. Defaults to today. Important: format is "yyyy-MM-dd"
+    /// The date that should be revised. Defaults to today.
+    /// Format: "yyyy-MM-dd", "MM/dd", "today", "yesterday" or a weekday like "mon" (the most recent one).
     #[clap(short, long)]
     date: Option<String>,
-    /// The time that should be revised. Defaults to 0700, which means 7am. Important: format is "hhmm".
+    /// The time that should be revised. Defaults to 0700, which means 7am.
+    /// Format: "hhmm", "hh:mm" (up to 2600, 2am) or relative like "now" and "now-15m".
     #[clap(short, long, default_value = "0700")]
     time: String,
     /// Additional memo/note for the "Push"/clock in text field. Defaults to "work start"
     #[clap(short, long, default_value = "work start")]
     message: String,
+    /// Add the entry even if the date is a holiday or company day off.
+    #[clap(short, long)]
+    force: bool,
+    /// The Slack channel to post to. Only used when SLACK_TOKEN and SLACK_TEMPLATE_REVISE are set.
+    /// Default: #standup
+    #[clap(long, default_value = "#standup", name = "slack-channel")]
+    slack_channel: String,
 }
 
 /// Click on the big orange "PUSH" button.
@@ -104,6 +241,159 @@ struct List {
     /// Output as CSV data. Default: false
     #[clap(short, long)]
     csv: bool,
+    /// Output format for the summary and forecast. "json" prints only the forecast. Default: human
+    #[clap(short, long, default_value = "human", possible_values = &["human", "json"])]
+    format: OutputFormat,
+}
+
+/// Scan past days for missing or odd punches. Only days before today are checked.
+#[derive(Clap, Debug)]
+struct Check {
+    /// The month to check, format YYYYMM. Default: the current month
+    #[clap(short, long)]
+    date: Option<String>,
+    /// First day to check, format "yyyy-MM-dd". Overrides --date.
+    #[clap(long)]
+    from: Option<String>,
+    /// Last day to check, format "yyyy-MM-dd". Default: yesterday
+    #[clap(long)]
+    to: Option<String>,
+    /// Shifts longer than this many hours (1 to 48) are reported. Default: 16
+    #[clap(long, default_value = "16", name = "max-shift", parse(try_from_str = parse_max_shift))]
+    max_shift: u32,
+    /// Ask for every suggested fix whether it should be applied.
+    #[clap(long)]
+    fix: bool,
+}
+
+/// Overtime limits check, e.g. for a cron job or monitoring.
+#[derive(Clap, Debug)]
+struct OvertimeCheck {
+    /// Optional month to check, format YYYYMM. Default: the current month
+    #[clap(short, long)]
+    date: Option<String>,
+    /// Output format. Default: human
+    #[clap(short, long, default_value = "human", possible_values = &["human", "json"])]
+    format: OutputFormat,
+}
+
+/// Read-only view of the current working status.
+#[derive(Clap, Debug)]
+struct Status {
+    /// Output format. Default: human
+    #[clap(short, long, default_value = "human", possible_values = &["human", "json"])]
+    format: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Human,
+    Json,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(OutputFormat::Human),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("Unknown output format '{}'.", s)),
+        }
+    }
+}
+
+/// Automatic clock-in/out. Skips national holidays, days in JC_DAYS_OFF and days Jobcan lists as holiday
+/// or vacation, and only punches
+/// when the current status fits (e.g. no clock-in when already working).
+#[derive(Clap, Debug)]
+struct Daemon {
+    /// Memo/note for the automatic clock-in.
+    #[clap(long, default_value = "work start", name = "clock-in-message")]
+    clock_in_message: String,
+
+    /// Memo/note for the automatic clock-out.
+    #[clap(long, default_value = "work end", name = "clock-out-message")]
+    clock_out_message: String,
+
+    /// The Slack channel to post to, by name ("#standup") or ID ("C0123ABCD"). Only used when SLACK_TOKEN is set.
+    /// Default: #standup
+    #[clap(long, default_value = "#standup", name = "slack-channel")]
+    slack_channel: String,
+}
+
+/// The monthly report, e.g. for a manager.
+#[derive(Clap, Debug)]
+struct Report {
+    /// Optional month, format YYYYMM. Default: the current month
+    #[clap(short, long)]
+    date: Option<String>,
+    /// Send the report by email (SMTP_HOST, EMAIL_FROM, EMAIL_TO) instead of printing it.
+    #[clap(short, long)]
+    email: bool,
+    /// One report for every Jobcan account linked with `slack link`, instead of JC_LOGIN.
+    #[clap(long)]
+    linked: bool,
+}
+
+/// Desktop reminders to clock out. They are shown even without JC_DESKTOP_NOTIFICATIONS.
+#[derive(Clap, Debug)]
+struct Remind {
+    /// Remind again when still clocked in this many hours after the end of the working day. 0 turns it off.
+    #[clap(long, default_value = "2", name = "after-hours", parse(try_from_str = parse_after_hours))]
+    after_hours: u32,
+}
+
+#[derive(Clap, Debug)]
+struct Slack {
+    #[clap(subcommand)]
+    subcmd: SlackCommand,
+}
+
+#[derive(Clap, Debug)]
+enum SlackCommand {
+    /// Post the messages from the outbox, i.e. the ones that failed earlier because Slack could not be reached.
+    /// Exits with an error if some are still left.
+    #[clap(name = "flush")]
+    Flush,
+
+    /// Let the Slack user with the given member ID (e.g. U0123ABCD) use `serve-slack` with the Jobcan account
+    /// from JC_LOGIN and JC_PASSWORD.
+    #[clap(name = "link")]
+    Link(SlackUser),
+
+    /// Remove the Jobcan account of the Slack user with the given member ID.
+    #[clap(name = "unlink")]
+    Unlink(SlackUser),
+}
+
+#[derive(Clap, Debug)]
+struct SlackUser {
+    /// The member ID, from the user's Slack profile -> "Copy member ID".
+    user_id: String,
+}
+
+/// Listen for Slack's requests on the given address.
+#[derive(Clap, Debug)]
+struct ServeSlack {
+    /// The port to listen on. Set the Request URL of the slash command and of Interactivity in the Slack app to
+    /// this server, e.g. https://jobcan.example.com/slack.
+    #[clap(short, long, default_value = "3000")]
+    port: u16,
+    /// The address to listen on. Use 0.0.0.0 to listen on all interfaces instead of only behind a reverse proxy.
+    #[clap(long, default_value = "127.0.0.1")]
+    host: std::net::IpAddr,
+}
+
+/// Serve the mock Jobcan pages on localhost.
+#[derive(Clap, Debug)]
+struct MockServer {
+    /// The port to listen on. Point JC_ID_BASE_URL and JC_SSL_BASE_URL to http://localhost:<port>.
+    #[clap(short, long, default_value = "4567")]
+    port: u16,
+    /// Redirect this many requests to the attendance page to the rate limit page first.
+    #[clap(long, default_value = "0", name = "rate-limit")]
+    rate_limit: u32,
 }
 
 #[tokio::main]
@@ -117,6 +407,8 @@ async fn main() -> color_eyre::Result<()> {
     dotenv::dotenv().ok();
     env_logger::init();
 
+    let opts: Opts = Opts::parse();
+
     let config = Configuration::from_env();
     if !config.is_ok() {
         bail!(
@@ -125,198 +417,372 @@ async fn main() -> color_eyre::Result<()> {
             ENVVAR_NAME_PASSWORD
         );
     }
-
-    let opts: Opts = Opts::parse();
+    let calendar = config.holiday_calendar()?;
+    let timezone = config.jobcan_timezone()?;
+    if config.has_notifiers() {
+        config.slack_templates.validate()?;
+    }
+    config.webhooks()?;
+    config.slack_status.validate()?;
 
     // Sanity check before we start up the browser.
+    // NOTE(dkg): Resolved only once, so "now" doesn't move while the browser starts up.
+    let mut punch_at = None;
     match &opts.subcmd {
         // Left in for testing.
         // SubCommand::PushIt(push_it) => {
         //     let _ = post_to_slack(&config, &push_it.slack_channel, &push_it.slack_message).await;
         //     return Ok(());
         // }
-        SubCommand::ReviseClockingData(revise_data) => {
-            if let Some(input_date_str) = &revise_data.date {
-                NaiveDate::parse_from_str(input_date_str, "%Y-%m-%d")
-                    .wrap_err("Unable to parse the date.")?;
-            }
-            if revise_data.time.len() != 4 {
-                bail!("The time has a wrong format. It should be hhmm, e.g. 0700 for 7am, 2300 for 11pm, etc.");
-            }
-            let time = revise_data.time.parse::<i64>()?;
-            // Jobcan allows apparently times greater than 2400, since 2600 is supposed to be 2am as their example
-            // on their site states "ex) 2:00 a.m. ⇒　2600"
-            if !(0..=2600).contains(&time) {
-                bail!("The time has a wrong value. It should be between 0000 (midnight) and 2600 (2am), e.g. 0700 for 7am, 2300 for 11pm, etc.");
+        SubCommand::PushIt(push_it) => {
+            punch_at = resolve_push_at(push_it, timezone.now())?;
+            let date = punch_at.map_or_else(|| timezone.today(), |(date, _)| date);
+            check_holiday(&calendar, date, push_it.force)?;
+            if config.can_post_to_slack() {
+                slack::parse_channel(&push_it.slack_channel)?;
             }
         }
+        SubCommand::ReviseClockingData(revise_data) => {
+            let (date, time) = resolve_revise(revise_data, timezone.now())?;
+            check_holiday(&calendar, date, revise_data.force)?;
+            punch_at = Some((date, time));
+        }
+        SubCommand::List(list) if list.csv && list.format == OutputFormat::Json => {
+            bail!("Please use either --csv or --format json, not both.");
+        }
         SubCommand::Login if !opts.visible || opts.sleep_time.is_none() => {
             bail!("The 'login only' command only makes sense for debugging when the 'visible' flag set and 'sleep' is > 0.");
         }
+        SubCommand::MockServer(mock_server) => {
+            let mut state = MockState::new(&config.login, &config.password);
+            state.timezone = timezone;
+            state.rate_limited_requests = mock_server.rate_limit;
+            let addr = ([127, 0, 0, 1], mock_server.port).into();
+            return mock::serve(addr, Arc::new(Mutex::new(state))).await;
+        }
+        SubCommand::Daemon(daemon) => {
+            return daemon::run(&config, daemon, opts.visible).await;
+        }
+        SubCommand::Remind(remind) => {
+            return remind::run(&config, remind, opts.visible).await;
+        }
+        SubCommand::Report(report) => {
+            return report::run(&config, report, opts.visible).await;
+        }
+        SubCommand::Slack(slack) => match &slack.subcmd {
+            SlackCommand::Flush => {
+                if !config.can_post_to_slack() {
+                    bail!("Slack is not configured, please set SLACK_TOKEN and SLACK_USER_NAME.");
+                }
+                let left = outbox::flush(&config).await?;
+                if left > 0 {
+                    bail!(
+                        "{} Slack message(s) could not be posted and are still in the outbox.",
+                        left
+                    );
+                }
+                info!("All Slack messages have been posted.");
+                return Ok(());
+            }
+            SlackCommand::Link(user) => {
+                let mut accounts = SlackAccounts::load(&config.state_dir)?;
+                accounts.accounts.insert(
+                    user.user_id.clone(),
+                    JobcanAccount {
+                        login: config.login.clone(),
+                        password: config.password.clone(),
+                    },
+                );
+                accounts.save(&config.state_dir)?;
+                info!(
+                    "Linked the Slack user {} to {}.",
+                    user.user_id, config.login
+                );
+                return Ok(());
+            }
+            SlackCommand::Unlink(user) => {
+                let mut accounts = SlackAccounts::load(&config.state_dir)?;
+                if accounts.accounts.remove(&user.user_id).is_none() {
+                    bail!("The Slack user {} is not linked.", user.user_id);
+                }
+                accounts.save(&config.state_dir)?;
+                info!("Unlinked the Slack user {}.", user.user_id);
+                return Ok(());
+            }
+        },
+        SubCommand::ServeSlack(serve) => {
+            let addr = (serve.host, serve.port).into();
+            return slack_bot::serve(&config, addr, opts.visible).await;
+        }
         _ => (),
     }
 
-    debug!("Starting WebDriver ...");
-
-    let mut caps = DesiredCapabilities::chrome();
-    if !opts.visible {
-        caps.set_headless()?;
-    }
-
-    // TODO(dkg): consider starting up Chromedriver manually here in a separate thread
-    let chromedriver_host =
-        env::var("CHROMEDRIVER_HOST").unwrap_or_else(|_| "http://localhost".into());
-    let chromedriver_port = env::var("CHROMEDRIVER_PORT")
-        .unwrap_or_else(|_| "4444".into())
-        .parse::<i32>()
-        .unwrap_or(4444);
-    let chromedriver_url = format!("{}:{}", chromedriver_host, chromedriver_port);
-    let driver = WebDriver::new(&chromedriver_url, &caps).await?;
-
-    let dev_tools = ChromeDevTools::new(driver.session());
-    let version_info = dev_tools.execute_cdp("Browser.getVersion").await?;
-
-    debug!("Using Chrome Version: {:?}", version_info);
-
-    // Login via https://id.jobcan.jp/users/sign_in
-    driver.get("https://id.jobcan.jp/users/sign_in").await?;
+    flush_outbox(&config).await;
 
-    let elem_form = driver.find_element(By::ClassName("form")).await?;
-
-    // Find login input box and type in the user's login
-    let elem_login = elem_form.find_element(By::Id("user_email")).await?;
-    elem_login.send_keys(&config.login).await?;
-
-    // Find password input box and type in the user's password
-    let elem_password = elem_form.find_element(By::Id("user_password")).await?;
-    elem_password.send_keys(&config.password).await?;
-
-    // Click the login button
-    let elem_button = elem_form.find_element(By::ClassName("form__login")).await?;
-    elem_button.click().await?;
-
-    thread::sleep(time::Duration::from_millis(1500));
-
-    // NOTE(dkg): Directly opening the edit URL or navigating there won't work and we will be prompted to login again.
-    driver
-        .cmd(Command::NavigateTo(String::from(
-            "https://ssl.jobcan.jp/jbcoauth/login",
-        )))
-        .await?;
-
-    debug!("Waiting to avoid rate limit trigger ...");
-
-    thread::sleep(time::Duration::from_millis(3000));
+    let driver = jobcan::start_webdriver(opts.visible).await?;
+    jobcan::login(&driver, &config).await?;
 
     match &opts.subcmd {
         SubCommand::PushIt(push_it) => {
-            driver
-                .cmd(Command::NavigateTo(String::from(
-                    "https://ssl.jobcan.jp/employee",
-                )))
+            let result = push_and_post(&driver, &config, &timezone, push_it, punch_at).await;
+            desktop_on_failure(&config, "punch", result).await?;
+        }
+        SubCommand::BreakStart(break_data) => {
+            let working_status = jobcan::read_working_status(&driver, &config).await?;
+            if working_status != WorkingStatus::Working {
+                bail!(
+                    "You can only start a break while working. Current status: {}",
+                    working_status
+                );
+            }
+            let result = async {
+                jobcan::push(
+                    &driver,
+                    &config,
+                    PunchKind::BreakStart,
+                    &break_data.message,
+                    config.default_group(),
+                )
                 .await?;
-
-            let elem_note_field = driver.find_element(By::Id("notice_value")).await?;
-            elem_note_field.send_keys(&push_it.message).await?;
-
-            let elem_push_button = driver.find_element(By::Id("adit-button-push")).await?;
-            elem_push_button.click().await?;
-
-            if config.can_post_to_slack() {
-                debug!("Waiting before trying to post to Slack ...");
-                thread::sleep(time::Duration::from_secs(30));
-
-                let message = if push_it.slack_message.is_empty() {
-                    &push_it.message
-                } else {
-                    &push_it.slack_message
-                };
-
-                let result = post_to_slack(&config, &push_it.slack_channel, message).await;
-                if result.is_err() {
-                    bail!("Slack returned an error.\n{}", result.unwrap_err());
-                }
+                jobcan::verify_punch(&driver, &config, working_status).await
+            }
+            .await;
+            desktop_on_failure(&config, "break start", result).await?;
+            let (date, time) = now_on_clock(&timezone);
+            let details =
+                PunchDetails::new(PunchEvent::BreakStart, date, time, &break_data.message);
+            post_punch(
+                &driver,
+                &config,
+                &timezone,
+                &break_data.slack_channel,
+                None,
+                details,
+            )
+            .await;
+        }
+        SubCommand::BreakEnd(break_data) => {
+            let working_status = jobcan::read_working_status(&driver, &config).await?;
+            if working_status != WorkingStatus::OnBreak {
+                bail!(
+                    "You can only end a break while being on a break. Current status: {}",
+                    working_status
+                );
+            }
+            let result = async {
+                jobcan::push(
+                    &driver,
+                    &config,
+                    PunchKind::BreakEnd,
+                    &break_data.message,
+                    config.default_group(),
+                )
+                .await?;
+                jobcan::verify_punch(&driver, &config, working_status).await
             }
+            .await;
+            desktop_on_failure(&config, "break end", result).await?;
+            let (date, time) = now_on_clock(&timezone);
+            let details = PunchDetails::new(PunchEvent::BreakEnd, date, time, &break_data.message);
+            post_punch(
+                &driver,
+                &config,
+                &timezone,
+                &break_data.slack_channel,
+                None,
+                details,
+            )
+            .await;
         }
         SubCommand::ReviseClockingData(revise_data) => {
+            let (date, time) =
+                punch_at.ok_or_else(|| eyre!("The date and time to revise are missing."))?;
+            info!(
+                "Adding {} ({}) as {}",
+                timezone.describe_clock(date, time),
+                date.weekday(),
+                time.to_jobcan()
+            );
+
+            let jobcan_time = time.to_jobcan();
+            if !jobcan::revise(
+                &driver,
+                &config,
+                Some(date),
+                &jobcan_time,
+                &revise_data.message,
+            )
+            .await?
+            {
+                error!("The format for the 'time' argument is wrong. Please check. It should be 'hhmm'.");
+                if opts.visible && opts.sleep_time.is_none() {
+                    error!(
+                        "Sleeping for 90 seconds. Please check the error display on the website."
+                    );
+                    thread::sleep(time::Duration::from_secs(90));
+                }
+                bail!("The 'time' argument has the wrong format. It should be 'hhmm'.");
+            }
+            let details = PunchDetails::new(PunchEvent::Revise, date, time, &revise_data.message);
+            post_punch(
+                &driver,
+                &config,
+                &timezone,
+                &revise_data.slack_channel,
+                None,
+                details,
+            )
+            .await;
+        }
+        SubCommand::Login => {
             driver
-                .cmd(Command::NavigateTo(String::from(
-                    "https://ssl.jobcan.jp/employee/adit/modify/",
-                )))
+                .cmd(Command::NavigateTo(config.endpoints.adit_modify.clone()))
                 .await?;
-
-            if let Some(input_date_str) = &revise_data.date {
-                let naive_date = NaiveDate::parse_from_str(input_date_str, "%Y-%m-%d")?;
-                driver
-                    .cmd(Command::NavigateTo(format!(
-                        "https://ssl.jobcan.jp/employee/adit/modify?year={}&month={}&day={}",
-                        &naive_date.year(),
-                        &naive_date.month(),
-                        &naive_date.day()
-                    )))
-                    .await?;
+        }
+        SubCommand::Status(status) => {
+            let working_status = jobcan::read_working_status(&driver, &config).await?;
+            let now = timezone.now();
+            let punches = jobcan::read_punches(&driver, &config, now.date()).await?;
+            let today = TodayStatus::new(working_status, punches, now.hour() * 60 + now.minute());
+
+            match status.format {
+                OutputFormat::Human => {
+                    info!("Now: {}", timezone.describe(now));
+                    today.print();
+                }
+                OutputFormat::Json => println!("{}", serde_json::to_string(&today)?),
+            }
+        }
+        SubCommand::Check(check) => {
+            let (from, to) = check_range(check, timezone.today())?;
+            let schedule = Schedule::parse(&config.schedule)?;
+
+            let mut findings = vec![];
+            let mut month = from.with_day(1).unwrap();
+            while month <= to {
+                for day in jobcan::read_attendance(&driver, &config, month).await? {
+                    if day.date < from || day.date > to {
+                        continue;
+                    }
+                    if let Some(issue) = check::find_issue(&day, &calendar, check.max_shift * 60) {
+                        let issue = if issue == check::Issue::MissingEnd {
+                            let punches = jobcan::read_punches(&driver, &config, day.date).await?;
+                            check::refine_with_punches(issue, &punches)
+                        } else {
+                            issue
+                        };
+                        findings.push(Finding {
+                            date: day.date,
+                            issue,
+                            fixes: check::suggest_fixes(issue, day.date, &schedule),
+                        });
+                    }
+                }
+                month = holidays::last_day_of_month(month).succ();
             }
 
-            let elem_note_time = driver.find_element(By::Id("ter_time")).await?;
-            elem_note_time.send_keys(&revise_data.time).await?;
+            if findings.is_empty() {
+                println!("No problems found between {} and {}.", from, to);
+            }
+            for finding in &findings {
+                println!(
+                    "{} {}: {}",
+                    finding.date,
+                    finding.date.weekday(),
+                    finding.issue
+                );
+                if finding.fixes.is_empty() {
+                    println!("    Please check this day on the Jobcan website.");
+                }
+                for fix in &finding.fixes {
+                    println!("    {}", fix.command_line());
+                    if check.fix && confirm("    Apply this fix?")? {
+                        apply_fix(&driver, &config, fix).await?;
+                    }
+                }
+            }
+        }
+        SubCommand::OvertimeCheck(overtime_check) => {
+            let today = timezone.today();
+            let month = match &overtime_check.date {
+                Some(input_date_str) => {
+                    NaiveDate::parse_from_str(&format!("{}01", input_date_str), "%Y%m%d")?
+                }
+                None => today.with_day(1).unwrap(),
+            };
+
+            let mut history = OvertimeHistory::load(&config.state_dir)?;
+            for past_month in overtime::months_to_check(&config.overtime_limits, month) {
+                // NOTE(dkg): Past months are final, only the running month has to be read again.
+                let is_running =
+                    past_month.year() == today.year() && past_month.month() == today.month();
+                if history.get(past_month).is_some() && !is_running {
+                    continue;
+                }
+                if let Some(minutes) = jobcan::read_overtime(&driver, &config, past_month).await? {
+                    history.set(past_month, minutes);
+                }
+            }
+            history.save(&config.state_dir)?;
 
-            let elem_note_field = driver
-                .find_element(By::Css("textarea[name='notice']"))
-                .await?;
-            elem_note_field.send_keys(&revise_data.message).await?;
-
-            let elem_insert_button = driver.find_element(By::Id("insert_button")).await?;
-            elem_insert_button.click().await?;
-
-            // Check for date or time errors
-            let elem_time_error = driver.find_element(By::Id("time_error")).await;
-            if let Ok(elem) = elem_time_error {
-                let elem_error = elem.find_element(By::ClassName("alert")).await;
-                if elem_error.is_ok() {
-                    error!("The format for the 'time' argument is wrong. Please check. It should be 'hhmm'.");
-                    if opts.visible && opts.sleep_time.is_none() {
-                        error!("Sleeping for 90 seconds. Please check the error display on the website.");
-                        thread::sleep(time::Duration::from_secs(90));
+            let warnings = overtime::check(&config.overtime_limits, &history, month);
+            match overtime_check.format {
+                OutputFormat::Human if warnings.is_empty() => {
+                    println!("All overtime limits are fine.")
+                }
+                OutputFormat::Human => {
+                    for warning in &warnings {
+                        println!("{}", warning);
                     }
-                    bail!("The 'time' argument has the wrong format. It should be 'hhmm'.");
                 }
+                OutputFormat::Json => println!("{}", serde_json::to_string(&warnings)?),
+            }
+
+            if let Some(exit_code) = overtime_exit_code(&warnings) {
+                driver.quit().await?;
+                std::process::exit(exit_code);
             }
         }
-        SubCommand::Login => {
-            driver
-                .cmd(Command::NavigateTo(String::from(
-                    "https://ssl.jobcan.jp/employee/adit/modify/",
-                )))
-                .await?;
+        SubCommand::Daemon(_)
+        | SubCommand::Remind(_)
+        | SubCommand::Report(_)
+        | SubCommand::MockServer(_)
+        | SubCommand::Slack(_)
+        | SubCommand::ServeSlack(_) => {
+            unreachable!("handled before the browser is started")
         }
         SubCommand::List(list) => {
             driver
-                .cmd(Command::NavigateTo(String::from(
-                    "https://ssl.jobcan.jp/employee/attendance",
-                )))
+                .cmd(Command::NavigateTo(config.endpoints.attendance.clone()))
                 .await?;
 
             thread::sleep(time::Duration::from_millis(3000));
 
             debug!("Checking if we were redirected to the partial error page ...");
 
-            wait_for_page_to_load(&driver).await?;
+            wait_for_page_to_load(&driver, &config.endpoints).await?;
+
+            let quiet = list.csv || list.format == OutputFormat::Json;
+            let month = match &list.date {
+                Some(input_date_str) => {
+                    NaiveDate::parse_from_str(&format!("{}01", input_date_str), "%Y%m%d")?
+                }
+                None => timezone.today().with_day(1).unwrap(),
+            };
 
             if let Some(input_date_str) = &list.date {
                 let full_input_date = format!("{}01", input_date_str); // format is YYYYMM
                 let naive_date = NaiveDate::parse_from_str(&full_input_date, "%Y%m%d")?;
 
                 driver
-                    .cmd(Command::NavigateTo(format!(
-                        "https://ssl.jobcan.jp/employee/attendance?list_type=normal&search_type=month&year={}&month={}",
-                        &naive_date.year(),
-                        &naive_date.month()
-                    )))
+                    .cmd(Command::NavigateTo(
+                        config.endpoints.attendance_for(naive_date),
+                    ))
                     .await?;
-                wait_for_page_to_load(&driver).await?;
+                wait_for_page_to_load(&driver, &config.endpoints).await?;
             }
 
-            if !list.csv {
+            if !quiet {
                 let title_element = driver.find_element(By::ClassName("card-title")).await;
                 if let Ok(title) = title_element {
                     info!("---------------------------");
@@ -331,6 +797,7 @@ async fn main() -> color_eyre::Result<()> {
                 let body = table.find_element(By::Tag("tbody")).await?;
                 let mut total_punched_minutes: u32 = 0;
                 let mut total_break_minutes: u32 = 0;
+                let mut day_records = vec![];
 
                 for tr in body.find_elements(By::Tag("tr")).await? {
                     let columns = tr.find_elements(By::Tag("td")).await?;
@@ -342,29 +809,53 @@ async fn main() -> color_eyre::Result<()> {
                         let column_break_time = &columns[COLUMN_BREAK_TIME];
 
                         let date = column_date.text().await?;
-                        let _holiday = column_holiday.text().await?;
+                        let holiday = column_holiday.text().await?;
                         let start_time = column_start_time.text().await?;
                         let end_time = column_end_time.text().await?;
                         let break_time = column_break_time.text().await?;
 
-                        if !list.csv {
+                        if !quiet {
+                            let day_off = day_off_label(&calendar, &holiday, &date, month.year());
                             info!(
-                                "{}: {} - {} (break: {})",
-                                date, start_time, end_time, break_time
+                                "{}: {} - {} (break: {}){}",
+                                date,
+                                start_time,
+                                end_time,
+                                break_time,
+                                day_off.map(|l| format!(" [{}]", l)).unwrap_or_default()
                             );
                         }
 
+                        if !start_time.is_empty() || !end_time.is_empty() {
+                            day_records.push(DayRecord {
+                                date: date.clone(),
+                                start: start_time.clone(),
+                                end: end_time.clone(),
+                                break_time: break_time.clone(),
+                            });
+                        }
+
                         if !start_time.is_empty() {
-                            let start = calc_minutes(&start_time);
-                            let end = calc_minutes(&end_time);
-                            if start.is_none() || end.is_none() {
-                                if !list.csv {
-                                    debug!("<--- previous ignored, either start or end is 0");
+                            let (start, end) = match (
+                                start_time.parse::<ClockTime>(),
+                                end_time.parse::<ClockTime>(),
+                            ) {
+                                (Ok(start), Ok(end)) => (start, end),
+                                _ => {
+                                    if !quiet {
+                                        debug!(
+                                            "<--- previous ignored, either start or end is missing"
+                                        );
+                                    }
+                                    continue;
                                 }
-                                continue;
-                            }
-                            let break_minutes = calc_minutes(&break_time).unwrap_or_default();
-                            let total_for_day = end.unwrap() - start.unwrap();
+                            };
+                            let break_minutes = break_time
+                                .parse::<WorkDuration>()
+                                .map(WorkDuration::minutes)
+                                .unwrap_or_default();
+                            // NOTE(dkg): Handles night shifts, where the end can be before the start.
+                            let total_for_day = start.until(end).minutes();
 
                             total_punched_minutes += total_for_day;
                             total_break_minutes += break_minutes;
@@ -372,7 +863,8 @@ async fn main() -> color_eyre::Result<()> {
                             if list.csv {
                                 // NOTE(dkg): With default language being Japanese, the output means the following
                                 // mm/dd, hh:mm (start); hh:mm (end), hh:mm (break duration), minutes (total work time without breaks)
-                                let total_for_day_without_breaks = total_for_day - break_minutes;
+                                let total_for_day_without_breaks =
+                                    total_for_day.saturating_sub(break_minutes);
                                 let hours = total_for_day_without_breaks / 60;
                                 let minutes = total_for_day_without_breaks % 60;
 
@@ -405,7 +897,7 @@ async fn main() -> color_eyre::Result<()> {
                         let worked_so_far = col_worked_so_far.text().await?;
                         let worked_expected = col_worked_expected.text().await?;
 
-                        if !list.csv {
+                        if !quiet {
                             info!("------------ Jobcan says ---------------");
                             info!("Worked  : {}", worked_so_far);
                             info!("Expected: {}", worked_expected);
@@ -420,17 +912,39 @@ async fn main() -> color_eyre::Result<()> {
                     None
                 };
 
+                if !quiet {
+                    let last_day = holidays::last_day_of_month(month);
+                    let working_days = calendar.working_days_between(month, last_day);
+                    let holidays_on_weekdays = calendar.weekday_holidays_between(month, last_day);
+                    let expected_minutes = working_days * config.daily_minutes;
+                    info!("------------ Calendar says -------------");
+                    info!(
+                        "Working days: {} ({} weekdays off)",
+                        working_days,
+                        holidays_on_weekdays.len()
+                    );
+                    for (date, name) in holidays_on_weekdays {
+                        info!("Day off     : {} {}", date.format("%m/%d"), name);
+                    }
+                    info!(
+                        "Expected    : {:02}:{:02}",
+                        expected_minutes / 60,
+                        expected_minutes % 60
+                    );
+                    info!("----------------------------------------");
+                }
+
                 let (punched_hours, punched_minutes) = if total_punched_minutes > 0 {
                     let hours_worked = total_punched_minutes / 60;
                     let minutes_worked = total_punched_minutes % 60;
                     let hours_break = total_break_minutes / 60;
                     let minutes_break = total_break_minutes % 60;
                     let total_punched_minutes_without_breaks =
-                        total_punched_minutes - total_break_minutes;
+                        total_punched_minutes.saturating_sub(total_break_minutes);
                     let hours_worked_no_breaks = total_punched_minutes_without_breaks / 60;
                     let minutes_worked_no_breaks = total_punched_minutes_without_breaks % 60;
 
-                    if !list.csv {
+                    if !quiet {
                         info!(
                             "\nTotal amount of time worked: {} minutes, or {:02}:{:02} hh:mm (breaks: {:02}:{:02})",
                             total_punched_minutes, hours_worked, minutes_worked, hours_break, minutes_break,
@@ -445,19 +959,57 @@ async fn main() -> color_eyre::Result<()> {
                     (0, 0)
                 };
 
+                // NOTE(dkg): Prefer Jobcan's numbers, they include vacations and the company's own settings.
+                let jobcan_minutes = jobcan_calculated_data.as_ref().map(|(expected, so_far)| {
+                    let parse = |total: &str| {
+                        total
+                            .parse::<WorkDuration>()
+                            .ok()
+                            .map(WorkDuration::minutes)
+                    };
+                    (parse(expected), parse(so_far))
+                });
+                let worked_minutes = jobcan_minutes
+                    .and_then(|(_, so_far)| so_far)
+                    .unwrap_or(punched_hours * 60 + punched_minutes);
+                let required_minutes = jobcan_minutes
+                    .and_then(|(expected, _)| expected)
+                    .unwrap_or_else(|| {
+                        calendar.working_days_between(month, holidays::last_day_of_month(month))
+                            * config.daily_minutes
+                    });
+                let forecast = Forecast::new(
+                    &calendar,
+                    month,
+                    timezone.today(),
+                    worked_minutes,
+                    required_minutes,
+                    config.daily_minutes,
+                );
+                match list.format {
+                    OutputFormat::Human if !list.csv => {
+                        info!("---------------------------");
+                        forecast.print();
+                    }
+                    OutputFormat::Human => (),
+                    OutputFormat::Json => println!("{}", serde_json::to_string(&forecast)?),
+                }
+
                 if let Some((expected, so_far)) = jobcan_calculated_data {
-                    if !list.csv {
+                    if !quiet {
                         info!("---------------------------");
                         info!("required {} and {}", expected, so_far);
-                        info!(
-                            "punched  {}:{} and {}:{}",
-                            punched_hours, punched_minutes, punched_hours, punched_minutes
-                        );
+                        info!("punched  {:02}:{:02}", punched_hours, punched_minutes);
                         info!("---------------------------");
                     }
                 }
 
-                if !list.csv {
+                if !quiet {
+                    let jobcan_worked = jobcan_minutes.and_then(|(_, so_far)| so_far);
+                    Reconciliation::new(&day_records, jobcan_worked).print();
+                }
+
+                if !quiet {
                     // NOTE(dkg): Get the working hours directly from jobcan
 
                     // press button to display stats
@@ -480,6 +1032,7 @@ async fn main() -> color_eyre::Result<()> {
                         "Day Hours",
                     ];
 
+                    let mut overtime_minutes = None;
                     for (index, tr) in body.find_elements(By::Tag("tr")).await?.iter().enumerate() {
                         // 1. row: Actual Working Hours
                         // 2. row: scheduled monthly working hours
@@ -494,6 +1047,12 @@ async fn main() -> color_eyre::Result<()> {
                             let column_value = &columns[0];
                             let value = column_value.text().await?;
                             info!("{}: {}", LABELS_WORKING_HOURS[index], value);
+                            if index == ROW_WITH_OVERTIME {
+                                overtime_minutes = value
+                                    .parse::<WorkDuration>()
+                                    .ok()
+                                    .map(WorkDuration::minutes);
+                            }
                         } else {
                             warn!(
                                 "Number of columns for row {} is wrong: {}, expected 1.",
@@ -504,6 +1063,15 @@ async fn main() -> color_eyre::Result<()> {
                     }
                     info!("---------------------------");
 
+                    if let Some(minutes) = overtime_minutes {
+                        let mut history = OvertimeHistory::load(&config.state_dir)?;
+                        history.set(month, minutes);
+                        history.save(&config.state_dir)?;
+                        for warning in overtime::check(&config.overtime_limits, &history, month) {
+                            warn!("{}", warning);
+                        }
+                    }
+
                     let table = &tables[INDEX_FOR_TABLE_WITH_VACATION_DATA];
                     let body = table.find_element(By::Tag("tbody")).await?;
                     const LABELS_VACATION: &[&str] = &[
@@ -546,48 +1114,375 @@ async fn main() -> color_eyre::Result<()> {
     Ok(())
 }
 
-async fn wait_for_page_to_load(driver: &WebDriver) -> color_eyre::Result<()> {
-    let mut i = 1;
-    let success = loop {
-        if i == 10 {
-            break false;
+/// Hours for `remind --after-hours`, at most a day.
+fn parse_after_hours(input: &str) -> Result<u32, String> {
+    parse_hours(input, 0..=24)
+}
+
+/// Hours for `check --max-shift`. Jobcan's working day is at most 26 hours, so 2 days is plenty.
+fn parse_max_shift(input: &str) -> Result<u32, String> {
+    parse_hours(input, 1..=48)
+}
+
+fn parse_hours(input: &str, range: std::ops::RangeInclusive<u32>) -> Result<u32, String> {
+    input
+        .trim()
+        .parse::<u32>()
+        .ok()
+        .filter(|hours| range.contains(hours))
+        .ok_or_else(|| {
+            format!(
+                "'{}' is not a number of hours between {} and {}",
+                input,
+                range.start(),
+                range.end()
+            )
+        })
+}
+
+/// The day and time for "revise clocking data", e.g. "--date yesterday --time 18:05" or "--time now-15m".
+fn resolve_revise(
+    revise_data: &ReviseClockingData,
+    now: NaiveDateTime,
+) -> color_eyre::Result<(NaiveDate, ClockTime)> {
+    let date = revise_data
+        .date
+        .as_deref()
+        .map(|date| input::parse_date(date, now.date()))
+        .transpose()?;
+    let time = input::parse_time(&revise_data.time)?;
+
+    input::resolve(date, time, now)
+}
+
+/// When `push-it --at`/`--minutes-ago` should punch. `None` means now, i.e. just press the button.
+fn resolve_push_at(
+    push_it: &PushIt,
+    now: NaiveDateTime,
+) -> color_eyre::Result<Option<(NaiveDate, ClockTime)>> {
+    let time = match (&push_it.at, push_it.minutes_ago) {
+        (Some(at), _) => input::parse_time(at)?,
+        (None, Some(minutes_ago)) => input::TimeInput::FromNow(-(minutes_ago as i64)),
+        (None, None) => return Ok(None),
+    };
+    let (date, time) = input::resolve(None, time, now)?;
+
+    let minutes_ago = (now - date.and_hms(0, 0, 0)).num_minutes() - time.minutes() as i64;
+    match minutes_ago {
+        0 => Ok(None),
+        minutes_ago if minutes_ago < 0 => bail!(
+            "{} is in the future. For a time of an earlier day please use revise-clock --date.",
+            time
+        ),
+        _ => Ok(Some((date, time))),
+    }
+}
+
+/// The days `check` looks at: the given month or range, but never today or later.
+fn check_range(check: &Check, today: NaiveDate) -> color_eyre::Result<(NaiveDate, NaiveDate)> {
+    let parse_date = |input: &str| {
+        NaiveDate::parse_from_str(input, "%Y-%m-%d").wrap_err("Unable to parse the date.")
+    };
+    let yesterday = today.pred();
+
+    let (from, to) = match (&check.from, &check.date) {
+        (Some(from), _) => (parse_date(from)?, yesterday),
+        (None, Some(month)) => {
+            let month = NaiveDate::parse_from_str(&format!("{}01", month), "%Y%m%d")
+                .wrap_err("Unable to parse the month.")?;
+            (month, holidays::last_day_of_month(month))
         }
-        let right_url = driver.current_url().await?;
-        if right_url.contains("error/partial-rate-limit") {
-            warn!("We are being rate limited. Try {} of 10.", i);
-            driver.back().await?;
-            thread::sleep(time::Duration::from_millis(5000));
-        } else {
-            break true;
+        (None, None) => (today.with_day(1).unwrap(), yesterday),
+    };
+    let to = match &check.to {
+        Some(to) => parse_date(to)?,
+        None => to,
+    }
+    .min(yesterday);
+
+    if from > to {
+        bail!("Nothing to check between {} and {}.", from, to);
+    }
+
+    Ok((from, to))
+}
+
+/// Ask a yes/no question on the terminal. Defaults to no.
+fn confirm(question: &str) -> color_eyre::Result<bool> {
+    use std::io::Write;
+
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+async fn apply_fix(
+    driver: &WebDriver,
+    config: &Configuration,
+    fix: &Fix,
+) -> color_eyre::Result<()> {
+    if jobcan::revise(driver, config, Some(fix.date), &fix.time, &fix.message).await? {
+        info!("Added {} at {} on {}.", fix.message, fix.time, fix.date);
+    } else {
+        error!(
+            "Jobcan did not accept {} at {} on {}.",
+            fix.message, fix.time, fix.date
+        );
+    }
+
+    Ok(())
+}
+
+/// Exit code for `overtime-check`: 1 when a limit is near, 2 when a limit is exceeded.
+fn overtime_exit_code(warnings: &[OvertimeWarning]) -> Option<i32> {
+    match warnings.iter().map(|w| w.severity).max() {
+        Some(Severity::Exceeded) => Some(2),
+        Some(Severity::Near) => Some(1),
+        None => None,
+    }
+}
+
+/// Refuse to punch on holidays, unless forced.
+fn check_holiday(
+    calendar: &HolidayCalendar,
+    date: NaiveDate,
+    force: bool,
+) -> color_eyre::Result<()> {
+    if let Some(name) = calendar.holiday_name(date) {
+        if !force {
+            bail!(
+                "{} is a holiday ({}). Use --force if you really want to punch.",
+                date,
+                name
+            );
         }
-        i += 1;
+        warn!("{} is a holiday ({}), punching anyway.", date, name);
+    }
+
+    Ok(())
+}
+
+/// What to show next to a day in the "list" output: Jobcan's own label first, then our calendar.
+/// `date` is the date column of the attendance table, e.g. "06/01(木)".
+fn day_off_label(
+    calendar: &HolidayCalendar,
+    jobcan_label: &str,
+    date: &str,
+    year: i32,
+) -> Option<String> {
+    let jobcan_label = jobcan_label.trim();
+    if !jobcan_label.is_empty() {
+        return Some(jobcan_label.to_owned());
+    }
+    let date =
+        NaiveDate::parse_from_str(&format!("{}/{}", year, date.get(..5)?), "%Y/%m/%d").ok()?;
+    calendar.holiday_name(date).map(String::from)
+}
+
+/// The "PUSH" flow: press the button and post the message to Slack, if configured.
+async fn push_and_post(
+    driver: &WebDriver,
+    config: &Configuration,
+    timezone: &JobcanTimezone,
+    push_it: &PushIt,
+    at: Option<(NaiveDate, ClockTime)>,
+) -> color_eyre::Result<()> {
+    // NOTE(dkg): Jobcan toggles between clock-in and clock-out, the status tells which one this is.
+    let before = jobcan::read_working_status(driver, config).await?;
+    let event = match before {
+        WorkingStatus::NotStarted => PunchEvent::ClockIn,
+        _ => PunchEvent::ClockOut,
     };
+    let group = push_it.group.as_deref().or_else(|| config.default_group());
+    let (date, time) = at.unwrap_or_else(|| now_on_clock(timezone));
 
-    if !success {
-        bail!("Rate limit could not be circumvented. Aborting.");
+    match at {
+        None => {
+            jobcan::push(driver, config, PunchKind::Work, &push_it.message, group).await?;
+            jobcan::verify_punch(driver, config, before).await?;
+        }
+        Some(_) => {
+            info!(
+                "Adding {} via revise clocking data ({})",
+                timezone.describe_clock(date, time),
+                time.to_jobcan()
+            );
+            let time = time.to_jobcan();
+            if !jobcan::revise(driver, config, Some(date), &time, &push_it.message).await? {
+                bail!("Jobcan did not accept the time {}.", time);
+            }
+        }
+    }
+
+    if !config.slack_status.is_empty() {
+        let punch_at = date.and_hms(0, 0, 0) + chrono::Duration::minutes(time.minutes() as i64);
+        if let Err(err) = slack::update_status(config, timezone, event, punch_at).await {
+            warn!(
+                "The punch was successful, but updating the Slack status failed: {}",
+                err
+            );
+        }
+    }
+
+    if config.has_notifiers() {
+        let details = PunchDetails {
+            group: group.unwrap_or_default().to_string(),
+            ..PunchDetails::new(event, date, time, &push_it.message)
+        };
+        let template = Some(push_it.slack_message.as_str()).filter(|message| !message.is_empty());
+        post_punch(
+            driver,
+            config,
+            timezone,
+            &push_it.slack_channel,
+            template,
+            details,
+        )
+        .await;
     }
 
     Ok(())
 }
 
-/// Turn timestamps like 06:45 into total minutes from 00:00 onwards.
-/// Example: 06:45 would be 6 * 60 + 45 = 360 + 45 = 405 minutes
-fn calc_minutes(time_string: &str) -> Option<u32> {
-    if time_string.is_empty() {
-        return None;
+/// Retry the Slack messages that failed on an earlier run. Slack still being unreachable is only a warning.
+async fn flush_outbox(config: &Configuration) {
+    match outbox::flush(config).await {
+        Ok(0) => (),
+        Ok(left) => warn!(
+            "{} Slack message(s) are still in the outbox, they are retried on the next run or with `slack flush`.",
+            left
+        ),
+        Err(err) => warn!("Could not read the Slack outbox: {}", err),
     }
-    if !time_string.contains(':') {
-        return None;
+}
+
+/// Tell the desktop about a failed punch, e.g. when the bot was started from a hotkey without a terminal.
+async fn desktop_on_failure<T>(
+    config: &Configuration,
+    what: &str,
+    result: color_eyre::Result<T>,
+) -> color_eyre::Result<T> {
+    if let (Err(err), true) = (&result, config.desktop_notifications) {
+        let summary = format!("Jobcan {} failed", what);
+        if let Err(notify_err) = notifier::show_desktop(summary, err.to_string()).await {
+            warn!("Could not show a desktop notification: {}", notify_err);
+        }
+    }
+    result
+}
+
+/// The current date and time on Jobcan's clock.
+fn now_on_clock(timezone: &JobcanTimezone) -> (NaiveDate, ClockTime) {
+    let now = timezone.now();
+    // NOTE(dkg): Can't fail, the hour of a NaiveDateTime is always below 24.
+    let time = ClockTime::from_hm(now.hour(), now.minute()).unwrap();
+    (now.date(), time)
+}
+
+/// Announce the punch on Slack and the webhooks, using the given template or the configured one for the event.
+/// NOTE(dkg): The punch is done at this point, so a notification problem must not turn it into a failure.
+async fn post_punch(
+    driver: &WebDriver,
+    config: &Configuration,
+    timezone: &JobcanTimezone,
+    channel: &str,
+    template: Option<&str>,
+    mut details: PunchDetails,
+) {
+    if !config.has_notifiers() {
+        return;
     }
-    if time_string.len() < 2 {
-        return None;
+    let webhooks = config.webhooks().unwrap_or_else(|err| {
+        warn!("{}", err);
+        vec![]
+    });
+    let configured = details
+        .event
+        .and_then(|event| config.slack_templates.for_event(event));
+    let slack_template = template.or(configured);
+    let has_template = slack_template.is_some();
+    let post_to_slack = config.can_post_to_slack() && has_template;
+    if !post_to_slack
+        && !config.desktop_notifications
+        && !webhooks
+            .iter()
+            .any(|webhook| webhook.wants(details.event, has_template))
+    {
+        return;
     }
-    let index = 2;
-    let (front, back) = time_string.split_at(index);
-    let hours = front[..index].parse::<u32>().ok()?;
-    let minutes = back[1..].parse::<u32>().ok()?;
+    let template = slack_template.unwrap_or(template::BLOCKS_FALLBACK_TEMPLATE);
 
-    Some(hours * 60 + minutes)
+    details.name = if config.slack_user_name.is_empty() {
+        config.login.clone()
+    } else {
+        config.slack_user_name.clone()
+    };
+    let today = timezone.now();
+    let is_clock_out = details.event == Some(PunchEvent::ClockOut);
+    let thread = if post_to_slack
+        && is_clock_out
+        && (config.slack_thread_clock_out || config.slack_total_on_clock_in)
+    {
+        DailyThread::load(&config.state_dir, &details.date)
+    } else {
+        None
+    };
+    let update_clock_in = thread.is_some() && config.slack_total_on_clock_in;
+    if template::uses(template, "worked_today") || update_clock_in {
+        match jobcan::read_punches(driver, config, today.date()).await {
+            Ok(punches) => {
+                let now_minutes = today.hour() * 60 + today.minute();
+                details.worked_today_minutes = Some(status::worked_minutes(&punches, now_minutes));
+            }
+            Err(err) => warn!(
+                "Could not read today's punches for the notification: {}",
+                err
+            ),
+        }
+    }
+    if template::uses(template, "remaining_month") {
+        let month = today.date().with_day(1).unwrap();
+        match jobcan::read_monthly_totals(driver, config, month).await {
+            Ok(totals) => details.remaining_month_minutes = totals.remaining(),
+            Err(err) => warn!(
+                "Could not read the monthly totals for the notification: {}",
+                err
+            ),
+        }
+    }
+
+    let message = match template::render(template, &details) {
+        Ok(message) => message,
+        Err(err) => {
+            warn!(
+                "The punch was successful, but the message could not be rendered: {}",
+                err
+            );
+            return;
+        }
+    };
+    let slack = Some(SlackNotifier {
+        config,
+        channel,
+        thread: thread.as_ref(),
+    })
+    .filter(|_| post_to_slack);
+    let notifiers = notifier::notifiers_for(
+        &webhooks,
+        details.event,
+        has_template,
+        slack,
+        config.desktop_notifications,
+    );
+    let notification = Notification {
+        details: &details,
+        message: &message,
+        at: today.format("%Y-%m-%d %H:%M").to_string(),
+    };
+    notifier::notify_all(&notifiers, &notification).await;
 }
 
 #[cfg(test)]
@@ -605,67 +1500,171 @@ mod tests {
     }
 
     #[test]
-    fn test_calc_minutes_works_1() {
-        let input = String::from("09:51");
-        let minutes = calc_minutes(&input).unwrap_or_default();
-
-        assert_eq!(9 * 60 + 51, minutes);
+    fn test_check_holiday() {
+        let calendar = HolidayCalendar::new("2021-12-29").unwrap();
+        let date = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+        assert!(check_holiday(&calendar, date("2021-11-03"), false).is_err());
+        assert!(check_holiday(&calendar, date("2021-11-03"), true).is_ok());
+        assert!(check_holiday(&calendar, date("2021-12-29"), false).is_err());
+        assert!(check_holiday(&calendar, date("2021-11-04"), false).is_ok());
     }
 
     #[test]
-    fn test_calc_minutes_works_2() {
-        let input = String::from("23:59");
-        let minutes = calc_minutes(&input).unwrap_or_default();
+    fn test_day_off_label() {
+        let calendar = HolidayCalendar::default();
 
-        assert_eq!(23 * 60 + 59, minutes);
+        assert_eq!(
+            Some(String::from("文化の日")),
+            day_off_label(&calendar, "", "11/03(水)", 2021)
+        );
+        assert_eq!(
+            Some(String::from("有休")),
+            day_off_label(&calendar, " 有休 ", "11/04(木)", 2021)
+        );
+        assert_eq!(None, day_off_label(&calendar, "", "11/04(木)", 2021));
+        assert_eq!(None, day_off_label(&calendar, "", "", 2021));
     }
 
     #[test]
-    fn test_calc_minutes_works_3() {
-        let input = String::from("00:01");
-        let minutes = calc_minutes(&input).unwrap_or_default();
-
-        assert_eq!(1, minutes);
+    fn test_overtime_exit_code() {
+        let warning = |severity| OvertimeWarning {
+            severity,
+            message: String::new(),
+        };
+
+        assert_eq!(None, overtime_exit_code(&[]));
+        assert_eq!(Some(1), overtime_exit_code(&[warning(Severity::Near)]));
+        assert_eq!(
+            Some(2),
+            overtime_exit_code(&[warning(Severity::Near), warning(Severity::Exceeded)])
+        );
     }
 
     #[test]
-    fn test_calc_minutes_returns_0_on_failure_1() {
-        let input = String::from("勤務中");
-        let minutes = calc_minutes(&input);
+    fn test_check_range() {
+        let date = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        let check = |date: Option<&str>, from: Option<&str>, to: Option<&str>| Check {
+            date: date.map(String::from),
+            from: from.map(String::from),
+            to: to.map(String::from),
+            max_shift: 16,
+            fix: false,
+        };
+        let today = date("2021-10-15");
 
-        assert_eq!(None, minutes);
+        assert_eq!(
+            (date("2021-10-01"), date("2021-10-14")),
+            check_range(&check(None, None, None), today).unwrap()
+        );
+        assert_eq!(
+            (date("2021-09-01"), date("2021-09-30")),
+            check_range(&check(Some("202109"), None, None), today).unwrap()
+        );
+        assert_eq!(
+            (date("2021-08-20"), date("2021-09-10")),
+            check_range(&check(None, Some("2021-08-20"), Some("2021-09-10")), today).unwrap()
+        );
+        assert!(check_range(&check(Some("202111"), None, None), today).is_err());
     }
 
     #[test]
-    fn test_calc_minutes_returns_0_on_failure_2() {
-        let input = String::from("11:mm");
-        let minutes = calc_minutes(&input);
-
-        assert_eq!(None, minutes);
+    fn test_parse_after_hours() {
+        assert_eq!(Ok(0), parse_after_hours("0"));
+        assert_eq!(Ok(24), parse_after_hours("24"));
+        assert!(parse_after_hours("25").is_err());
+        assert!(parse_after_hours("4294967295").is_err());
+        assert!(parse_after_hours("-1").is_err());
+
+        let remind = |hours| Opts::try_parse_from(["jobcan-bot", "remind", "--after-hours", hours]);
+        assert!(remind("3").is_ok());
+        assert!(remind("999999999").is_err());
     }
 
     #[test]
-    fn test_calc_minutes_returns_0_on_failure_3() {
-        let input = String::from("mm:11");
-        let minutes = calc_minutes(&input);
-
-        assert_eq!(None, minutes);
+    fn test_parse_max_shift() {
+        assert_eq!(Ok(16), parse_max_shift("16"));
+        assert!(parse_max_shift("0").is_err());
+        assert!(parse_max_shift("49").is_err());
+        assert!(parse_max_shift("4294967295").is_err());
     }
 
     #[test]
-    fn test_calc_minutes_returns_0_on_failure_4() {
-        let input = String::from(":");
-        let minutes = calc_minutes(&input);
+    fn test_resolve_revise() {
+        let revise = |date: Option<&str>, time: &str| ReviseClockingData {
+            date: date.map(String::from),
+            time: time.into(),
+            message: "work start".into(),
+            force: false,
+            slack_channel: String::new(),
+        };
+        // A Thursday
+        let now = NaiveDate::from_ymd(2021, 10, 14).and_hms(18, 20, 0);
+        let jobcan = |revise_data: ReviseClockingData| {
+            resolve_revise(&revise_data, now)
+                .map(|(date, time)| (date.to_string(), time.to_jobcan()))
+                .ok()
+        };
 
-        assert_eq!(None, minutes);
+        assert_eq!(
+            Some(("2021-10-14".into(), "0700".into())),
+            jobcan(revise(None, "0700"))
+        );
+        assert_eq!(
+            Some(("2021-10-13".into(), "0930".into())),
+            jobcan(revise(Some("yesterday"), "9:30"))
+        );
+        assert_eq!(
+            Some(("2021-10-11".into(), "2600".into())),
+            jobcan(revise(Some("mon"), "26:00"))
+        );
+        assert_eq!(
+            Some(("2021-10-14".into(), "1805".into())),
+            jobcan(revise(None, "now-15m"))
+        );
+        assert_eq!(None, jobcan(revise(None, "2601")));
+        assert_eq!(None, jobcan(revise(None, "700")));
+        assert_eq!(None, jobcan(revise(Some("someday"), "0700")));
     }
 
     #[test]
-    fn test_calc_minutes_returns_0_on_failure_5() {
-        let input = String::from("0:0");
-        let minutes = calc_minutes(&input);
-
-        assert_eq!(None, minutes);
+    fn test_resolve_push_at() {
+        let push_it = |at: Option<&str>, minutes_ago: Option<u32>| PushIt {
+            message: "work end".into(),
+            slack_message: String::new(),
+            slack_channel: String::new(),
+            group: None,
+            force: false,
+            at: at.map(String::from),
+            minutes_ago,
+        };
+        let now = NaiveDate::from_ymd(2021, 10, 14).and_hms(18, 20, 30);
+        let jobcan = |push_it: PushIt| {
+            resolve_push_at(&push_it, now)
+                .map(|at| at.map(|(date, time)| (date.to_string(), time.to_jobcan())))
+                .ok()
+        };
+
+        assert_eq!(Some(None), jobcan(push_it(None, None)));
+        assert_eq!(Some(None), jobcan(push_it(Some("18:20"), None)));
+        assert_eq!(Some(None), jobcan(push_it(Some("now"), None)));
+        assert_eq!(
+            Some(Some(("2021-10-14".into(), "1800".into()))),
+            jobcan(push_it(Some("18:00"), None))
+        );
+        assert_eq!(
+            Some(Some(("2021-10-14".into(), "1800".into()))),
+            jobcan(push_it(None, Some(20)))
+        );
+        assert_eq!(None, jobcan(push_it(Some("19:00"), None)));
+        assert_eq!(None, jobcan(push_it(Some("7pm"), None)));
+        // Errors, no overflow panics
+        assert_eq!(None, jobcan(push_it(Some("now-99999999999999"), None)));
+        assert_eq!(
+            None,
+            jobcan(push_it(Some("now-1000000000000000000h"), None))
+        );
+        assert_eq!(None, jobcan(push_it(None, Some(u32::MAX))));
     }
 
     // TODO(dkg): add more tests
@@ -0,0 +1,795 @@
+//! A small in-process mock of the Jobcan pages the bot touches.
+//!
+//! It uses the same paths as [`Endpoints`](crate::endpoints::Endpoints) and only implements enough of
+//! the markup (element ids, class names, table layout) for the WebDriver flows in `main` to work, so the bot can be run end to end against it, e.g.
+//!
+//! ```text
+//! jobcan-bot mock-server --port 4567
+//! JC_ID_BASE_URL=http://localhost:4567 JC_SSL_BASE_URL=http://localhost:4567 jobcan-bot push-it
+//! ```
+//!
+//! Both the "id" and the "ssl" host are served from the same address.
+use chrono::prelude::*;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
+use log::{debug, info};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use crate::endpoints::{
+    PATH_ADIT_MODIFY, PATH_ATTENDANCE, PATH_EMPLOYEE, PATH_OAUTH_LOGIN, PATH_RATE_LIMIT,
+    PATH_SIGN_IN,
+};
+use crate::timezone::JobcanTimezone;
+
+const SESSION_COOKIE: &str = "jobcan_mock_session";
+
+/// The real page punches via JavaScript, the mock uses a plain form post to this path.
+const PATH_ADIT: &str = "/employee/adit";
+
+/// 月規定労働時間 of every month. Anything worked on top of it is listed as overtime.
+const REQUIRED_MINUTES: u32 = 160 * 60;
+
+/// A single clock in/out entry recorded by the mock.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockPunch {
+    pub date: NaiveDate,
+    /// Minutes since midnight of `date`. Can be larger than 24 * 60 for the "2600" convention.
+    pub minutes: u32,
+    pub notice: String,
+    /// Break (休憩) punch instead of clocking in/out.
+    pub rest: bool,
+    /// Value of the selected group option, if any.
+    pub group_id: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct MockState {
+    pub login: String,
+    pub password: String,
+    /// How many of the next requests to the attendance page are redirected to the rate limit page.
+    pub rate_limited_requests: u32,
+    /// Hide the break buttons, like for tenants without the break punching feature.
+    pub rest_disabled: bool,
+    /// The options of the group select box as (value, name) pairs.
+    pub groups: Vec<(String, String)>,
+    /// Labels for the holiday column of the attendance list, e.g. 祝日 or 有休.
+    pub days_off: HashMap<NaiveDate, String>,
+    pub punches: Vec<MockPunch>,
+    /// Jobcan's clock, for "today" and the time of punches.
+    pub timezone: JobcanTimezone,
+}
+
+impl MockState {
+    pub fn new(login: &str, password: &str) -> Self {
+        MockState {
+            login: login.into(),
+            password: password.into(),
+            groups: vec![
+                (String::from("1"), String::from("Office")),
+                (String::from("2"), String::from("Remote")),
+                (String::from("3"), String::from("Client site")),
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn punches_for(&self, date: NaiveDate) -> Vec<&MockPunch> {
+        let mut punches: Vec<&MockPunch> = self.punches.iter().filter(|p| p.date == date).collect();
+        punches.sort_by_key(|p| p.minutes);
+        punches
+    }
+
+    fn work_punches_for(&self, date: NaiveDate) -> Vec<&MockPunch> {
+        self.punches_for(date)
+            .into_iter()
+            .filter(|p| !p.rest)
+            .collect()
+    }
+
+    fn rest_punches_for(&self, date: NaiveDate) -> Vec<&MockPunch> {
+        self.punches_for(date)
+            .into_iter()
+            .filter(|p| p.rest)
+            .collect()
+    }
+
+    /// Jobcan's wording for the current state: 未出勤, 勤務中, 休憩中 or 退室中.
+    fn working_status(&self, today: NaiveDate) -> &'static str {
+        let on_break = self.rest_punches_for(today).len() % 2 == 1;
+        match self.work_punches_for(today).len() {
+            0 => "未出勤",
+            n if n % 2 == 1 && on_break => "休憩中",
+            n if n % 2 == 1 => "勤務中",
+            _ => "退室中",
+        }
+    }
+}
+
+pub type SharedMockState = Arc<Mutex<MockState>>;
+
+/// Start the mock server on the given address and return the address it is actually bound to
+/// (useful when binding to port 0). The server runs on the current tokio runtime until it is dropped.
+pub fn spawn(addr: SocketAddr, state: SharedMockState) -> hyper::Result<SocketAddr> {
+    let make_svc = make_service_fn(move |_conn| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let state = state.clone();
+                async move { Ok::<_, Infallible>(handle(state, req).await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)?.serve(make_svc);
+    let local_addr = server.local_addr();
+    tokio::spawn(server);
+
+    Ok(local_addr)
+}
+
+/// Run the mock server until the process is terminated.
+pub async fn serve(addr: SocketAddr, state: SharedMockState) -> color_eyre::Result<()> {
+    let local_addr = spawn(addr, state)?;
+    info!("Mock Jobcan server listening on http://{}", local_addr);
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(3600)).await;
+    }
+}
+
+async fn handle(state: SharedMockState, req: Request<Body>) -> Response<Body> {
+    let method = req.method().clone();
+    let path = req.uri().path().trim_end_matches('/').to_string();
+    let query = parse_form(req.uri().query().unwrap_or_default().as_bytes());
+    let logged_in = has_session(&req);
+    let form = if method == Method::POST {
+        match hyper::body::to_bytes(req.into_body()).await {
+            Ok(bytes) => parse_form(&bytes),
+            Err(_) => HashMap::new(),
+        }
+    } else {
+        HashMap::new()
+    };
+
+    debug!("mock: {} {} (logged in: {})", method, path, logged_in);
+
+    let mut state = state.lock().unwrap();
+    let today = state.timezone.today();
+
+    match (method, path.as_str()) {
+        (Method::GET, PATH_SIGN_IN) => html(sign_in_page(None)),
+        (Method::POST, PATH_SIGN_IN) => {
+            let login = form.get("user[email]").cloned().unwrap_or_default();
+            let password = form.get("user[password]").cloned().unwrap_or_default();
+            if login == state.login && password == state.password {
+                let mut response = redirect("/");
+                response.headers_mut().insert(
+                    header::SET_COOKIE,
+                    format!("{}=1; Path=/", SESSION_COOKIE).parse().unwrap(),
+                );
+                response
+            } else {
+                html(sign_in_page(Some(
+                    "メールアドレスまたはパスワードが違います。",
+                )))
+            }
+        }
+        (Method::GET, "") => html(page("Jobcan ID", "<p>ログインしました。</p>")),
+        (_, _) if !logged_in => redirect(PATH_SIGN_IN),
+        (Method::GET, PATH_OAUTH_LOGIN) => redirect(PATH_EMPLOYEE),
+        (Method::GET, PATH_EMPLOYEE) => html(employee_page(&state, today)),
+        (Method::POST, PATH_ADIT) => {
+            let now = state.timezone.now();
+            let rest = form.get("adit_item").map(String::as_str) == Some("rest");
+            if rest && state.rest_disabled {
+                return Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .body(Body::from("Break punching is disabled."))
+                    .unwrap();
+            }
+            state.punches.push(MockPunch {
+                date: today,
+                minutes: now.hour() * 60 + now.minute(),
+                notice: form.get("notice").cloned().unwrap_or_default(),
+                rest,
+                group_id: form.get("adit_group_id").cloned(),
+            });
+            redirect(PATH_EMPLOYEE)
+        }
+        (Method::GET, PATH_ADIT_MODIFY) => {
+            let date = query_date(&query, today);
+            html(modify_page(&state, date, false))
+        }
+        (Method::POST, PATH_ADIT_MODIFY) => {
+            let date = query_date(&form, today);
+            match form.get("time").and_then(|t| parse_hhmm(t)) {
+                Some(minutes) => {
+                    state.punches.push(MockPunch {
+                        date,
+                        minutes,
+                        notice: form.get("notice").cloned().unwrap_or_default(),
+                        rest: false,
+                        group_id: None,
+                    });
+                    html(modify_page(&state, date, false))
+                }
+                None => html(modify_page(&state, date, true)),
+            }
+        }
+        (Method::GET, PATH_ATTENDANCE) => {
+            if state.rate_limited_requests > 0 {
+                state.rate_limited_requests -= 1;
+                redirect(PATH_RATE_LIMIT)
+            } else {
+                let month = query_month(&query, today);
+                html(attendance_page(&state, month))
+            }
+        }
+        (Method::GET, PATH_RATE_LIMIT) => html(page(
+            "Rate limit",
+            "<p>アクセスが集中しています。しばらくしてから再度お試しください。</p>",
+        )),
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from("Not Found"))
+            .unwrap(),
+    }
+}
+
+fn has_session(req: &Request<Body>) -> bool {
+    req.headers()
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .any(|cookie| cookie.trim().starts_with(&format!("{}=", SESSION_COOKIE)))
+}
+
+fn parse_form(bytes: &[u8]) -> HashMap<String, String> {
+    url::form_urlencoded::parse(bytes).into_owned().collect()
+}
+
+fn query_date(query: &HashMap<String, String>, default: NaiveDate) -> NaiveDate {
+    let get = |key: &str| query.get(key).and_then(|v| v.parse::<u32>().ok());
+    match (get("year"), get("month"), get("day")) {
+        (Some(year), Some(month), Some(day)) => {
+            NaiveDate::from_ymd_opt(year as i32, month, day).unwrap_or(default)
+        }
+        _ => default,
+    }
+}
+
+fn query_month(query: &HashMap<String, String>, default: NaiveDate) -> NaiveDate {
+    let get = |key: &str| query.get(key).and_then(|v| v.parse::<u32>().ok());
+    match (get("year"), get("month")) {
+        (Some(year), Some(month)) => NaiveDate::from_ymd_opt(year as i32, month, 1)
+            .unwrap_or_else(|| default.with_day(1).unwrap()),
+        _ => default.with_day(1).unwrap(),
+    }
+}
+
+/// Same validation as Jobcan: exactly four digits between 0000 and 2600.
+fn parse_hhmm(time: &str) -> Option<u32> {
+    if time.len() != 4 || !time.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours = time[..2].parse::<u32>().ok()?;
+    let minutes = time[2..].parse::<u32>().ok()?;
+    let total = hours * 60 + minutes;
+    if minutes >= 60 || total > 26 * 60 {
+        return None;
+    }
+    Some(total)
+}
+
+fn format_minutes(minutes: u32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+fn redirect(location: &str) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::FOUND)
+        .header(header::LOCATION, location)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn html(body: String) -> Response<Body> {
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn page(title: &str, content: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{}</title></head><body>{}</body></html>",
+        title, content
+    )
+}
+
+fn sign_in_page(error: Option<&str>) -> String {
+    let error = error
+        .map(|e| format!("<div class=\"alert\">{}</div>", e))
+        .unwrap_or_default();
+    page(
+        "ログイン",
+        &format!(
+            r#"{}<form class="form" method="post" action="{}">
+<input id="user_email" type="text" name="user[email]">
+<input id="user_password" type="password" name="user[password]">
+<input class="form__login" type="submit" value="ログイン">
+</form>"#,
+            error, PATH_SIGN_IN
+        ),
+    )
+}
+
+fn employee_page(state: &MockState, today: NaiveDate) -> String {
+    let groups: String = state
+        .groups
+        .iter()
+        .map(|(value, name)| format!("<option value=\"{}\">{}</option>", value, name))
+        .collect();
+    let rest_buttons = if !state.rest_disabled {
+        r#"<button id="adit-button-rest-start" type="submit" name="adit_item" value="rest">休憩開始</button>
+<button id="adit-button-rest-end" type="submit" name="adit_item" value="rest">休憩終了</button>"#
+    } else {
+        ""
+    };
+    page(
+        "打刻",
+        &format!(
+            r#"<p id="working_status">{}</p>
+<form method="post" action="{}">
+<select id="adit_group_id" name="adit_group_id">{}</select>
+<input id="notice_value" type="text" name="notice">
+<button id="adit-button-push" type="submit" name="adit_item" value="work">打刻</button>
+{}
+</form>"#,
+            state.working_status(today),
+            PATH_ADIT,
+            groups,
+            rest_buttons
+        ),
+    )
+}
+
+fn modify_page(state: &MockState, date: NaiveDate, time_error: bool) -> String {
+    let error = if time_error {
+        r#"<div class="alert">時刻を正しく入力してください。</div>"#
+    } else {
+        ""
+    };
+    let punches = state.punches_for(date);
+    let logs = if punches.is_empty() {
+        String::new()
+    } else {
+        let (mut work_count, mut rest_count) = (0, 0);
+        let rows: String = punches
+            .iter()
+            .map(|punch| {
+                let kind = if punch.rest {
+                    rest_count += 1;
+                    if rest_count % 2 == 1 {
+                        "休憩開始"
+                    } else {
+                        "休憩終了"
+                    }
+                } else {
+                    work_count += 1;
+                    if work_count % 2 == 1 {
+                        "出勤"
+                    } else {
+                        "退勤"
+                    }
+                };
+                format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    format_minutes(punch.minutes),
+                    kind,
+                    punch.notice
+                )
+            })
+            .collect();
+        format!(r#"<table id="logs-table"><tbody>{}</tbody></table>"#, rows)
+    };
+    page(
+        "打刻修正",
+        &format!(
+            r#"<form method="post" action="{}">
+<input type="hidden" name="year" value="{}">
+<input type="hidden" name="month" value="{}">
+<input type="hidden" name="day" value="{}">
+<input id="ter_time" type="text" name="time">
+<div id="time_error">{}</div>
+<textarea name="notice"></textarea>
+<button id="insert_button" type="submit">打刻</button>
+</form>{}"#,
+            PATH_ADIT_MODIFY,
+            date.year(),
+            date.month(),
+            date.day(),
+            error,
+            logs
+        ),
+    )
+}
+
+fn single_column_table(rows: &[(&str, String)]) -> String {
+    let rows: String = rows
+        .iter()
+        .map(|(label, value)| format!("<tr><th>{}</th><td>{}</td></tr>", label, value))
+        .collect();
+    format!("<table><tbody>{}</tbody></table>", rows)
+}
+
+fn attendance_page(state: &MockState, month: NaiveDate) -> String {
+    const WEEKDAYS: &[&str] = &["月", "火", "水", "木", "金", "土", "日"];
+
+    let mut worked_minutes = 0;
+    let mut rows = String::new();
+    let mut date = month;
+    while date.month() == month.month() {
+        let punches = state.work_punches_for(date);
+        let break_minutes: u32 = state
+            .rest_punches_for(date)
+            .chunks(2)
+            .filter(|pair| pair.len() == 2)
+            .map(|pair| pair[1].minutes - pair[0].minutes)
+            .sum();
+        let start = punches.first().map(|p| format_minutes(p.minutes));
+        // NOTE(dkg): is_multiple_of would need Rust 1.87.
+        #[allow(clippy::manual_is_multiple_of)]
+        let end = if punches.len() >= 2 && punches.len() % 2 == 0 {
+            let end = punches.last().unwrap().minutes;
+            worked_minutes += (end - punches[0].minutes).saturating_sub(break_minutes);
+            Some(format_minutes(end))
+        } else if !punches.is_empty() {
+            Some(String::from("勤務中"))
+        } else {
+            None
+        };
+        rows.push_str(&format!(
+            "<tr><td>{}({})</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            date.format("%m/%d"),
+            WEEKDAYS[date.weekday().num_days_from_monday() as usize],
+            state.days_off.get(&date).cloned().unwrap_or_default(),
+            start.unwrap_or_default(),
+            end.unwrap_or_default(),
+            if punches.is_empty() {
+                String::new()
+            } else {
+                format_minutes(break_minutes)
+            },
+        ));
+        date = date.succ();
+    }
+
+    // NOTE(dkg): The bot looks up the tables by index, so the order (and the filler tables) matter.
+    let tables = [
+        single_column_table(&[]),
+        single_column_table(&[]),
+        single_column_table(&[]),
+        single_column_table(&[
+            ("実労働時間", format_minutes(worked_minutes)),
+            ("月規定労働時間", format_minutes(REQUIRED_MINUTES)),
+            (
+                "残業時間",
+                format_minutes(worked_minutes.saturating_sub(REQUIRED_MINUTES)),
+            ),
+            ("深夜時間", String::from("00:00")),
+            ("平日時間", format_minutes(worked_minutes)),
+        ]),
+        single_column_table(&[
+            ("有給休暇", String::from("10.0日")),
+            ("代休", String::from("0.0日")),
+            ("振替休日", String::from("0.0日")),
+            ("特別休暇", String::from("0.0日")),
+        ]),
+        single_column_table(&[]),
+        format!("<table><tbody>{}</tbody></table>", rows),
+    ];
+
+    page(
+        "出勤簿",
+        &format!(
+            r#"<h5 class="card-title">{}</h5><button data-toggle="collapse">集計</button>{}"#,
+            month.format("%Y年%m月"),
+            tables.concat()
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::Client;
+
+    async fn start() -> (String, SharedMockState) {
+        let state = Arc::new(Mutex::new(MockState::new("me@example.com", "secret")));
+        let addr = spawn(([127, 0, 0, 1], 0).into(), state.clone()).unwrap();
+        (format!("http://{}", addr), state)
+    }
+
+    async fn request(
+        method: Method,
+        url: &str,
+        cookie: Option<&str>,
+        form: Option<&str>,
+    ) -> (StatusCode, Option<String>, String) {
+        let mut builder = Request::builder().method(method).uri(url);
+        if let Some(cookie) = cookie {
+            builder = builder.header(header::COOKIE, cookie);
+        }
+        let body = match form {
+            Some(form) => {
+                builder = builder.header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+                Body::from(form.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = Client::new()
+            .request(builder.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .map(|l| l.to_str().unwrap().to_string());
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, location, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    async fn login(base: &str) -> String {
+        let (status, _, _) = request(
+            Method::POST,
+            &format!("{}{}", base, PATH_SIGN_IN),
+            None,
+            Some("user%5Bemail%5D=me%40example.com&user%5Bpassword%5D=secret"),
+        )
+        .await;
+        assert_eq!(StatusCode::FOUND, status);
+        format!("{}=1", SESSION_COOKIE)
+    }
+
+    #[tokio::test]
+    async fn test_requires_login() {
+        let (base, _) = start().await;
+        let (status, location, _) = request(
+            Method::GET,
+            &format!("{}{}", base, PATH_EMPLOYEE),
+            None,
+            None,
+        )
+        .await;
+
+        assert_eq!(StatusCode::FOUND, status);
+        assert_eq!(Some(String::from(PATH_SIGN_IN)), location);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_password_is_rejected() {
+        let (base, _) = start().await;
+        let (status, _, body) = request(
+            Method::POST,
+            &format!("{}{}", base, PATH_SIGN_IN),
+            None,
+            Some("user%5Bemail%5D=me%40example.com&user%5Bpassword%5D=wrong"),
+        )
+        .await;
+
+        assert_eq!(StatusCode::OK, status);
+        assert!(body.contains("class=\"alert\""));
+    }
+
+    #[tokio::test]
+    async fn test_push_toggles_working_status() {
+        let (base, state) = start().await;
+        let cookie = login(&base).await;
+        let employee = format!("{}{}", base, PATH_EMPLOYEE);
+
+        let (status, location, _) = request(
+            Method::GET,
+            &format!("{}{}", base, PATH_OAUTH_LOGIN),
+            Some(&cookie),
+            None,
+        )
+        .await;
+        assert_eq!(StatusCode::FOUND, status);
+        assert_eq!(Some(String::from(PATH_EMPLOYEE)), location);
+
+        let (_, _, body) = request(Method::GET, &employee, Some(&cookie), None).await;
+        assert!(body.contains("未出勤"));
+
+        let adit = format!("{}{}", base, PATH_ADIT);
+        request(
+            Method::POST,
+            &adit,
+            Some(&cookie),
+            Some("notice=work+start"),
+        )
+        .await;
+        let (_, _, body) = request(Method::GET, &employee, Some(&cookie), None).await;
+        assert!(body.contains("勤務中"));
+
+        request(Method::POST, &adit, Some(&cookie), Some("adit_item=rest")).await;
+        let (_, _, body) = request(Method::GET, &employee, Some(&cookie), None).await;
+        assert!(body.contains("休憩中"));
+
+        request(Method::POST, &adit, Some(&cookie), Some("adit_item=rest")).await;
+        let (_, _, body) = request(Method::GET, &employee, Some(&cookie), None).await;
+        assert!(body.contains("勤務中"));
+
+        request(
+            Method::POST,
+            &adit,
+            Some(&cookie),
+            Some("notice=work+end&adit_group_id=2"),
+        )
+        .await;
+        let (_, _, body) = request(Method::GET, &employee, Some(&cookie), None).await;
+        assert!(body.contains("退室中"));
+
+        let state = state.lock().unwrap();
+        assert_eq!(4, state.punches.len());
+        assert_eq!("work start", state.punches[0].notice);
+        assert_eq!(Some(String::from("2")), state.punches[3].group_id);
+    }
+
+    /// The daemon's clock-out during a break: the break end has to show up as 勤務中 before the clock-out is
+    /// pushed, otherwise the clock-out can't be told apart from the break end.
+    #[tokio::test]
+    async fn test_break_end_before_clock_out() {
+        let (base, state) = start().await;
+        let cookie = login(&base).await;
+        let employee = format!("{}{}", base, PATH_EMPLOYEE);
+        let adit = format!("{}{}", base, PATH_ADIT);
+
+        request(
+            Method::POST,
+            &adit,
+            Some(&cookie),
+            Some("notice=work+start"),
+        )
+        .await;
+        request(Method::POST, &adit, Some(&cookie), Some("adit_item=rest")).await;
+        let (_, _, body) = request(Method::GET, &employee, Some(&cookie), None).await;
+        assert!(body.contains("休憩中"));
+
+        // The break end alone is what verify_punch(OnBreak) waits for
+        request(Method::POST, &adit, Some(&cookie), Some("adit_item=rest")).await;
+        let (_, _, body) = request(Method::GET, &employee, Some(&cookie), None).await;
+        assert!(body.contains("勤務中"));
+
+        // Only the clock-out after it finishes the day
+        request(Method::POST, &adit, Some(&cookie), Some("notice=work+end")).await;
+        let (_, _, body) = request(Method::GET, &employee, Some(&cookie), None).await;
+        assert!(body.contains("退室中"));
+
+        let state = state.lock().unwrap();
+        let kinds: Vec<bool> = state.punches.iter().map(|punch| punch.rest).collect();
+        assert_eq!(vec![false, true, true, false], kinds);
+    }
+
+    #[tokio::test]
+    async fn test_rest_buttons_can_be_disabled() {
+        let (base, state) = start().await;
+        state.lock().unwrap().rest_disabled = true;
+        let cookie = login(&base).await;
+
+        let (_, _, body) = request(
+            Method::GET,
+            &format!("{}{}", base, PATH_EMPLOYEE),
+            Some(&cookie),
+            None,
+        )
+        .await;
+        assert!(!body.contains("adit-button-rest-start"));
+
+        let (status, _, _) = request(
+            Method::POST,
+            &format!("{}{}", base, PATH_ADIT),
+            Some(&cookie),
+            Some("adit_item=rest"),
+        )
+        .await;
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+        assert!(state.lock().unwrap().punches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_modify_validates_time() {
+        let (base, state) = start().await;
+        let cookie = login(&base).await;
+        let modify = format!("{}{}", base, PATH_ADIT_MODIFY);
+
+        let (_, _, body) = request(
+            Method::POST,
+            &modify,
+            Some(&cookie),
+            Some("year=2021&month=10&day=4&time=7am&notice=x"),
+        )
+        .await;
+        assert!(body.contains("class=\"alert\""));
+
+        let (_, _, body) = request(
+            Method::POST,
+            &modify,
+            Some(&cookie),
+            Some("year=2021&month=10&day=4&time=2600&notice=late"),
+        )
+        .await;
+        assert!(!body.contains("class=\"alert\""));
+
+        let (_, _, body) = request(
+            Method::GET,
+            &format!("{}?year=2021&month=10&day=4", modify),
+            Some(&cookie),
+            None,
+        )
+        .await;
+        assert!(body.contains("<tr><td>26:00</td><td>出勤</td><td>late</td></tr>"));
+
+        let state = state.lock().unwrap();
+        assert_eq!(
+            vec![MockPunch {
+                date: NaiveDate::from_ymd(2021, 10, 4),
+                minutes: 26 * 60,
+                notice: String::from("late"),
+                rest: false,
+                group_id: None,
+            }],
+            state.punches
+        );
+    }
+
+    #[tokio::test]
+    async fn test_attendance_is_rate_limited_and_lists_punches() {
+        let (base, state) = start().await;
+        let cookie = login(&base).await;
+        {
+            let mut state = state.lock().unwrap();
+            state.rate_limited_requests = 1;
+            state
+                .days_off
+                .insert(NaiveDate::from_ymd(2021, 10, 11), String::from("祝日"));
+            for (minutes, rest) in &[
+                (9 * 60, false),
+                (12 * 60, true),
+                (13 * 60, true),
+                (18 * 60 + 30, false),
+            ] {
+                state.punches.push(MockPunch {
+                    date: NaiveDate::from_ymd(2021, 10, 4),
+                    minutes: *minutes,
+                    notice: String::new(),
+                    rest: *rest,
+                    group_id: None,
+                });
+            }
+        }
+        let attendance = format!(
+            "{}{}?list_type=normal&search_type=month&year=2021&month=10",
+            base, PATH_ATTENDANCE
+        );
+
+        let (status, location, _) = request(Method::GET, &attendance, Some(&cookie), None).await;
+        assert_eq!(StatusCode::FOUND, status);
+        assert_eq!(Some(String::from(PATH_RATE_LIMIT)), location);
+
+        let (status, _, body) = request(Method::GET, &attendance, Some(&cookie), None).await;
+        assert_eq!(StatusCode::OK, status);
+        assert_eq!(7, body.matches("<table>").count());
+        assert!(body.contains("2021年10月"));
+        assert!(
+            body.contains("<td>10/04(月)</td><td></td><td>09:00</td><td>18:30</td><td>01:00</td>")
+        );
+        assert!(body.contains("<th>実労働時間</th><td>08:30</td>"));
+        assert!(body.contains("<th>残業時間</th><td>00:00</td>"));
+        assert!(body.contains("<td>10/11(月)</td><td>祝日</td>"));
+    }
+}
@@ -0,0 +1,580 @@
+//! Where the punches are announced: Slack, incoming webhooks of Microsoft Teams, Discord, Mattermost or any
+//! HTTP endpoint that takes JSON, and desktop notifications.
+//!
+//! The webhooks come from JC_WEBHOOKS, separated by ";": `<kind>:<url> [events]`, e.g.
+//! `teams:https://example.webhook.office.com/... clock-in,clock-out; json:http://localhost:8080/punch`.
+use async_trait::async_trait;
+use color_eyre::eyre::{bail, eyre, WrapErr};
+use hyper::{Body, Client, Request};
+use hyper_rustls::HttpsConnector;
+use log::{debug, warn};
+use serde_json::{json, Value};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::config::Configuration;
+use crate::outbox;
+use crate::slack::{self, DailyThread};
+use crate::template::{PunchDetails, PunchEvent, RenderedMessage};
+
+/// The event name in JC_WEBHOOKS for problems, e.g. a failed automatic punch of the daemon.
+pub const ERROR_EVENT: &str = "error";
+
+/// Discord rejects longer messages.
+const DISCORD_MAX_CHARS: usize = 2000;
+
+/// The sender of the desktop notifications.
+const DESKTOP_APP_NAME: &str = "jobcan-bot";
+
+/// What is announced: the rendered template and the details it was rendered from.
+pub struct Notification<'a> {
+    /// No event means a problem, see ERROR_EVENT.
+    pub details: &'a PunchDetails,
+    pub message: &'a RenderedMessage,
+    /// When it happened on Jobcan's clock, yyyy-mm-dd hh:mm.
+    pub at: String,
+}
+
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// For the log, e.g. "Slack" or "Teams webhook".
+    fn name(&self) -> String;
+
+    async fn notify(&self, notification: &Notification<'_>) -> color_eyre::Result<()>;
+}
+
+/// Send the notification everywhere. A failing notifier is only a warning, the others still get it.
+pub async fn notify_all(notifiers: &[Box<dyn Notifier + '_>], notification: &Notification<'_>) {
+    for notifier in notifiers {
+        match notifier.notify(notification).await {
+            Ok(()) => debug!("Notified {}.", notifier.name()),
+            Err(err) => warn!("Notifying {} failed: {}", notifier.name(), err),
+        }
+    }
+}
+
+/// Slack, if given, plus the webhooks that want the event. `has_template` tells whether a template was set for it.
+/// The desktop gets every event.
+pub fn notifiers_for<'a>(
+    webhooks: &'a [Webhook],
+    event: Option<PunchEvent>,
+    has_template: bool,
+    slack: Option<SlackNotifier<'a>>,
+    desktop: bool,
+) -> Vec<Box<dyn Notifier + 'a>> {
+    let mut notifiers: Vec<Box<dyn Notifier + 'a>> = vec![];
+    if let Some(slack) = slack {
+        notifiers.push(Box::new(slack));
+    }
+    if desktop {
+        notifiers.push(Box::new(DesktopNotifier));
+    }
+    for webhook in webhooks
+        .iter()
+        .filter(|webhook| webhook.wants(event, has_template))
+    {
+        notifiers.push(Box::new(webhook.clone()));
+    }
+    notifiers
+}
+
+/// Posts to a Slack channel. A clock-out can go into the thread of the day's clock-in message, and messages are
+/// kept in the outbox when Slack can't be reached.
+pub struct SlackNotifier<'a> {
+    pub config: &'a Configuration,
+    pub channel: &'a str,
+    /// The day's clock-in message, when clocking out.
+    pub thread: Option<&'a DailyThread>,
+}
+
+#[async_trait]
+impl Notifier for SlackNotifier<'_> {
+    fn name(&self) -> String {
+        "Slack".to_string()
+    }
+
+    async fn notify(&self, notification: &Notification<'_>) -> color_eyre::Result<()> {
+        let config = self.config;
+        let details = notification.details;
+        let message = notification.message;
+
+        // NOTE(dkg): A reply goes to the channel of the clock-in message, whatever --slack-channel says.
+        let reply_to = self
+            .thread
+            .filter(|_| config.slack_thread_clock_out)
+            .map(|thread| &thread.posted);
+        let (channel, thread_ts) = match reply_to {
+            Some(reply_to) => (reply_to.channel.as_str(), Some(reply_to.ts.as_str())),
+            None => (self.channel, None),
+        };
+        let remember = config.slack_thread_clock_out || config.slack_total_on_clock_in;
+        let thread_date = Some(details.date.as_str())
+            .filter(|_| remember && details.event == Some(PunchEvent::ClockIn));
+        let posted = outbox::post_or_queue(
+            config,
+            channel,
+            message,
+            thread_ts,
+            thread_date,
+            &notification.at,
+        )
+        .await?;
+
+        if let (Some(posted), Some(date)) = (posted, thread_date) {
+            let thread = DailyThread {
+                date: date.to_string(),
+                posted,
+                message: message.clone(),
+            };
+            thread
+                .save(&config.state_dir)
+                .wrap_err("Could not remember the clock-in message for the clock-out")?;
+            return Ok(());
+        }
+
+        if let (Some(thread), Some(worked), true) = (
+            self.thread,
+            details.worked_today_minutes,
+            config.slack_total_on_clock_in,
+        ) {
+            let footer = format!(
+                "Worked today: {}",
+                crate::forecast::format_minutes(worked as i64)
+            );
+            slack::update_message(config, &thread.posted, &thread.message.with_footer(&footer))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A freedesktop notification (D-Bus) on the machine the bot runs on, e.g. for running it on a laptop.
+pub struct DesktopNotifier;
+
+impl DesktopNotifier {
+    /// The summary and body. Slack's markup would only get in the way here, so it is built from the details.
+    fn text(notification: &Notification<'_>) -> (String, String) {
+        let details = notification.details;
+        match (details.event, details.time) {
+            (Some(event), Some(time)) => (
+                format!("Jobcan {} at {}", event, time),
+                details.memo.clone(),
+            ),
+            (Some(event), None) => (format!("Jobcan {}", event), details.memo.clone()),
+            (None, _) => (
+                "Jobcan problem".to_string(),
+                notification.message.text.clone(),
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for DesktopNotifier {
+    fn name(&self) -> String {
+        "the desktop".to_string()
+    }
+
+    async fn notify(&self, notification: &Notification<'_>) -> color_eyre::Result<()> {
+        let (summary, body) = DesktopNotifier::text(notification);
+        show_desktop(summary, body).await
+    }
+}
+
+/// Show a desktop notification. Fails without a notification daemon, e.g. on a server.
+pub async fn show_desktop(summary: String, body: String) -> color_eyre::Result<()> {
+    // NOTE(dkg): Talking to D-Bus blocks, keep it off the runtime's threads.
+    tokio::task::spawn_blocking(move || {
+        notify_rust::Notification::new()
+            .appname(DESKTOP_APP_NAME)
+            .summary(&summary)
+            .body(&body)
+            .show()
+            .map(|_| ())
+    })
+    .await??;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebhookKind {
+    Teams,
+    Discord,
+    Mattermost,
+    /// The punch details as JSON, for anything else.
+    Json,
+}
+
+impl FromStr for WebhookKind {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "teams" => Ok(WebhookKind::Teams),
+            "discord" => Ok(WebhookKind::Discord),
+            "mattermost" => Ok(WebhookKind::Mattermost),
+            "json" | "http" => Ok(WebhookKind::Json),
+            other => Err(eyre!(
+                "Unknown webhook '{}'. Use teams, discord, mattermost or json.",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for WebhookKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            WebhookKind::Teams => "Teams",
+            WebhookKind::Discord => "Discord",
+            WebhookKind::Mattermost => "Mattermost",
+            WebhookKind::Json => "JSON",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Webhook {
+    pub kind: WebhookKind,
+    pub url: String,
+    /// Event names like "clock-in" or "error". None means the same as Slack: clock-in, clock-out, the events with a
+    /// template, and errors.
+    pub events: Option<Vec<String>>,
+}
+
+impl Webhook {
+    /// All webhooks from JC_WEBHOOKS. Empty means none.
+    pub fn parse_all(input: &str) -> color_eyre::Result<Vec<Self>> {
+        input
+            .split(';')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(Webhook::parse)
+            .collect()
+    }
+
+    /// `<kind>:<url> [events]`, e.g. "discord:https://discord.com/api/webhooks/... clock-in,clock-out".
+    pub fn parse(entry: &str) -> color_eyre::Result<Self> {
+        let mut parts = entry.split_whitespace();
+        let target = parts.next().unwrap_or_default();
+        let (kind, url) = target
+            .split_once(':')
+            .ok_or_else(|| eyre!("'{}' is not a webhook, use <kind>:<url>.", entry))?;
+        let kind = kind.parse::<WebhookKind>()?;
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            bail!(
+                "The URL of the {} webhook must start with http(s)://.",
+                kind
+            );
+        }
+        url.parse::<hyper::Uri>()
+            .map_err(|err| eyre!("'{}' is not a valid URL: {}", url, err))?;
+
+        let events = match parts.next() {
+            Some(events) => {
+                let events: Vec<String> = events.split(',').map(|e| e.trim().to_string()).collect();
+                for event in &events {
+                    if event != ERROR_EVENT && PunchEvent::from_key(event).is_none() {
+                        bail!(
+                            "Unknown event '{}' for the {} webhook. Use clock-in, clock-out, break-start, break-end, revise or error.",
+                            event,
+                            kind
+                        );
+                    }
+                }
+                Some(events)
+            }
+            None => None,
+        };
+        if let Some(rest) = parts.next() {
+            bail!(
+                "Unexpected '{}' after the events of the {} webhook.",
+                rest,
+                kind
+            );
+        }
+
+        Ok(Webhook {
+            kind,
+            url: url.to_string(),
+            events,
+        })
+    }
+
+    pub fn wants(&self, event: Option<PunchEvent>, has_template: bool) -> bool {
+        let key = event.map_or(ERROR_EVENT, |event| event.key());
+        match &self.events {
+            Some(events) => events.iter().any(|e| e == key),
+            None => match event {
+                Some(PunchEvent::ClockIn) | Some(PunchEvent::ClockOut) | None => true,
+                Some(_) => has_template,
+            },
+        }
+    }
+
+    /// The body for the webhook. Block Kit layouts are Slack only, the others get the text.
+    pub fn payload(&self, notification: &Notification<'_>) -> Value {
+        let text = &notification.message.text;
+        match self.kind {
+            WebhookKind::Teams => json!({ "text": text }),
+            WebhookKind::Discord => json!({
+                "content": text.chars().take(DISCORD_MAX_CHARS).collect::<String>()
+            }),
+            WebhookKind::Mattermost => json!({ "text": text }),
+            WebhookKind::Json => {
+                let details = notification.details;
+                json!({
+                    "event": details.event.map_or(ERROR_EVENT, |event| event.key()),
+                    "text": text,
+                    "name": details.name,
+                    "date": details.date,
+                    "time": details.time.map(|time| time.to_string()),
+                    "group": details.group,
+                    "memo": details.memo,
+                    "worked_today_minutes": details.worked_today_minutes,
+                    "remaining_month_minutes": details.remaining_month_minutes,
+                    "at": notification.at,
+                })
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for Webhook {
+    fn name(&self) -> String {
+        format!("the {} webhook", self.kind)
+    }
+
+    async fn notify(&self, notification: &Notification<'_>) -> color_eyre::Result<()> {
+        let body = self.payload(notification).to_string();
+        let request = Request::post(self.url.as_str())
+            .header("content-type", "application/json; charset=utf-8")
+            .body(Body::from(body))?;
+
+        let client = Client::builder().build::<_, Body>(HttpsConnector::with_native_roots());
+        let response = client.request(request).await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = hyper::body::to_bytes(response.into_body())
+                .await
+                .unwrap_or_default();
+            bail!(
+                "{} answered with {}: {}",
+                self.kind,
+                status,
+                String::from_utf8_lossy(&body)
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Response, Server, StatusCode};
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    type Received = Arc<Mutex<Vec<Value>>>;
+
+    /// A local webhook that answers with the given status and keeps the JSON it received.
+    fn stub(status: StatusCode) -> (SocketAddr, Received) {
+        let received: Received = Arc::new(Mutex::new(vec![]));
+        let shared = received.clone();
+        let make_svc = make_service_fn(move |_conn| {
+            let received = shared.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let received = received.clone();
+                    async move {
+                        let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                        received
+                            .lock()
+                            .unwrap()
+                            .push(serde_json::from_slice(&body).unwrap());
+                        Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(status)
+                                .body(Body::from("nope"))
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        (addr, received)
+    }
+
+    fn details() -> PunchDetails {
+        PunchDetails {
+            name: "dkg".into(),
+            group: "Office".into(),
+            ..PunchDetails::new(
+                PunchEvent::ClockIn,
+                chrono::NaiveDate::from_ymd(2021, 10, 14),
+                "09:05".parse().unwrap(),
+                "work start",
+            )
+        }
+    }
+
+    #[test]
+    fn test_parse_webhooks() {
+        let webhooks = Webhook::parse_all(
+            "teams:https://example.webhook.office.com/a clock-in,clock-out; json:http://localhost:8080/punch",
+        )
+        .unwrap();
+
+        assert_eq!(2, webhooks.len());
+        assert_eq!(WebhookKind::Teams, webhooks[0].kind);
+        assert_eq!("https://example.webhook.office.com/a", webhooks[0].url);
+        assert_eq!(
+            Some(vec!["clock-in".to_string(), "clock-out".to_string()]),
+            webhooks[0].events
+        );
+        assert_eq!(WebhookKind::Json, webhooks[1].kind);
+        assert_eq!(None, webhooks[1].events);
+
+        assert!(Webhook::parse_all("").unwrap().is_empty());
+        assert!(Webhook::parse("slack:https://example.com").is_err());
+        assert!(Webhook::parse("discord:ftp://example.com").is_err());
+        assert!(Webhook::parse("discord:https://example.com lunch").is_err());
+        assert!(Webhook::parse("https://example.com").is_err());
+    }
+
+    #[test]
+    fn test_wants() {
+        let all = Webhook::parse("json:http://localhost/").unwrap();
+        assert!(all.wants(Some(PunchEvent::ClockIn), false));
+        assert!(all.wants(None, false));
+        assert!(!all.wants(Some(PunchEvent::BreakStart), false));
+        assert!(all.wants(Some(PunchEvent::BreakStart), true));
+
+        let breaks = Webhook::parse("json:http://localhost/ break-start,break-end").unwrap();
+        assert!(breaks.wants(Some(PunchEvent::BreakStart), false));
+        assert!(!breaks.wants(Some(PunchEvent::ClockIn), true));
+        assert!(!breaks.wants(None, true));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_payloads() {
+        let (addr, received) = stub(StatusCode::OK);
+        let details = details();
+        let message = RenderedMessage::text(":sunrise: dkg started work");
+        let notification = Notification {
+            details: &details,
+            message: &message,
+            at: "2021-10-14 09:05".into(),
+        };
+
+        for kind in &["teams", "discord", "mattermost", "json"] {
+            let webhook = Webhook::parse(&format!("{}:http://{}/hook", kind, addr)).unwrap();
+            webhook.notify(&notification).await.unwrap();
+        }
+
+        let received = received.lock().unwrap();
+        assert_eq!(json!({"text": ":sunrise: dkg started work"}), received[0]);
+        assert_eq!(
+            json!({"content": ":sunrise: dkg started work"}),
+            received[1]
+        );
+        assert_eq!(json!({"text": ":sunrise: dkg started work"}), received[2]);
+        assert_eq!("clock-in", received[3]["event"]);
+        assert_eq!("09:05", received[3]["time"]);
+        assert_eq!("Office", received[3]["group"]);
+        assert_eq!("2021-10-14 09:05", received[3]["at"]);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_error() {
+        let (addr, _) = stub(StatusCode::NOT_FOUND);
+        let details = details();
+        let message = RenderedMessage::text("hi");
+        let notification = Notification {
+            details: &details,
+            message: &message,
+            at: String::new(),
+        };
+        let webhook = Webhook::parse(&format!("discord:http://{}/hook", addr)).unwrap();
+
+        let err = webhook.notify(&notification).await.unwrap_err();
+        assert!(err.to_string().contains("404"));
+    }
+
+    #[test]
+    fn test_desktop_text() {
+        let details = details();
+        let message = RenderedMessage::text(":sunrise: dkg started work");
+        let notification = Notification {
+            details: &details,
+            message: &message,
+            at: String::new(),
+        };
+        assert_eq!(
+            (
+                "Jobcan clock-in at 09:05".to_string(),
+                "work start".to_string()
+            ),
+            DesktopNotifier::text(&notification)
+        );
+
+        let details = PunchDetails {
+            memo: "Jobcan is down".into(),
+            ..Default::default()
+        };
+        let message = RenderedMessage::text("The automatic Jobcan clock-in failed: Jobcan is down");
+        let notification = Notification {
+            details: &details,
+            message: &message,
+            at: String::new(),
+        };
+        assert_eq!(
+            (
+                "Jobcan problem".to_string(),
+                "The automatic Jobcan clock-in failed: Jobcan is down".to_string()
+            ),
+            DesktopNotifier::text(&notification)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_notifiers_for() {
+        let (addr, received) = stub(StatusCode::NO_CONTENT);
+        let webhooks = Webhook::parse_all(&format!(
+            "mattermost:http://{}/a; json:http://{}/b clock-out",
+            addr, addr
+        ))
+        .unwrap();
+        let details = details();
+        let message = RenderedMessage::text("hi");
+        let notification = Notification {
+            details: &details,
+            message: &message,
+            at: String::new(),
+        };
+
+        let notifiers = notifiers_for(&webhooks, Some(PunchEvent::ClockIn), false, None, false);
+        assert_eq!(1, notifiers.len());
+        // Only the desktop takes a break without a template
+        let desktop_only =
+            notifiers_for(&webhooks, Some(PunchEvent::BreakStart), false, None, true);
+        assert_eq!(
+            vec!["the desktop"],
+            desktop_only.iter().map(|n| n.name()).collect::<Vec<_>>()
+        );
+        notify_all(&notifiers, &notification).await;
+
+        assert_eq!(vec![json!({"text": "hi"})], *received.lock().unwrap());
+    }
+}
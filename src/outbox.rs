@@ -0,0 +1,179 @@
+//! Slack messages that could not be posted, kept in the state directory until they can be.
+//!
+//! A network problem or a Slack outage must not lose the standup message, so failed posts are queued here and
+//! retried on the next run, by the daemon before every punch, or with `slack flush`.
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::config::Configuration;
+use crate::slack::{self, DailyThread, PostedMessage};
+use crate::template::RenderedMessage;
+
+const OUTBOX_FILE: &str = "slack_outbox.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    pub channel: String,
+    pub message: RenderedMessage,
+    pub thread_ts: Option<String>,
+    /// Set for a clock-in message: the working day to remember it for, so the clock-out can reply to it.
+    pub thread_date: Option<String>,
+    /// When it was queued, for the log. Jobcan's clock, yyyy-mm-dd hh:mm.
+    pub queued_at: String,
+    pub attempts: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Outbox {
+    pub messages: Vec<QueuedMessage>,
+}
+
+impl Outbox {
+    /// An empty outbox when nothing was queued yet.
+    pub fn load(state_dir: &Path) -> color_eyre::Result<Self> {
+        match fs::read_to_string(state_dir.join(OUTBOX_FILE)) {
+            Ok(data) => Ok(serde_json::from_str(&data)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Outbox::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, state_dir: &Path) -> color_eyre::Result<()> {
+        fs::create_dir_all(state_dir)?;
+        let path = state_dir.join(OUTBOX_FILE);
+        if self.messages.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+/// Post the message, or queue it if Slack can't be reached. Errors that a retry won't fix (e.g. an unknown
+/// channel) are returned instead. Ok(None) means the message was queued (or Slack is not configured).
+pub async fn post_or_queue(
+    config: &Configuration,
+    channel: &str,
+    message: &RenderedMessage,
+    thread_ts: Option<&str>,
+    thread_date: Option<&str>,
+    queued_at: &str,
+) -> color_eyre::Result<Option<PostedMessage>> {
+    match slack::post_message(config, channel, message, thread_ts).await {
+        Ok(posted) => Ok(posted),
+        Err(err) if err.is_retryable() => {
+            warn!(
+                "Could not post to Slack ({}), the message is kept in the outbox and posted on the next run.",
+                err
+            );
+            let mut outbox = Outbox::load(&config.state_dir)?;
+            outbox.messages.push(QueuedMessage {
+                channel: channel.to_string(),
+                message: message.clone(),
+                thread_ts: thread_ts.map(String::from),
+                thread_date: thread_date.map(String::from),
+                queued_at: queued_at.to_string(),
+                attempts: 1,
+            });
+            outbox.save(&config.state_dir)?;
+            Ok(None)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Post the queued messages in order. Returns how many are left, e.g. because Slack is still down.
+pub async fn flush(config: &Configuration) -> color_eyre::Result<usize> {
+    let mut outbox = Outbox::load(&config.state_dir)?;
+    if outbox.is_empty() || !config.can_post_to_slack() {
+        return Ok(outbox.messages.len());
+    }
+
+    let mut left = vec![];
+    for mut queued in outbox.messages.drain(..) {
+        // NOTE(dkg): Keep the order, once one fails the rest stays queued as well.
+        if !left.is_empty() {
+            left.push(queued);
+            continue;
+        }
+        let thread_ts = queued.thread_ts.as_deref();
+        match slack::post_message(config, &queued.channel, &queued.message, thread_ts).await {
+            Ok(posted) => {
+                info!(
+                    "Posted the Slack message queued at {} to {}.",
+                    queued.queued_at, queued.channel
+                );
+                remember_thread(config, &queued, posted);
+            }
+            Err(err) if err.is_retryable() => {
+                warn!("Still can't post to Slack: {}", err);
+                queued.attempts += 1;
+                left.push(queued);
+            }
+            Err(err) => {
+                warn!(
+                    "Dropping the Slack message queued at {} ('{}'): {}",
+                    queued.queued_at, queued.message.text, err
+                );
+            }
+        }
+    }
+
+    outbox.messages = left;
+    outbox.save(&config.state_dir)?;
+    Ok(outbox.messages.len())
+}
+
+fn remember_thread(config: &Configuration, queued: &QueuedMessage, posted: Option<PostedMessage>) {
+    if let (Some(date), Some(posted)) = (&queued.thread_date, posted) {
+        let thread = DailyThread {
+            date: date.clone(),
+            posted,
+            message: queued.message.clone(),
+        };
+        if let Err(err) = thread.save(&config.state_dir) {
+            warn!("Could not remember the clock-in message: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outbox_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("jobcan-bot-outbox-{}", std::process::id()));
+
+        assert_eq!(Outbox::default(), Outbox::load(&dir).unwrap());
+
+        let outbox = Outbox {
+            messages: vec![QueuedMessage {
+                channel: "#standup".into(),
+                message: RenderedMessage::text("work start"),
+                thread_ts: None,
+                thread_date: Some("2021-10-14".into()),
+                queued_at: "2021-10-14 09:00".into(),
+                attempts: 1,
+            }],
+        };
+        outbox.save(&dir).unwrap();
+        assert_eq!(outbox, Outbox::load(&dir).unwrap());
+
+        // An empty outbox removes the file
+        Outbox::default().save(&dir).unwrap();
+        assert!(!dir.join(OUTBOX_FILE).exists());
+        assert_eq!(Outbox::default(), Outbox::load(&dir).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
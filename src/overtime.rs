@@ -0,0 +1,389 @@
+//! Overtime limits of the Labor Standards Act (労働基準法) under a 36 agreement (36協定).
+//!
+//! Without a special clause the limits are 45 hours per month and 360 hours per year. With a special clause
+//! a month must stay under 100 hours and the average over any 2 to 6 consecutive months must not exceed 80 hours.
+//! NOTE(dkg): The 100 and 80 hours limits include work on legal holidays, which Jobcan does not count as
+//! overtime (残業). Lower the thresholds if that applies to you.
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::{fmt, fs};
+
+use crate::forecast::format_minutes;
+
+/// File in the state directory with the overtime of past months, so that they don't have to be read again.
+const HISTORY_FILE: &str = "overtime.json";
+
+/// Months of the rolling average check under the special clause.
+pub const AVERAGE_MONTHS: std::ops::RangeInclusive<u32> = 2..=6;
+
+/// How many months a year may go over the monthly limit under the special clause.
+const MAX_MONTHS_OVER_MONTHLY_LIMIT: usize = 6;
+
+/// The thresholds, all in minutes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OvertimeLimits {
+    /// Default: 45 hours
+    pub monthly: u32,
+    /// Default: 360 hours
+    pub yearly: u32,
+    /// Default: 100 hours
+    pub special_monthly: u32,
+    /// Average over 2 to 6 months. Default: 80 hours
+    pub average: u32,
+    /// Warn when this percentage of a limit is reached. Default: 80
+    pub warn_percent: u32,
+    /// First month of the agreement year. Default: 4 (April)
+    pub year_start_month: u32,
+}
+
+impl Default for OvertimeLimits {
+    fn default() -> Self {
+        OvertimeLimits {
+            monthly: 45 * 60,
+            yearly: 360 * 60,
+            special_monthly: 100 * 60,
+            average: 80 * 60,
+            warn_percent: 80,
+            year_start_month: 4,
+        }
+    }
+}
+
+impl OvertimeLimits {
+    /// The first month of the agreement year the given month belongs to.
+    pub fn year_start(&self, month: NaiveDate) -> NaiveDate {
+        let year = if month.month() >= self.year_start_month {
+            month.year()
+        } else {
+            month.year() - 1
+        };
+        NaiveDate::from_ymd(year, self.year_start_month, 1)
+    }
+
+    fn classify(&self, minutes: u32, limit: u32) -> Option<Severity> {
+        if minutes > limit {
+            Some(Severity::Exceeded)
+        } else if minutes as u64 * 100 >= limit as u64 * self.warn_percent as u64 {
+            Some(Severity::Near)
+        } else {
+            None
+        }
+    }
+}
+
+/// Overtime minutes per month ("yyyy-mm"), as read from Jobcan.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OvertimeHistory {
+    months: BTreeMap<String, u32>,
+}
+
+impl OvertimeHistory {
+    /// An empty history when nothing was saved yet.
+    pub fn load(state_dir: &Path) -> color_eyre::Result<Self> {
+        match fs::read_to_string(state_dir.join(HISTORY_FILE)) {
+            Ok(data) => Ok(serde_json::from_str(&data)?),
+            Err(_) => Ok(OvertimeHistory::default()),
+        }
+    }
+
+    pub fn save(&self, state_dir: &Path) -> color_eyre::Result<()> {
+        fs::create_dir_all(state_dir)?;
+        fs::write(state_dir.join(HISTORY_FILE), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, month: NaiveDate) -> Option<u32> {
+        self.months.get(&month_key(month)).copied()
+    }
+
+    pub fn set(&mut self, month: NaiveDate, minutes: u32) {
+        self.months.insert(month_key(month), minutes);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Near,
+    Exceeded,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OvertimeWarning {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for OvertimeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Near => write!(f, "Near the limit: {}", self.message),
+            Severity::Exceeded => write!(f, "Limit exceeded: {}", self.message),
+        }
+    }
+}
+
+/// All months that `check` looks at for the given month, oldest first.
+pub fn months_to_check(limits: &OvertimeLimits, month: NaiveDate) -> Vec<NaiveDate> {
+    let month = month.with_day(1).unwrap();
+    let first = limits
+        .year_start(month)
+        .min(months_before(month, *AVERAGE_MONTHS.end() - 1));
+    let count = month_index(month) - month_index(first);
+    (0..=count as u32)
+        .rev()
+        .map(|back| months_before(month, back))
+        .collect()
+}
+
+/// Check the given month against all limits. Months missing in the history are left out of the yearly total,
+/// and averages that would need them are skipped.
+pub fn check(
+    limits: &OvertimeLimits,
+    history: &OvertimeHistory,
+    month: NaiveDate,
+) -> Vec<OvertimeWarning> {
+    let month = month.with_day(1).unwrap();
+    let mut warnings = vec![];
+    let mut warn = |severity: Option<Severity>, message: String| {
+        if let Some(severity) = severity {
+            warnings.push(OvertimeWarning { severity, message });
+        }
+    };
+
+    if let Some(minutes) = history.get(month) {
+        warn(
+            limits.classify(minutes, limits.monthly),
+            format!(
+                "{} hours overtime in {}, the monthly limit is {}.",
+                format_minutes(minutes as i64),
+                month_key(month),
+                format_minutes(limits.monthly as i64)
+            ),
+        );
+        // NOTE(dkg): The special clause requires "less than" 100 hours.
+        let special = if minutes >= limits.special_monthly {
+            Some(Severity::Exceeded)
+        } else {
+            limits.classify(minutes, limits.special_monthly)
+        };
+        warn(
+            special,
+            format!(
+                "{} hours overtime in {}, it must stay below {} even with a special clause.",
+                format_minutes(minutes as i64),
+                month_key(month),
+                format_minutes(limits.special_monthly as i64)
+            ),
+        );
+    }
+
+    let year: Vec<u32> = months_to_check(limits, month)
+        .into_iter()
+        .filter(|m| *m >= limits.year_start(month))
+        .filter_map(|m| history.get(m))
+        .collect();
+    let year_total: u32 = year.iter().sum();
+    warn(
+        limits.classify(year_total, limits.yearly),
+        format!(
+            "{} hours overtime since {}, the yearly limit is {}.",
+            format_minutes(year_total as i64),
+            month_key(limits.year_start(month)),
+            format_minutes(limits.yearly as i64)
+        ),
+    );
+    let months_over = year.iter().filter(|m| **m > limits.monthly).count();
+    if months_over > MAX_MONTHS_OVER_MONTHLY_LIMIT {
+        warn(
+            Some(Severity::Exceeded),
+            format!(
+                "{} months over the monthly limit this year, at most {} are allowed.",
+                months_over, MAX_MONTHS_OVER_MONTHLY_LIMIT
+            ),
+        );
+    }
+
+    // NOTE(dkg): Only report the worst average, all of them are about the same limit.
+    let worst_average = AVERAGE_MONTHS
+        .filter_map(|count| {
+            let minutes = (0..count)
+                .map(|back| history.get(months_before(month, back)))
+                .collect::<Option<Vec<u32>>>()?;
+            Some((count, minutes.iter().sum::<u32>() / count))
+        })
+        .max_by_key(|(_, average)| *average);
+    if let Some((count, average)) = worst_average {
+        warn(
+            limits.classify(average, limits.average),
+            format!(
+                "{} hours overtime on average over the last {} months, the limit is {}.",
+                format_minutes(average as i64),
+                count,
+                format_minutes(limits.average as i64)
+            ),
+        );
+    }
+
+    warnings
+}
+
+fn month_key(month: NaiveDate) -> String {
+    month.format("%Y-%m").to_string()
+}
+
+/// Months since year 0, for month arithmetic.
+fn month_index(month: NaiveDate) -> i32 {
+    month.year() * 12 + month.month0() as i32
+}
+
+/// The first day of the month `count` months before the given one.
+fn months_before(month: NaiveDate, count: u32) -> NaiveDate {
+    let months = month_index(month) - count as i32;
+    NaiveDate::from_ymd(months.div_euclid(12), months.rem_euclid(12) as u32 + 1, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn month(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(&format!("{}-01", s), "%Y-%m-%d").unwrap()
+    }
+
+    fn history(months: &[(&str, u32)]) -> OvertimeHistory {
+        let mut history = OvertimeHistory::default();
+        for (m, hours) in months {
+            history.set(month(m), hours * 60);
+        }
+        history
+    }
+
+    #[test]
+    fn test_months_before() {
+        assert_eq!(month("2021-11"), months_before(month("2021-11"), 0));
+        assert_eq!(month("2020-12"), months_before(month("2021-01"), 1));
+        assert_eq!(month("2020-08"), months_before(month("2021-01"), 5));
+    }
+
+    #[test]
+    fn test_months_to_check() {
+        let limits = OvertimeLimits::default();
+
+        // April to November for the year, which covers the last 6 months as well
+        let months = months_to_check(&limits, month("2021-11"));
+        assert_eq!(8, months.len());
+        assert_eq!(month("2021-04"), months[0]);
+        assert_eq!(month("2021-11"), months[7]);
+
+        // May: the last 6 months reach back into the previous year
+        let months = months_to_check(&limits, month("2021-05"));
+        assert_eq!(month("2020-12"), months[0]);
+        assert_eq!(6, months.len());
+    }
+
+    #[test]
+    fn test_year_start() {
+        let limits = OvertimeLimits::default();
+
+        assert_eq!(month("2021-04"), limits.year_start(month("2021-04")));
+        assert_eq!(month("2020-04"), limits.year_start(month("2021-03")));
+    }
+
+    #[test]
+    fn test_check_within_limits() {
+        let limits = OvertimeLimits::default();
+        let history = history(&[("2021-10", 10), ("2021-11", 20)]);
+
+        assert!(check(&limits, &history, month("2021-11")).is_empty());
+    }
+
+    #[test]
+    fn test_check_monthly_limit() {
+        let limits = OvertimeLimits::default();
+
+        let warnings = check(&limits, &history(&[("2021-11", 40)]), month("2021-11"));
+        assert_eq!(1, warnings.len());
+        assert_eq!(Severity::Near, warnings[0].severity);
+
+        let warnings = check(&limits, &history(&[("2021-11", 50)]), month("2021-11"));
+        assert_eq!(1, warnings.len());
+        assert_eq!(Severity::Exceeded, warnings[0].severity);
+        assert!(warnings[0].message.contains("monthly limit"));
+    }
+
+    #[test]
+    fn test_check_special_clause_and_average() {
+        let limits = OvertimeLimits::default();
+        let history = history(&[("2021-10", 70), ("2021-11", 100)]);
+
+        let warnings = check(&limits, &history, month("2021-11"));
+        let exceeded: Vec<&str> = warnings
+            .iter()
+            .filter(|w| w.severity == Severity::Exceeded)
+            .map(|w| w.message.as_str())
+            .collect();
+        assert!(exceeded.iter().any(|m| m.contains("must stay below")));
+        assert!(exceeded
+            .iter()
+            .any(|m| m.contains("on average over the last 2 months")));
+    }
+
+    #[test]
+    fn test_check_yearly_limit() {
+        let limits = OvertimeLimits::default();
+        let history = history(&[
+            ("2021-03", 100),
+            ("2021-04", 44),
+            ("2021-05", 44),
+            ("2021-06", 44),
+            ("2021-07", 44),
+            ("2021-08", 44),
+            ("2021-09", 44),
+            ("2021-10", 44),
+            ("2021-11", 44),
+            ("2021-12", 44),
+        ]);
+
+        let warnings = check(&limits, &history, month("2021-12"));
+        // 2021-03 belongs to the previous year
+        assert!(warnings
+            .iter()
+            .any(|w| w.severity == Severity::Exceeded && w.message.contains("396:00")));
+    }
+
+    #[test]
+    fn test_check_months_over_limit() {
+        let limits = OvertimeLimits {
+            yearly: 1000 * 60,
+            average: 1000 * 60,
+            ..OvertimeLimits::default()
+        };
+        let months: Vec<(String, u32)> = (4..=10).map(|m| (format!("2021-{:02}", m), 50)).collect();
+        let months: Vec<(&str, u32)> = months.iter().map(|(m, h)| (m.as_str(), *h)).collect();
+
+        let warnings = check(&limits, &history(&months), month("2021-10"));
+        assert!(warnings
+            .iter()
+            .any(|w| w.message.contains("7 months over the monthly limit")));
+    }
+
+    #[test]
+    fn test_history_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("jobcan-bot-overtime-{}", std::process::id()));
+        let saved = history(&[("2021-11", 12)]);
+        saved.save(&dir).unwrap();
+
+        let loaded = OvertimeHistory::load(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(saved, loaded);
+        assert_eq!(Some(12 * 60), loaded.get(month("2021-11")));
+        assert_eq!(
+            OvertimeHistory::default(),
+            OvertimeHistory::load(&dir).unwrap()
+        );
+    }
+}
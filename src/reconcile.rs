@@ -0,0 +1,228 @@
+use log::info;
+use std::fmt;
+
+use crate::clock::{ClockTime, WorkDuration};
+use crate::forecast::format_minutes;
+
+/// A row of the attendance list, as shown by Jobcan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayRecord {
+    /// e.g. "06/01(木)"
+    pub date: String,
+    pub start: String,
+    pub end: String,
+    pub break_time: String,
+}
+
+impl DayRecord {
+    /// Worked minutes without breaks, or why the day can't be counted.
+    pub fn worked_minutes(&self) -> Result<u32, Problem> {
+        let start = self
+            .start
+            .parse::<ClockTime>()
+            .map_err(|_| Problem::MissingStart)?;
+        let end = match self.end.parse::<ClockTime>() {
+            Ok(end) => end,
+            Err(_) if self.end.contains("勤務中") => return Err(Problem::StillWorking),
+            Err(_) => return Err(Problem::MissingEnd),
+        };
+        let total = start.until(end).minutes();
+        if total > 24 * 60 {
+            return Err(Problem::LongerThanADay(total));
+        }
+        let break_minutes = self
+            .break_time
+            .parse::<WorkDuration>()
+            .map(WorkDuration::minutes)
+            .unwrap_or_default();
+
+        Ok(total.saturating_sub(break_minutes))
+    }
+}
+
+/// Why a day is likely to be responsible for a difference between the totals.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Problem {
+    MissingStart,
+    MissingEnd,
+    /// 勤務中: Jobcan counts the time until now, the bot can't.
+    StillWorking,
+    LongerThanADay(u32),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::MissingStart => write!(f, "no start punch"),
+            Problem::MissingEnd => write!(f, "no end punch"),
+            Problem::StillWorking => write!(f, "still working (勤務中)"),
+            Problem::LongerThanADay(minutes) => {
+                write!(
+                    f,
+                    "longer than 24h ({})",
+                    WorkDuration::from_minutes(*minutes)
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuspiciousDay {
+    pub date: String,
+    pub problem: Problem,
+}
+
+/// The bot's total compared to Jobcan's 実労働時間.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reconciliation {
+    pub bot_minutes: u32,
+    pub jobcan_minutes: Option<u32>,
+    /// Jobcan minus the bot.
+    pub difference_minutes: Option<i64>,
+    pub suspicious_days: Vec<SuspiciousDay>,
+}
+
+impl Reconciliation {
+    pub fn new(days: &[DayRecord], jobcan_minutes: Option<u32>) -> Self {
+        let mut bot_minutes = 0;
+        let mut suspicious_days = vec![];
+        for day in days {
+            match day.worked_minutes() {
+                Ok(minutes) => bot_minutes += minutes,
+                Err(problem) => suspicious_days.push(SuspiciousDay {
+                    date: day.date.clone(),
+                    problem,
+                }),
+            }
+        }
+
+        Reconciliation {
+            bot_minutes,
+            jobcan_minutes,
+            difference_minutes: jobcan_minutes.map(|jobcan| jobcan as i64 - bot_minutes as i64),
+            suspicious_days,
+        }
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.difference_minutes.unwrap_or_default() == 0 && self.suspicious_days.is_empty()
+    }
+
+    pub fn print(&self) {
+        info!("------------ Reconciliation ------------");
+        info!("Bot   : {}", format_minutes(self.bot_minutes as i64));
+        match (self.jobcan_minutes, self.difference_minutes) {
+            (Some(jobcan), Some(difference)) => {
+                info!("Jobcan: {}", format_minutes(jobcan as i64));
+                info!("Diff  : {}", format_minutes(difference));
+            }
+            _ => info!("Jobcan: unknown"),
+        }
+        if self.is_consistent() {
+            info!("The totals match.");
+        } else if self.suspicious_days.is_empty() {
+            info!("No day stands out, please compare the days with Jobcan's daily totals.");
+        } else {
+            info!("Please check these days:");
+            for day in &self.suspicious_days {
+                info!("{}: {}", day.date, day.problem);
+            }
+        }
+        info!("----------------------------------------");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(date: &str, start: &str, end: &str, break_time: &str) -> DayRecord {
+        DayRecord {
+            date: date.into(),
+            start: start.into(),
+            end: end.into(),
+            break_time: break_time.into(),
+        }
+    }
+
+    #[test]
+    fn test_worked_minutes() {
+        assert_eq!(
+            Ok(8 * 60),
+            day("10/01(金)", "09:00", "18:00", "01:00").worked_minutes()
+        );
+        assert_eq!(
+            Ok(9 * 60),
+            day("10/01(金)", "09:00", "18:00", "").worked_minutes()
+        );
+        assert_eq!(
+            Err(Problem::MissingEnd),
+            day("10/01(金)", "09:00", "", "").worked_minutes()
+        );
+        assert_eq!(
+            Err(Problem::StillWorking),
+            day("10/01(金)", "09:00", "勤務中", "").worked_minutes()
+        );
+        // Overnight
+        assert_eq!(
+            Ok(8 * 60),
+            day("10/01(金)", "22:00", "06:00", "").worked_minutes()
+        );
+        assert_eq!(
+            Err(Problem::LongerThanADay(26 * 60)),
+            day("10/01(金)", "00:00", "26:00", "").worked_minutes()
+        );
+        assert_eq!(
+            Err(Problem::MissingStart),
+            day("10/01(金)", "", "18:00", "").worked_minutes()
+        );
+    }
+
+    #[test]
+    fn test_reconciliation_matches() {
+        let days = vec![
+            day("10/01(金)", "09:00", "18:00", "01:00"),
+            day("10/04(月)", "09:00", "17:30", "00:30"),
+        ];
+        let reconciliation = Reconciliation::new(&days, Some(16 * 60));
+
+        assert_eq!(16 * 60, reconciliation.bot_minutes);
+        assert_eq!(Some(0), reconciliation.difference_minutes);
+        assert!(reconciliation.is_consistent());
+    }
+
+    #[test]
+    fn test_reconciliation_lists_suspicious_days() {
+        let days = vec![
+            day("10/01(金)", "09:00", "18:00", "01:00"),
+            day("10/04(月)", "09:00", "", ""),
+            day("10/05(火)", "09:00", "勤務中", ""),
+        ];
+        let reconciliation = Reconciliation::new(&days, Some(10 * 60));
+
+        assert_eq!(Some(2 * 60), reconciliation.difference_minutes);
+        assert!(!reconciliation.is_consistent());
+        assert_eq!(
+            vec![
+                SuspiciousDay {
+                    date: "10/04(月)".into(),
+                    problem: Problem::MissingEnd
+                },
+                SuspiciousDay {
+                    date: "10/05(火)".into(),
+                    problem: Problem::StillWorking
+                },
+            ],
+            reconciliation.suspicious_days
+        );
+    }
+
+    #[test]
+    fn test_reconciliation_without_jobcan_total() {
+        let reconciliation = Reconciliation::new(&[], None);
+
+        assert_eq!(None, reconciliation.difference_minutes);
+        assert!(reconciliation.is_consistent());
+    }
+}
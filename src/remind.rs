@@ -0,0 +1,159 @@
+//! Desktop reminders to clock out: at the end of the working day from JC_SCHEDULE and, if you are still
+//! clocked in, again some hours later.
+use chrono::prelude::*;
+use chrono::Duration;
+use color_eyre::eyre::{bail, eyre};
+use log::{info, warn};
+
+use crate::config::{Configuration, ENVVAR_SCHEDULE};
+use crate::daemon::sleep_until;
+use crate::jobcan;
+use crate::notifier;
+use crate::schedule::Schedule;
+use crate::status::WorkingStatus;
+use crate::Remind;
+
+pub async fn run(config: &Configuration, remind: &Remind, visible: bool) -> color_eyre::Result<()> {
+    let schedule = Schedule::parse(&config.schedule)?;
+    if schedule.is_empty() {
+        bail!(
+            "No schedule configured. Set {}, e.g. \"mon-fri 09:00-18:00\".",
+            ENVVAR_SCHEDULE
+        );
+    }
+    let calendar = config.holiday_calendar()?;
+    let timezone = config.jobcan_timezone()?;
+
+    let mut after = timezone.now();
+    loop {
+        let end = schedule
+            .next_clock_out(after)
+            .ok_or_else(|| eyre!("The schedule has no upcoming clock-out."))?;
+        after = end;
+
+        if let Some(name) = calendar.holiday_name(end.date()) {
+            info!("No reminder on {}: {}.", end.date(), name);
+            continue;
+        }
+
+        info!("Next clock-out reminder at {}.", timezone.describe(end));
+        sleep_until(&timezone, end).await;
+        if !remind_if_working(config, visible, end, None).await || remind.after_hours == 0 {
+            continue;
+        }
+
+        let late = end + Duration::hours(remind.after_hours as i64);
+        info!(
+            "Still clocked in, reminding again at {}.",
+            timezone.describe(late)
+        );
+        sleep_until(&timezone, late).await;
+        remind_if_working(config, visible, end, Some(remind.after_hours)).await;
+    }
+}
+
+/// Read the status from Jobcan and remind when still clocked in. Returns whether that was the case.
+/// NOTE(dkg): Jobcan or the desktop not being available is only a warning, the next day might work again.
+async fn remind_if_working(
+    config: &Configuration,
+    visible: bool,
+    end: NaiveDateTime,
+    hours_after: Option<u32>,
+) -> bool {
+    let status = match read_working_status(config, visible).await {
+        Ok(status) => status,
+        Err(err) => {
+            warn!("Could not read the working status: {}", err);
+            return false;
+        }
+    };
+    let (summary, body) = match reminder(status, end, hours_after) {
+        Some(reminder) => reminder,
+        None => {
+            info!("No reminder needed, the current status is '{}'.", status);
+            return false;
+        }
+    };
+
+    info!("{}: {}", summary, body);
+    if let Err(err) = notifier::show_desktop(summary, body).await {
+        warn!("Could not show the reminder: {}", err);
+    }
+    true
+}
+
+async fn read_working_status(
+    config: &Configuration,
+    visible: bool,
+) -> color_eyre::Result<WorkingStatus> {
+    let driver = jobcan::start_webdriver(visible).await?;
+    let result = async {
+        jobcan::login(&driver, config).await?;
+        jobcan::read_working_status(&driver, config).await
+    }
+    .await;
+    if let Err(err) = driver.quit().await {
+        warn!("Could not close the browser: {}", err);
+    }
+
+    result
+}
+
+/// The summary and body of the reminder, or None when already clocked out (or not clocked in at all).
+/// `hours_after` is set for the second reminder.
+fn reminder(
+    status: WorkingStatus,
+    end: NaiveDateTime,
+    hours_after: Option<u32>,
+) -> Option<(String, String)> {
+    let state = match status {
+        WorkingStatus::Working => "clocked in",
+        WorkingStatus::OnBreak => "on a break",
+        WorkingStatus::NotStarted | WorkingStatus::Finished | WorkingStatus::Unknown => {
+            return None
+        }
+    };
+    let end = end.format("%H:%M");
+    let reminder = match hours_after {
+        None => (
+            "Time to clock out".to_string(),
+            format!("Your working day ended at {}, you are still {}.", end, state),
+        ),
+        Some(hours) => (
+            format!("Still {}", state),
+            format!(
+                "It is {} hour(s) after the end of your working day ({}). Did you forget to clock out?",
+                hours, end
+            ),
+        ),
+    };
+    Some(reminder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reminder() {
+        let end = NaiveDate::from_ymd(2021, 10, 14).and_hms(18, 0, 0);
+
+        assert_eq!(
+            Some((
+                "Time to clock out".to_string(),
+                "Your working day ended at 18:00, you are still clocked in.".to_string()
+            )),
+            reminder(WorkingStatus::Working, end, None)
+        );
+        assert_eq!(
+            Some((
+                "Still on a break".to_string(),
+                "It is 2 hour(s) after the end of your working day (18:00). Did you forget to clock out?"
+                    .to_string()
+            )),
+            reminder(WorkingStatus::OnBreak, end, Some(2))
+        );
+        assert_eq!(None, reminder(WorkingStatus::Finished, end, None));
+        assert_eq!(None, reminder(WorkingStatus::NotStarted, end, Some(2)));
+    }
+}
@@ -0,0 +1,522 @@
+//! The monthly summary for managers: the days of the month, Jobcan's totals, the overtime limits and the vacation
+//! balances, as plain text and HTML for an email.
+use chrono::prelude::*;
+use color_eyre::eyre::bail;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use thirtyfour::prelude::*;
+
+use crate::config::{Configuration, ENVVAR_SMTP_HOST};
+use crate::email;
+use crate::forecast::{format_minutes, Forecast};
+use crate::holidays::{self, HolidayCalendar};
+use crate::jobcan::{self, MonthlyTotals, VacationBalances};
+use crate::overtime::{self, OvertimeHistory, OvertimeWarning};
+use crate::slack_bot::{self, SlackAccounts};
+use crate::timezone::JobcanTimezone;
+use crate::Report;
+
+/// The month of the last report the daemon sent.
+const LAST_REPORT_FILE: &str = "email_report.json";
+
+/// A day of the month as Jobcan lists it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportDay {
+    /// As Jobcan shows it, e.g. "10/04(月)".
+    pub date: String,
+    pub start: String,
+    pub end: String,
+    pub break_time: String,
+    /// The holiday or vacation, e.g. 有休 or 体育の日. Empty on working days.
+    pub note: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonthlyReport {
+    /// The team member, SLACK_USER_NAME or the Jobcan login.
+    pub name: String,
+    /// yyyy-mm
+    pub month: String,
+    pub days: Vec<ReportDay>,
+    pub totals: MonthlyTotals,
+    pub forecast: Forecast,
+    pub overtime_warnings: Vec<OvertimeWarning>,
+    pub vacations: VacationBalances,
+}
+
+impl MonthlyReport {
+    /// Read everything for the report from Jobcan. `month` is any day of the month.
+    pub async fn read(
+        driver: &WebDriver,
+        config: &Configuration,
+        calendar: &HolidayCalendar,
+        today: NaiveDate,
+        name: &str,
+        month: NaiveDate,
+    ) -> color_eyre::Result<Self> {
+        let month = month.with_day(1).unwrap();
+        let days = jobcan::read_attendance(driver, config, month)
+            .await?
+            .into_iter()
+            .map(|day| {
+                let note = if jobcan::is_day_off_label(&day.holiday) {
+                    day.holiday.clone()
+                } else {
+                    calendar
+                        .holiday_name(day.date)
+                        .map(String::from)
+                        .unwrap_or_default()
+                };
+                ReportDay {
+                    date: day.record.date,
+                    start: day.record.start,
+                    end: day.record.end,
+                    break_time: day.record.break_time,
+                    note,
+                }
+            })
+            .collect();
+        let totals = jobcan::read_monthly_totals(driver, config, month).await?;
+        let vacations = jobcan::read_vacation_balances(driver, config, month).await?;
+
+        let mut history = OvertimeHistory::load(&config.state_dir)?;
+        if let Some(minutes) = totals.overtime {
+            history.set(month, minutes);
+            history.save(&config.state_dir)?;
+        }
+        let overtime_warnings = overtime::check(&config.overtime_limits, &history, month);
+
+        let required_minutes = totals.required.unwrap_or_else(|| {
+            calendar.working_days_between(month, holidays::last_day_of_month(month))
+                * config.daily_minutes
+        });
+        let forecast = Forecast::new(
+            calendar,
+            month,
+            today,
+            totals.worked.unwrap_or_default(),
+            required_minutes,
+            config.daily_minutes,
+        );
+
+        Ok(MonthlyReport {
+            name: name.to_string(),
+            month: month.format("%Y-%m").to_string(),
+            days,
+            totals,
+            forecast,
+            overtime_warnings,
+            vacations,
+        })
+    }
+
+    pub fn subject(&self) -> String {
+        format!("Jobcan report for {}: {}", self.name, self.month)
+    }
+
+    /// The label and value of the totals, in the order they are shown.
+    fn summary(&self) -> Vec<(&'static str, String)> {
+        let minutes =
+            |minutes: Option<u32>| minutes.map_or("-".to_string(), |m| format_minutes(m as i64));
+        let forecast = &self.forecast;
+        vec![
+            ("Worked", format_minutes(forecast.worked_minutes as i64)),
+            ("Required", format_minutes(forecast.required_minutes as i64)),
+            (
+                "Still required",
+                format_minutes(forecast.remaining_minutes as i64),
+            ),
+            ("Overtime", minutes(self.totals.overtime)),
+            (
+                "Projected",
+                format!(
+                    "{} (overtime: {})",
+                    format_minutes(forecast.projected_minutes as i64),
+                    format_minutes(forecast.projected_overtime_minutes)
+                ),
+            ),
+        ]
+    }
+
+    fn vacation_rows(&self) -> Vec<(&'static str, &str)> {
+        vec![
+            ("Paid vacation", self.vacations.paid.as_str()),
+            (
+                "Compensatory days off",
+                self.vacations.compensatory.as_str(),
+            ),
+            ("Substitution days", self.vacations.substitution.as_str()),
+            ("Special vacation", self.vacations.special.as_str()),
+        ]
+    }
+
+    fn overtime_lines(&self) -> Vec<String> {
+        if self.overtime_warnings.is_empty() {
+            return vec!["All overtime limits are fine.".to_string()];
+        }
+        self.overtime_warnings
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        // NOTE(dkg): Writing to a String can't fail.
+        let _ = writeln!(text, "{}\n", self.subject());
+        let _ = writeln!(
+            text,
+            "{:<11} {:<5}  {:<5}  {:<5}",
+            "Day", "Start", "End", "Break"
+        );
+        for day in &self.days {
+            let line = format!(
+                "{:<11} {:<5}  {:<5}  {:<5}  {}",
+                day.date, day.start, day.end, day.break_time, day.note
+            );
+            let _ = writeln!(text, "{}", line.trim_end());
+        }
+
+        let _ = writeln!(text);
+        for (label, value) in self.summary() {
+            let _ = writeln!(text, "{:<14}: {}", label, value);
+        }
+
+        let _ = writeln!(text, "\nVacation balances");
+        for (label, value) in self.vacation_rows() {
+            let _ = writeln!(text, "{:<21}: {}", label, value);
+        }
+
+        let _ = writeln!(text, "\nOvertime limits");
+        for line in self.overtime_lines() {
+            let _ = writeln!(text, "{}", line);
+        }
+
+        text
+    }
+
+    pub fn html(&self) -> String {
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<html><body><h2>{}</h2>\n<table border=\"1\" cellpadding=\"4\" cellspacing=\"0\">\n\
+             <tr><th>Day</th><th>Start</th><th>End</th><th>Break</th><th></th></tr>\n",
+            escape(&self.subject())
+        );
+        for day in &self.days {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&day.date),
+                escape(&day.start),
+                escape(&day.end),
+                escape(&day.break_time),
+                escape(&day.note)
+            );
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h3>Totals</h3>\n<table>\n");
+        for (label, value) in self.summary() {
+            let _ = writeln!(
+                html,
+                "<tr><th align=\"left\">{}</th><td>{}</td></tr>",
+                label,
+                escape(&value)
+            );
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h3>Vacation balances</h3>\n<table>\n");
+        for (label, value) in self.vacation_rows() {
+            let _ = writeln!(
+                html,
+                "<tr><th align=\"left\">{}</th><td>{}</td></tr>",
+                label,
+                escape(value)
+            );
+        }
+        html.push_str("</table>\n");
+
+        html.push_str("<h3>Overtime limits</h3>\n<ul>\n");
+        for line in self.overtime_lines() {
+            let _ = writeln!(html, "<li>{}</li>", escape(&line));
+        }
+        html.push_str("</ul>\n</body></html>\n");
+
+        html
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The `report` subcommand: print the report, or email it with --email.
+pub async fn run(config: &Configuration, report: &Report, visible: bool) -> color_eyre::Result<()> {
+    let timezone = config.jobcan_timezone()?;
+    let month = match &report.date {
+        Some(input_date_str) => {
+            NaiveDate::parse_from_str(&format!("{}01", input_date_str), "%Y%m%d")?
+        }
+        None => timezone.today().with_day(1).unwrap(),
+    };
+    if report.email {
+        if config.email.is_empty() {
+            bail!("Email is not configured, please set {}.", ENVVAR_SMTP_HOST);
+        }
+        config.email.validate()?;
+    }
+
+    let members = if report.linked {
+        let accounts = SlackAccounts::load(&config.state_dir)?;
+        if accounts.accounts.is_empty() {
+            bail!("No Jobcan accounts are linked, see `slack link`.");
+        }
+        accounts
+            .accounts
+            .iter()
+            .map(|(user_id, account)| {
+                (
+                    account.login.clone(),
+                    slack_bot::config_for(config, user_id, account),
+                )
+            })
+            .collect()
+    } else {
+        vec![(member_name(config), config.clone())]
+    };
+
+    for (name, member_config) in &members {
+        let result =
+            send_report(member_config, &timezone, visible, name, month, report.email).await;
+        // NOTE(dkg): One broken account must not keep the others from getting their report.
+        match result {
+            Err(err) if members.len() > 1 => warn!("The report for {} failed: {}", name, err),
+            result => result?,
+        }
+    }
+
+    Ok(())
+}
+
+/// The daemon's monthly email: the report of the previous month, on EMAIL_REPORT_DAY or the first scheduled punch
+/// after it. Problems are only a warning, the next scheduled punch tries again.
+pub async fn send_if_due(config: &Configuration, timezone: &JobcanTimezone, visible: bool) {
+    let report_day = match config.email.report_day() {
+        Ok(Some(day)) if !config.email.is_empty() => day,
+        _ => return,
+    };
+    let today = timezone.today();
+    let month = match previous_month_due(today, report_day, &LastReport::load(&config.state_dir)) {
+        Some(month) => month,
+        None => return,
+    };
+
+    let name = member_name(config);
+    match send_report(config, timezone, visible, &name, month, true).await {
+        Ok(()) => {
+            let last = LastReport {
+                month: month.format("%Y-%m").to_string(),
+            };
+            if let Err(err) = last.save(&config.state_dir) {
+                warn!("Could not remember the sent report: {}", err);
+            }
+        }
+        Err(err) => warn!("Could not send the monthly report: {}", err),
+    }
+}
+
+/// The first day of the month the report is due for, if it was not sent yet.
+fn previous_month_due(today: NaiveDate, report_day: u32, last: &LastReport) -> Option<NaiveDate> {
+    if today.day() < report_day {
+        return None;
+    }
+    let month = today.with_day(1).unwrap().pred().with_day(1).unwrap();
+    if last.month == month.format("%Y-%m").to_string() {
+        return None;
+    }
+    Some(month)
+}
+
+fn member_name(config: &Configuration) -> String {
+    if config.slack_user_name.is_empty() {
+        config.login.clone()
+    } else {
+        config.slack_user_name.clone()
+    }
+}
+
+async fn send_report(
+    config: &Configuration,
+    timezone: &JobcanTimezone,
+    visible: bool,
+    name: &str,
+    month: NaiveDate,
+    by_email: bool,
+) -> color_eyre::Result<()> {
+    let calendar = config.holiday_calendar()?;
+    let driver = jobcan::start_webdriver(visible).await?;
+    let result = async {
+        jobcan::login(&driver, config).await?;
+        MonthlyReport::read(&driver, config, &calendar, timezone.today(), name, month).await
+    }
+    .await;
+    if let Err(err) = driver.quit().await {
+        warn!("Could not close the browser: {}", err);
+    }
+    let report = result?;
+
+    if by_email {
+        email::send(
+            &config.email,
+            &report.subject(),
+            &report.text(),
+            &report.html(),
+        )
+        .await?;
+        info!(
+            "Sent the report for {} ({}) to {}.",
+            name, report.month, config.email.to
+        );
+    } else {
+        println!("{}", report.text());
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct LastReport {
+    /// yyyy-mm
+    month: String,
+}
+
+impl LastReport {
+    /// Nothing sent yet when there is no file.
+    fn load(state_dir: &Path) -> Self {
+        fs::read_to_string(state_dir.join(LAST_REPORT_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, state_dir: &Path) -> color_eyre::Result<()> {
+        fs::create_dir_all(state_dir)?;
+        fs::write(
+            state_dir.join(LAST_REPORT_FILE),
+            serde_json::to_string(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::overtime::Severity;
+
+    fn report() -> MonthlyReport {
+        let calendar = HolidayCalendar::new("").unwrap();
+        let month = NaiveDate::from_ymd(2021, 10, 1);
+        MonthlyReport {
+            name: "dkg".into(),
+            month: "2021-10".into(),
+            days: vec![
+                ReportDay {
+                    date: "10/01(金)".into(),
+                    start: "09:00".into(),
+                    end: "18:00".into(),
+                    break_time: "01:00".into(),
+                    note: String::new(),
+                },
+                ReportDay {
+                    date: "10/04(月)".into(),
+                    start: String::new(),
+                    end: String::new(),
+                    break_time: String::new(),
+                    note: "有休".into(),
+                },
+            ],
+            totals: MonthlyTotals {
+                worked: Some(8 * 60),
+                required: Some(168 * 60),
+                overtime: Some(0),
+            },
+            forecast: Forecast::new(
+                &calendar,
+                month,
+                NaiveDate::from_ymd(2021, 11, 1),
+                8 * 60,
+                168 * 60,
+                8 * 60,
+            ),
+            overtime_warnings: vec![OvertimeWarning {
+                severity: Severity::Near,
+                message: "40:00 of 45:00 in 2021-10 <monthly>".into(),
+            }],
+            vacations: VacationBalances {
+                paid: "10.0日".into(),
+                compensatory: "0.0日".into(),
+                substitution: "0.0日".into(),
+                special: "0.0日".into(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_text() {
+        let text = report().text();
+
+        assert!(text.starts_with("Jobcan report for dkg: 2021-10\n"));
+        assert!(text.contains("\n10/01(金)    09:00  18:00  01:00\n"));
+        assert!(text.contains(&format!("\n10/04(月){}有休\n", " ".repeat(25))));
+        assert!(text.contains("\nWorked        : 08:00\n"));
+        assert!(text.contains("\nRequired      : 168:00\n"));
+        assert!(text.contains("\nOvertime      : 00:00\n"));
+        assert!(text.contains("\nPaid vacation        : 10.0日\n"));
+        assert!(text.contains("\nNear the limit: 40:00 of 45:00 in 2021-10 <monthly>\n"));
+    }
+
+    #[test]
+    fn test_html() {
+        let html = report().html();
+
+        assert!(html.contains("<h2>Jobcan report for dkg: 2021-10</h2>"));
+        assert!(html.contains(
+            "<tr><td>10/01(金)</td><td>09:00</td><td>18:00</td><td>01:00</td><td></td></tr>"
+        ));
+        assert!(html.contains("<tr><th align=\"left\">Paid vacation</th><td>10.0日</td></tr>"));
+        assert!(html.contains("<li>Near the limit: 40:00 of 45:00 in 2021-10 &lt;monthly&gt;</li>"));
+    }
+
+    #[test]
+    fn test_previous_month_due() {
+        let date = |day| NaiveDate::from_ymd(2021, 11, day);
+        let sent = |month: &str| LastReport {
+            month: month.into(),
+        };
+
+        assert_eq!(
+            Some(NaiveDate::from_ymd(2021, 10, 1)),
+            previous_month_due(date(1), 1, &LastReport::default())
+        );
+        assert_eq!(
+            Some(NaiveDate::from_ymd(2021, 10, 1)),
+            previous_month_due(date(5), 3, &sent("2021-09"))
+        );
+        assert_eq!(None, previous_month_due(date(2), 3, &sent("2021-09")));
+        assert_eq!(None, previous_month_due(date(5), 3, &sent("2021-10")));
+        // January reports December
+        assert_eq!(
+            Some(NaiveDate::from_ymd(2021, 12, 1)),
+            previous_month_due(NaiveDate::from_ymd(2022, 1, 1), 1, &sent("2021-11"))
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use color_eyre::eyre::{bail, eyre, WrapErr};
+use std::fmt;
+
+/// What the daemon should do at a scheduled time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScheduledPunch {
+    ClockIn,
+    ClockOut,
+}
+
+impl fmt::Display for ScheduledPunch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduledPunch::ClockIn => write!(f, "clock-in"),
+            ScheduledPunch::ClockOut => write!(f, "clock-out"),
+        }
+    }
+}
+
+/// Working hours per weekday, e.g. parsed from "mon-fri 09:00-18:00; sat 10:00-14:00".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schedule {
+    /// Indexed by `Weekday::num_days_from_monday()`. `None` means no work on that day.
+    days: [Option<(NaiveTime, NaiveTime)>; 7],
+}
+
+impl Schedule {
+    /// Parse a schedule. Entries are separated by ';'. Each entry is a list or range of weekdays
+    /// ("mon-fri", "mon,wed,fri", "sat") followed by the clock-in and clock-out time ("09:00-18:00").
+    pub fn parse(input: &str) -> color_eyre::Result<Self> {
+        let mut schedule = Schedule::default();
+
+        for entry in input.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (days, times) = entry.split_once(char::is_whitespace).ok_or_else(|| {
+                eyre!(
+                    "The schedule entry '{}' must look like 'mon-fri 09:00-18:00'.",
+                    entry
+                )
+            })?;
+            let (start, end) = times.trim().split_once('-').ok_or_else(|| {
+                eyre!(
+                    "The working hours '{}' must look like '09:00-18:00'.",
+                    times.trim()
+                )
+            })?;
+            let start = parse_time(start)?;
+            let end = parse_time(end)?;
+            if end <= start {
+                bail!(
+                    "The clock-out time must be after the clock-in time in '{}'.",
+                    entry
+                );
+            }
+
+            for weekday in parse_weekdays(days)? {
+                schedule.days[weekday.num_days_from_monday() as usize] = Some((start, end));
+            }
+        }
+
+        Ok(schedule)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.days.iter().all(Option::is_none)
+    }
+
+    /// The working hours for the given weekday, if it is a working day.
+    pub fn hours_for(&self, weekday: Weekday) -> Option<(NaiveTime, NaiveTime)> {
+        self.days[weekday.num_days_from_monday() as usize]
+    }
+
+    /// The first scheduled punch strictly after `after`.
+    pub fn next_event(&self, after: NaiveDateTime) -> Option<(NaiveDateTime, ScheduledPunch)> {
+        // NOTE(dkg): 8 days, so that today's weekday next week is covered when today's punches are over.
+        for offset in 0..8 {
+            let date = after.date() + Duration::days(offset);
+            if let Some((start, end)) = self.hours_for(date.weekday()) {
+                let events = [
+                    (date.and_time(start), ScheduledPunch::ClockIn),
+                    (date.and_time(end), ScheduledPunch::ClockOut),
+                ];
+                if let Some(event) = events.iter().find(|(at, _)| *at > after) {
+                    return Some(*event);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// The end of the first working day that ends strictly after `after`.
+    pub fn next_clock_out(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let (at, punch) = self.next_event(after)?;
+        match punch {
+            ScheduledPunch::ClockOut => Some(at),
+            ScheduledPunch::ClockIn => self.next_event(at).map(|(at, _)| at),
+        }
+    }
+}
+
+fn parse_time(input: &str) -> color_eyre::Result<NaiveTime> {
+    NaiveTime::parse_from_str(input.trim(), "%H:%M").wrap_err_with(|| {
+        format!(
+            "Unable to parse the time '{}', expected hh:mm.",
+            input.trim()
+        )
+    })
+}
+
+fn parse_weekday(input: &str) -> color_eyre::Result<Weekday> {
+    input.trim().parse::<Weekday>().map_err(|_| {
+        eyre!(
+            "Unknown weekday '{}'. Use mon, tue, wed, thu, fri, sat or sun.",
+            input.trim()
+        )
+    })
+}
+
+fn parse_weekdays(input: &str) -> color_eyre::Result<Vec<Weekday>> {
+    let mut weekdays = vec![];
+    for part in input.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let mut weekday = parse_weekday(first)?;
+                let last = parse_weekday(last)?;
+                loop {
+                    weekdays.push(weekday);
+                    if weekday == last {
+                        break;
+                    }
+                    weekday = weekday.succ();
+                }
+            }
+            None => weekdays.push(parse_weekday(part)?),
+        }
+    }
+
+    Ok(weekdays)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_parse_schedule() {
+        let schedule = Schedule::parse("mon-fri 09:00-18:00; sat 10:00-14:30").unwrap();
+        let office = (NaiveTime::from_hms(9, 0, 0), NaiveTime::from_hms(18, 0, 0));
+
+        assert_eq!(Some(office), schedule.hours_for(Weekday::Mon));
+        assert_eq!(Some(office), schedule.hours_for(Weekday::Fri));
+        assert_eq!(
+            Some((
+                NaiveTime::from_hms(10, 0, 0),
+                NaiveTime::from_hms(14, 30, 0)
+            )),
+            schedule.hours_for(Weekday::Sat)
+        );
+        assert_eq!(None, schedule.hours_for(Weekday::Sun));
+    }
+
+    #[test]
+    fn test_parse_schedule_with_weekday_list_and_wrap_around() {
+        let schedule = Schedule::parse("mon,wed 08:00-12:00;fri-sun 13:00-17:00").unwrap();
+
+        assert!(schedule.hours_for(Weekday::Mon).is_some());
+        assert!(schedule.hours_for(Weekday::Tue).is_none());
+        assert!(schedule.hours_for(Weekday::Wed).is_some());
+        assert!(schedule.hours_for(Weekday::Sat).is_some());
+        assert!(schedule.hours_for(Weekday::Sun).is_some());
+    }
+
+    #[test]
+    fn test_parse_schedule_errors() {
+        assert!(Schedule::parse("mon-fri").is_err());
+        assert!(Schedule::parse("mon-fri 9-18").is_err());
+        assert!(Schedule::parse("someday 09:00-18:00").is_err());
+        assert!(Schedule::parse("mon 18:00-09:00").is_err());
+        assert!(Schedule::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_next_event() {
+        let schedule = Schedule::parse("mon-fri 09:00-18:00").unwrap();
+
+        // 2021-10-04 is a Monday
+        assert_eq!(
+            Some((at("2021-10-04", "09:00"), ScheduledPunch::ClockIn)),
+            schedule.next_event(at("2021-10-04", "07:30"))
+        );
+        assert_eq!(
+            Some((at("2021-10-04", "18:00"), ScheduledPunch::ClockOut)),
+            schedule.next_event(at("2021-10-04", "09:00"))
+        );
+        assert_eq!(
+            Some((at("2021-10-05", "09:00"), ScheduledPunch::ClockIn)),
+            schedule.next_event(at("2021-10-04", "18:00"))
+        );
+        // Friday evening -> Monday morning
+        assert_eq!(
+            Some((at("2021-10-11", "09:00"), ScheduledPunch::ClockIn)),
+            schedule.next_event(at("2021-10-08", "19:00"))
+        );
+    }
+
+    #[test]
+    fn test_next_event_single_day_a_week() {
+        let schedule = Schedule::parse("mon 09:00-18:00").unwrap();
+
+        assert_eq!(
+            Some((at("2021-10-11", "09:00"), ScheduledPunch::ClockIn)),
+            schedule.next_event(at("2021-10-04", "18:30"))
+        );
+        assert_eq!(
+            None,
+            Schedule::default().next_event(at("2021-10-04", "18:30"))
+        );
+    }
+
+    #[test]
+    fn test_next_clock_out() {
+        let schedule = Schedule::parse("mon-fri 09:00-18:00").unwrap();
+
+        assert_eq!(
+            Some(at("2021-10-04", "18:00")),
+            schedule.next_clock_out(at("2021-10-04", "07:30"))
+        );
+        assert_eq!(
+            Some(at("2021-10-04", "18:00")),
+            schedule.next_clock_out(at("2021-10-04", "12:00"))
+        );
+        // Friday evening -> Monday evening
+        assert_eq!(
+            Some(at("2021-10-11", "18:00")),
+            schedule.next_clock_out(at("2021-10-08", "18:00"))
+        );
+        assert_eq!(
+            None,
+            Schedule::default().next_clock_out(at("2021-10-04", "07:30"))
+        );
+    }
+}
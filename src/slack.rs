@@ -1,100 +1,869 @@
-use log::{debug, warn};
+use chrono::prelude::*;
+use chrono::Duration;
+use color_eyre::eyre::eyre;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use slack_morphism::errors::SlackClientError;
 use slack_morphism::prelude::*;
+use slack_morphism::SlackClient;
+use slack_morphism_hyper::SlackClientHyperConnector;
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
 
-use crate::config::{Configuration, ENVVAR_SLACK_TOKEN, ENVVAR_SLACK_USER_NAME};
+use crate::clock::ClockTime;
+use crate::config::{
+    Configuration, ENVVAR_SLACK_DND_AFTER_WORK, ENVVAR_SLACK_STATUS_CLOCK_IN,
+    ENVVAR_SLACK_STATUS_CLOCK_OUT, ENVVAR_SLACK_TOKEN, ENVVAR_SLACK_USER_ID,
+    ENVVAR_SLACK_USER_NAME,
+};
+use crate::input;
+use crate::template::{PunchEvent, RenderedMessage};
+use crate::timezone::JobcanTimezone;
 
-pub async fn post_to_slack(
+/// Cache for the Slack user, in the state directory.
+const PROFILE_FILE: &str = "slack_user.json";
+
+/// Today's clock-in message, in the state directory.
+const THREAD_FILE: &str = "slack_thread.json";
+
+/// Slack recommends no more than 200 per page.
+const USERS_PER_PAGE: u16 = 200;
+
+#[derive(Debug, Error)]
+pub enum SlackError {
+    #[error("'{0}' is not a Slack channel. Use the name with a leading '#' (e.g. #standup) or the channel ID (e.g. C0123ABCD)")]
+    InvalidChannel(String),
+    #[error(
+        "The Slack user '{0}' could not be found in the workspace. Please check {} or set {}",
+        ENVVAR_SLACK_USER_NAME,
+        ENVVAR_SLACK_USER_ID
+    )]
+    UserNotFound(String),
+    #[error("The Slack user '{0}' has no user profile")]
+    NoProfile(String),
+    #[error("The Block Kit layout is not supported: {0}")]
+    InvalidBlocks(String),
+    #[error("Slack returned an error: {0}")]
+    Api(#[from] Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// Slack API errors that are worth another try later. Anything else (e.g. channel_not_found) needs a fix first.
+const RETRYABLE_API_ERRORS: &[&str] = &[
+    "ratelimited",
+    "fatal_error",
+    "internal_error",
+    "request_timeout",
+    "service_unavailable",
+];
+
+impl SlackError {
+    /// Whether posting again later could work, e.g. after a network problem or a Slack outage.
+    pub fn is_retryable(&self) -> bool {
+        let err = match self {
+            SlackError::Api(err) => err,
+            _ => return false,
+        };
+        match err.downcast_ref::<SlackClientError>() {
+            Some(SlackClientError::ApiError(api_error)) => {
+                RETRYABLE_API_ERRORS.contains(&api_error.code.as_str())
+            }
+            Some(SlackClientError::SystemError(_)) => false,
+            // NOTE(dkg): HTTP, protocol and connection errors, most likely the network.
+            _ => true,
+        }
+    }
+}
+
+/// The channel to post to: a name like "#standup" or a channel ID like "C0123ABCD".
+pub fn parse_channel(channel: &str) -> Result<SlackChannelId, SlackError> {
+    let channel = channel.trim();
+    let is_name = channel.len() > 1 && channel.starts_with('#') && !channel.contains(' ');
+    // NOTE(dkg): Public channels start with C, private ones with G and direct messages with D.
+    let is_id = channel.len() >= 9
+        && channel.starts_with(['C', 'G', 'D'])
+        && channel
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit());
+
+    if is_name || is_id {
+        Ok(SlackChannelId(channel.to_string()))
+    } else {
+        Err(SlackError::InvalidChannel(channel.to_string()))
+    }
+}
+
+/// The Slack user the bot posts as, cached in the state directory so the workspace isn't searched on every post.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlackProfile {
+    /// SLACK_USER_ID or SLACK_USER_NAME the profile was resolved for. A different one means the cache is outdated.
+    pub key: String,
+    pub user_id: String,
+    pub display_name: Option<String>,
+    pub icon_url: Option<String>,
+}
+
+impl SlackProfile {
+    fn from_user(key: &str, user: &SlackUser) -> Self {
+        let profile = user.profile.as_ref();
+        SlackProfile {
+            key: key.to_string(),
+            user_id: user.id.to_string(),
+            display_name: profile
+                .and_then(|profile| profile.display_name.clone())
+                .filter(|name| !name.is_empty()),
+            // NOTE(dkg): 48x48, not sure if this is the right one to use...
+            icon_url: profile
+                .and_then(|profile| profile.icon.as_ref())
+                .and_then(|icon| icon.images.as_ref())
+                .and_then(|images| {
+                    images
+                        .resolutions
+                        .iter()
+                        .find(|(resolution, _)| *resolution == 48)
+                        .map(|(_, url)| url.clone())
+                }),
+        }
+    }
+
+    /// None when nothing was cached yet or the cache is for another user.
+    pub fn load(state_dir: &Path, key: &str) -> Option<Self> {
+        let data = fs::read_to_string(state_dir.join(PROFILE_FILE)).ok()?;
+        serde_json::from_str::<SlackProfile>(&data)
+            .ok()
+            .filter(|profile| profile.key == key)
+    }
+
+    pub fn save(&self, state_dir: &Path) -> std::io::Result<()> {
+        fs::create_dir_all(state_dir)?;
+        fs::write(
+            state_dir.join(PROFILE_FILE),
+            serde_json::to_string(self).unwrap_or_default(),
+        )
+    }
+}
+
+/// Where a message ended up. The channel is always the ID, even when it was posted to "#name".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostedMessage {
+    pub channel: String,
+    pub ts: String,
+}
+
+/// The clock-in message of a working day, so the clock-out can reply in its thread and update it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyThread {
+    /// yyyy-mm-dd, the working day of the clock-in.
+    pub date: String,
+    pub posted: PostedMessage,
+    /// As it was posted, to add the total when updating it.
+    pub message: RenderedMessage,
+}
+
+impl DailyThread {
+    /// None when there is no clock-in message for this day.
+    pub fn load(state_dir: &Path, date: &str) -> Option<Self> {
+        let data = fs::read_to_string(state_dir.join(THREAD_FILE)).ok()?;
+        serde_json::from_str::<DailyThread>(&data)
+            .ok()
+            .filter(|thread| thread.date == date)
+    }
+
+    pub fn save(&self, state_dir: &Path) -> std::io::Result<()> {
+        fs::create_dir_all(state_dir)?;
+        fs::write(
+            state_dir.join(THREAD_FILE),
+            serde_json::to_string(self).unwrap_or_default(),
+        )
+    }
+}
+
+/// What identifies the user in the configuration, SLACK_USER_ID before SLACK_USER_NAME.
+fn user_key(config: &Configuration) -> String {
+    if config.slack_user_id.is_empty() {
+        format!("name:{}", config.slack_user_name)
+    } else {
+        format!("id:{}", config.slack_user_id)
+    }
+}
+
+fn is_user(user: &SlackUser, username: &str) -> bool {
+    user.name.as_deref() == Some(username)
+}
+
+/// Post a plain text or Block Kit message, as a reply if `thread_ts` is given.
+/// None if Slack is not configured.
+pub async fn post_message(
     config: &Configuration,
     channel: &str,
-    message: &str,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    message: &RenderedMessage,
+    thread_ts: Option<&str>,
+) -> Result<Option<PostedMessage>, SlackError> {
     if !config.can_post_to_slack() {
         debug!(
-            "'{}' and '{}' environment variable must be set in order to post to Slack -> ignoring",
-            ENVVAR_SLACK_TOKEN, ENVVAR_SLACK_USER_NAME,
+            "'{}' and '{}' (or '{}') environment variable must be set in order to post to Slack -> ignoring",
+            ENVVAR_SLACK_TOKEN, ENVVAR_SLACK_USER_NAME, ENVVAR_SLACK_USER_ID,
         );
-        return Ok(());
+        return Ok(None);
     }
 
-    let username = &config.slack_user_name;
+    let channel = parse_channel(channel)?;
+    let content = message_content(message)?;
 
-    if !channel.contains('#') {
-        // TODO(dkg): improve error handling
-        panic!("The Slack channel name must contain the leading '#'.");
-    }
+    let client = client();
+    let token = token(config);
+    let session = client.open_session(&token);
+
+    let key = user_key(config);
+    let profile = match SlackProfile::load(&config.state_dir, &key) {
+        Some(profile) => profile,
+        None => {
+            let profile = resolve_profile(&session, config, &key).await?;
+            if let Err(err) = profile.save(&config.state_dir) {
+                warn!("Could not cache the Slack user: {}", err);
+            }
+            profile
+        }
+    };
 
     debug!(
         "Posting message to Slack channel '{}' as user '{}'.",
-        channel, username
+        channel, profile.user_id
     );
 
-    use slack_morphism::*;
-    // Slack Morphism Hyper/Tokio support
-    use slack_morphism_hyper::*;
+    // Send a simple text message
+    let mut post_chat_req = SlackApiChatPostMessageRequest::new(channel, content);
+    if let Some(thread_ts) = thread_ts {
+        post_chat_req.thread_ts(thread_ts.into());
+    }
+
+    // NOTE(dkg): This is only needed when a bot token is used. A user token should handle this on its own.
+    let is_user_token = config.slack_token.starts_with("xoxp-");
+    if !is_user_token {
+        let name = profile
+            .display_name
+            .clone()
+            .unwrap_or_else(|| config.slack_user_name.clone());
+        post_chat_req.username(name);
+        match &profile.icon_url {
+            Some(icon_url) => {
+                post_chat_req.icon_url(icon_url.clone());
+            }
+            None => warn!("Profile icon with size 48x48 not found."),
+        }
+    }
 
-    let hyper_connector = SlackClientHyperConnector::new();
-    let client = SlackClient::new(hyper_connector);
+    // NOTE(dkg): slack-morphism's response drops the channel ID, which chat.update needs.
+    let posted: PostedMessage = session
+        .http_api
+        .http_post("chat.postMessage", &post_chat_req)
+        .await?;
+    debug!("Response: {:#?}", posted);
 
-    let token_value = config.slack_token.clone();
-    let token_value: SlackApiTokenValue = token_value.into();
-    let token: SlackApiToken = SlackApiToken::new(token_value);
+    Ok(Some(posted))
+}
 
-    // Create a Slack session with this token
+/// Replace an earlier message, e.g. to add the hours worked to the clock-in message.
+pub async fn update_message(
+    config: &Configuration,
+    posted: &PostedMessage,
+    message: &RenderedMessage,
+) -> Result<(), SlackError> {
+    let content = message_content(message)?;
+
+    let client = client();
+    let token = token(config);
     let session = client.open_session(&token);
 
-    let user_list_req = SlackApiUsersListRequest::new();
-    let user_list_res = session.users_list(&user_list_req).await?;
-
-    let search_for_user = Some(username.into());
-    let slack_user = user_list_res
-        .members
-        .into_iter()
-        .find(|user| user.name.eq(&search_for_user));
-    // TODO(dkg): improve error handling
-    let slack_user = slack_user.unwrap_or_else(|| {
-        panic!(
-            "The Slack user '{}' could not be found in the workspace.",
-            &username
-        )
-    });
-    let user_info_req = SlackApiUsersInfoRequest::new(SlackUserId(slack_user.id.to_string()));
+    let request = SlackApiChatUpdateRequest::new(
+        posted.channel.clone().into(),
+        content,
+        posted.ts.clone().into(),
+    );
+    let response = session.chat_update(&request).await?;
+    debug!("Response: {:#?}", response);
 
-    let user_info_resp = session.users_info(&user_info_req).await?;
-    let slack_user = user_info_resp.user;
+    Ok(())
+}
 
-    // Send a simple text message
-    let mut post_chat_req = SlackApiChatPostMessageRequest::new(
-        channel.into(),
-        SlackMessageContent::new().with_text(message.into()),
-    );
+/// Reply to a slash command or a button click. Only the user who sent it sees the reply.
+pub async fn respond(response_url: &str, message: &RenderedMessage) -> Result<(), SlackError> {
+    let request = SlackApiPostWebhookMessageRequest::new(message_content(message)?);
+    client()
+        .post_webhook_message(response_url, &request)
+        .await?;
+    Ok(())
+}
 
-    let is_user_token = config.slack_token.starts_with("xoxp-");
-    if !is_user_token {
-        if let Some(profile) = slack_user.profile {
-            post_chat_req.username(profile.display_name.unwrap_or_else(|| username.into()));
-            // NOTE(dkg): This is only needed when a bot token is used. A user token should handle this on its own.
-            if let Some(icon) = profile.icon {
-                if let Some(images) = icon.images {
-                    // TODO(dkg): not sure if this is the right one to use...
-                    let resolution48 = images.resolutions.into_iter().find(|(r, _)| *r == 48);
-                    if let Some(resolution48) = resolution48 {
-                        post_chat_req.icon_url(resolution48.1);
-                    } else {
-                        warn!("Profile icon with size 48x48 not found.");
-                    }
+fn message_content(message: &RenderedMessage) -> Result<SlackMessageContent, SlackError> {
+    let mut content = SlackMessageContent::new().with_text(message.text.clone());
+    if let Some(blocks) = &message.blocks {
+        content = content.with_blocks(parse_blocks(blocks)?);
+    }
+    Ok(content)
+}
+
+fn client() -> SlackClient<SlackClientHyperConnector> {
+    SlackClient::new(SlackClientHyperConnector::new())
+}
+
+fn token(config: &Configuration) -> SlackApiToken {
+    let token_value: SlackApiTokenValue = config.slack_token.clone().into();
+    SlackApiToken::new(token_value)
+}
+
+/// Longest duration for an expiration like "+8h". Slack statuses are for days, not months.
+const MAX_EXPIRATION_MINUTES: i64 = 7 * 24 * 60;
+
+/// When a status or do not disturb ends: at a time of day (the next one after the punch) or after some time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Expiration {
+    At(ClockTime),
+    AfterMinutes(i64),
+}
+
+impl Expiration {
+    /// "09:00" or "+8h", "+30m", "+1h30m".
+    pub fn parse(input: &str) -> color_eyre::Result<Self> {
+        let input = input.trim();
+        let expiration = match input.strip_prefix('+') {
+            Some(after) => input::parse_offset_minutes(after)
+                .filter(|minutes| *minutes <= MAX_EXPIRATION_MINUTES)
+                .map(Expiration::AfterMinutes),
+            None => input.parse::<ClockTime>().ok().map(Expiration::At),
+        };
+        expiration.ok_or_else(|| {
+            eyre!(
+                "'{}' is not an expiration. Use a time like 09:00 or a duration up to a week like +8h.",
+                input
+            )
+        })
+    }
+
+    pub fn after(&self, punch_at: NaiveDateTime) -> color_eyre::Result<NaiveDateTime> {
+        let after = match self {
+            Expiration::At(time) => {
+                let at =
+                    punch_at.date().and_hms(0, 0, 0) + Duration::minutes(time.minutes() as i64);
+                if at <= punch_at {
+                    at.checked_add_signed(Duration::days(1))
                 } else {
-                    warn!("No image_original");
+                    Some(at)
                 }
-            } else {
-                warn!("No profile icon");
             }
+            Expiration::AfterMinutes(minutes) => Some(*minutes)
+                .filter(|minutes| *minutes <= MAX_EXPIRATION_MINUTES)
+                .and_then(|minutes| punch_at.checked_add_signed(Duration::minutes(minutes))),
+        };
+        after.ok_or_else(|| eyre!("The expiration {:?} is too far away.", self))
+    }
+}
+
+/// A Slack status like ":office: In office | 19:00": the emoji, the text and when it expires.
+/// "clear" removes the status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlackStatus {
+    pub emoji: String,
+    pub text: String,
+    pub expiration: Option<Expiration>,
+}
+
+impl SlackStatus {
+    pub fn parse(input: &str) -> color_eyre::Result<Self> {
+        let (status, expiration) = match input.split_once('|') {
+            Some((status, expiration)) => (status.trim(), Some(Expiration::parse(expiration)?)),
+            None => (input.trim(), None),
+        };
+        if status.eq_ignore_ascii_case("clear") {
+            return Ok(SlackStatus {
+                emoji: String::new(),
+                text: String::new(),
+                expiration: None,
+            });
+        }
+
+        // NOTE(dkg): Slack only takes emoji names like ":office:" for the status, not the emoji itself.
+        let (emoji, text) = match status.split_once(' ') {
+            Some((emoji, text)) if is_emoji_name(emoji) => (emoji, text.trim()),
+            None if is_emoji_name(status) => (status, ""),
+            _ => ("", status),
+        };
+
+        Ok(SlackStatus {
+            emoji: emoji.to_string(),
+            text: text.to_string(),
+            expiration,
+        })
+    }
+}
+
+fn is_emoji_name(input: &str) -> bool {
+    input.len() > 2 && input.starts_with(':') && input.ends_with(':') && !input.contains(' ')
+}
+
+/// What to change on Slack when clocking in or out, from the SLACK_STATUS_*, SLACK_PRESENCE and
+/// SLACK_DND_AFTER_WORK environment variables. Empty means to leave it alone.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatusSettings {
+    pub clock_in: String,
+    pub clock_out: String,
+    /// Set the presence to "auto" when clocking in and to "away" when clocking out.
+    pub presence: bool,
+    /// Do not disturb after clocking out until then, e.g. "09:00" or "+12h".
+    pub dnd_after_work: String,
+}
+
+impl StatusSettings {
+    pub fn is_empty(&self) -> bool {
+        self.clock_in.trim().is_empty()
+            && self.clock_out.trim().is_empty()
+            && !self.presence
+            && self.dnd_after_work.trim().is_empty()
+    }
+
+    pub fn status_for(&self, event: PunchEvent) -> color_eyre::Result<Option<SlackStatus>> {
+        let status = match event {
+            PunchEvent::ClockIn => &self.clock_in,
+            PunchEvent::ClockOut => &self.clock_out,
+            _ => return Ok(None),
+        };
+        if status.trim().is_empty() {
+            return Ok(None);
+        }
+        SlackStatus::parse(status).map(Some)
+    }
+
+    pub fn dnd_after_work(&self) -> color_eyre::Result<Option<Expiration>> {
+        if self.dnd_after_work.trim().is_empty() {
+            return Ok(None);
+        }
+        Expiration::parse(&self.dnd_after_work).map(Some)
+    }
+
+    /// Make sure the settings can be parsed, so a typo shows up before punching.
+    pub fn validate(&self) -> color_eyre::Result<()> {
+        self.status_for(PunchEvent::ClockIn)
+            .map_err(|err| eyre!("Please check {}: {}", ENVVAR_SLACK_STATUS_CLOCK_IN, err))?;
+        self.status_for(PunchEvent::ClockOut)
+            .map_err(|err| eyre!("Please check {}: {}", ENVVAR_SLACK_STATUS_CLOCK_OUT, err))?;
+        self.dnd_after_work()
+            .map_err(|err| eyre!("Please check {}: {}", ENVVAR_SLACK_DND_AFTER_WORK, err))?;
+        Ok(())
+    }
+}
+
+/// Update the Slack status, presence and do not disturb for a clock-in or clock-out at `punch_at` (Jobcan's clock).
+/// Needs a user token (xoxp-...) with the users.profile:write, users:write and dnd:write scopes.
+pub async fn update_status(
+    config: &Configuration,
+    timezone: &JobcanTimezone,
+    event: PunchEvent,
+    punch_at: NaiveDateTime,
+) -> color_eyre::Result<()> {
+    let settings = &config.slack_status;
+    if config.slack_token.is_empty() || settings.is_empty() {
+        return Ok(());
+    }
+    if !config.slack_token.starts_with("xoxp-") {
+        warn!("Updating the Slack status needs a user token (xoxp-...), skipping it.");
+        return Ok(());
+    }
+
+    let client = client();
+    let token = token(config);
+    let session = client.open_session(&token);
+    let now = timezone.now();
+
+    if let Some(status) = settings.status_for(event)? {
+        let expires_at = status
+            .expiration
+            .map(|expiration| expiration.after(punch_at))
+            .transpose()?;
+        if expires_at.is_some_and(|expires_at| expires_at <= now) {
+            debug!(
+                "The Slack status for the {} already expired, skipping it.",
+                event
+            );
         } else {
-            panic!("The user '{}' has no user profile on Slack.", username);
+            let expiration = expires_at
+                .and_then(|expires_at| timezone.timestamp(expires_at))
+                .unwrap_or(0);
+            info!(
+                "Setting the Slack status to '{} {}'.",
+                status.emoji, status.text
+            );
+            // NOTE(dkg): slack-morphism's profile has no status_emoji, so the request is built by hand.
+            let request = serde_json::json!({
+                "profile": {
+                    "status_text": status.text,
+                    "status_emoji": status.emoji,
+                    "status_expiration": expiration,
+                }
+            });
+            session
+                .http_api
+                .http_post::<_, serde_json::Value>("users.profile.set", &request)
+                .await
+                .map_err(SlackError::from)?;
         }
     }
 
-    let post_chat_resp = session.chat_post_message(&post_chat_req).await?;
-    debug!("Response: {:#?}", post_chat_resp);
+    if settings.presence {
+        let presence = match event {
+            PunchEvent::ClockOut => "away",
+            _ => "auto",
+        };
+        session
+            .users_set_presence(&SlackApiUsersSetPresenceRequest::new(presence.into()))
+            .await
+            .map_err(SlackError::from)?;
+    }
+
+    if let Some(dnd) = settings.dnd_after_work()? {
+        match event {
+            PunchEvent::ClockOut => {
+                let minutes = (dnd.after(punch_at)? - now).num_minutes();
+                if minutes > 0 {
+                    info!("Pausing Slack notifications for {} minutes.", minutes);
+                    session
+                        .http_api
+                        .http_post::<_, serde_json::Value>(
+                            "dnd.setSnooze",
+                            &serde_json::json!({ "num_minutes": minutes }),
+                        )
+                        .await
+                        .map_err(SlackError::from)?;
+                }
+            }
+            PunchEvent::ClockIn => {
+                // NOTE(dkg): Fails with snooze_not_active when there is nothing to end, which is fine.
+                let result = session
+                    .http_api
+                    .http_post::<_, serde_json::Value>("dnd.endSnooze", &serde_json::json!({}))
+                    .await;
+                if let Err(err) = result {
+                    debug!("Could not end the Slack snooze: {}", err);
+                }
+            }
+            _ => (),
+        }
+    }
 
     Ok(())
 }
+
+/// Only the block types slack-morphism knows are supported, e.g. section, divider, image and context.
+pub fn parse_blocks(blocks: &serde_json::Value) -> Result<Vec<SlackBlock>, SlackError> {
+    serde_json::from_value(blocks.clone()).map_err(|err| SlackError::InvalidBlocks(err.to_string()))
+}
+
+/// Find the user: by SLACK_USER_ID, by JC_LOGIN as email address, or by going through all users for SLACK_USER_NAME.
+async fn resolve_profile(
+    session: &SlackClientSession<'_, SlackClientHyperConnector>,
+    config: &Configuration,
+    key: &str,
+) -> Result<SlackProfile, SlackError> {
+    let is_user_token = config.slack_token.starts_with("xoxp-");
+    let username = &config.slack_user_name;
+
+    let user = if !config.slack_user_id.is_empty() {
+        let user_info_req =
+            SlackApiUsersInfoRequest::new(SlackUserId(config.slack_user_id.clone()));
+        session.users_info(&user_info_req).await?.user
+    } else {
+        // NOTE(dkg): The JC_LOGIN email identifies the user, SLACK_USER_NAME is only needed without it.
+        match find_user_by_email(session, &config.login).await {
+            Some(user) => user,
+            None => find_user_by_name(session, username).await?,
+        }
+    };
+
+    if user.profile.is_none() && !is_user_token {
+        return Err(SlackError::NoProfile(user.id.to_string()));
+    }
+    info!("Found the Slack user {}.", user.id);
+
+    Ok(SlackProfile::from_user(key, &user))
+}
+
+/// Needs the users:read.email scope. Any error just means to search by name instead.
+async fn find_user_by_email(
+    session: &SlackClientSession<'_, SlackClientHyperConnector>,
+    email: &str,
+) -> Option<SlackUser> {
+    if !email.contains('@') {
+        return None;
+    }
+    let request = SlackApiUsersLookupByEmailRequest::new(EmailAddress(email.to_string()));
+    match session.users_lookup_by_email(&request).await {
+        Ok(response) => Some(response.user),
+        Err(err) => {
+            debug!("Slack user lookup by email failed: {}", err);
+            None
+        }
+    }
+}
+
+async fn find_user_by_name(
+    session: &SlackClientSession<'_, SlackClientHyperConnector>,
+    username: &str,
+) -> Result<SlackUser, SlackError> {
+    let mut cursor = None;
+    loop {
+        let mut request = SlackApiUsersListRequest::new();
+        request.limit = Some(USERS_PER_PAGE);
+        request.cursor = cursor;
+        let response = session.users_list(&request).await?;
+
+        if let Some(user) = response
+            .members
+            .into_iter()
+            .find(|user| is_user(user, username))
+        {
+            return Ok(user);
+        }
+
+        cursor = response
+            .response_metadata
+            .and_then(|metadata| metadata.next_cursor);
+        if cursor.is_none() {
+            return Err(SlackError::UserNotFound(username.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_channel(channel: &str) -> bool {
+        parse_channel(channel).is_ok()
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        use slack_morphism::errors::SlackClientApiError;
+
+        let api_error = |code: &str| {
+            SlackError::Api(Box::new(SlackClientError::ApiError(
+                SlackClientApiError::new(code.into()),
+            )))
+        };
+
+        assert!(api_error("ratelimited").is_retryable());
+        assert!(!api_error("channel_not_found").is_retryable());
+        assert!(SlackError::Api("connection refused".into()).is_retryable());
+        assert!(!SlackError::InvalidChannel("standup".into()).is_retryable());
+    }
+
+    #[test]
+    fn test_parse_channel() {
+        assert!(is_channel("#standup"));
+        assert!(is_channel(" #standup "));
+        assert!(is_channel("C0123ABCD"));
+        assert!(is_channel("G01ABCDEFGH"));
+        assert!(is_channel("D0123ABCD"));
+        assert_eq!(
+            SlackChannelId(String::from("#standup")),
+            parse_channel(" #standup").unwrap()
+        );
+
+        assert!(!is_channel("standup"));
+        assert!(!is_channel("#"));
+        assert!(!is_channel(""));
+        assert!(!is_channel("#stand up"));
+        assert!(!is_channel("c0123abcd"));
+        assert!(!is_channel("C01"));
+        assert!(!is_channel("X0123ABCD"));
+    }
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let status = SlackStatus::parse(":office: In office | 19:00").unwrap();
+        assert_eq!(":office:", status.emoji);
+        assert_eq!("In office", status.text);
+        assert_eq!(
+            Some(Expiration::At("19:00".parse().unwrap())),
+            status.expiration
+        );
+
+        let status = SlackStatus::parse("Working remotely|+8h").unwrap();
+        assert_eq!("", status.emoji);
+        assert_eq!("Working remotely", status.text);
+        assert_eq!(Some(Expiration::AfterMinutes(8 * 60)), status.expiration);
+
+        let status = SlackStatus::parse(":crescent_moon:").unwrap();
+        assert_eq!(":crescent_moon:", status.emoji);
+        assert_eq!("", status.text);
+
+        let status = SlackStatus::parse("clear").unwrap();
+        assert_eq!(("", ""), (status.emoji.as_str(), status.text.as_str()));
+
+        assert!(SlackStatus::parse(":office: In office | tomorrow").is_err());
+    }
+
+    #[test]
+    fn test_expiration_after() {
+        let punch_at = at("2021-10-14 18:30");
+
+        assert_eq!(
+            at("2021-10-15 09:00"),
+            Expiration::parse("09:00").unwrap().after(punch_at).unwrap()
+        );
+        assert_eq!(
+            at("2021-10-14 19:00"),
+            Expiration::parse("19:00").unwrap().after(punch_at).unwrap()
+        );
+        assert_eq!(
+            at("2021-10-15 02:30"),
+            Expiration::parse("+8h").unwrap().after(punch_at).unwrap()
+        );
+        assert!(Expiration::AfterMinutes(i64::MAX).after(punch_at).is_err());
+    }
+
+    #[test]
+    fn test_status_settings() {
+        let settings = StatusSettings {
+            clock_in: ":office: In office".into(),
+            ..Default::default()
+        };
+
+        assert!(!settings.is_empty());
+        assert!(settings.status_for(PunchEvent::ClockIn).unwrap().is_some());
+        assert_eq!(None, settings.status_for(PunchEvent::ClockOut).unwrap());
+        assert_eq!(None, settings.status_for(PunchEvent::BreakStart).unwrap());
+        assert!(settings.validate().is_ok());
+        assert!(StatusSettings::default().is_empty());
+
+        let settings = StatusSettings {
+            dnd_after_work: "later".into(),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+
+        // Way too long, would overflow when clocking out
+        for dnd_after_work in ["+99999999999999", "+153722867280912930m", "+169h"] {
+            let settings = StatusSettings {
+                dnd_after_work: dnd_after_work.into(),
+                ..Default::default()
+            };
+            assert!(settings.validate().is_err(), "{}", dnd_after_work);
+        }
+        let settings = StatusSettings {
+            clock_out: ":house: Off | +99999999999999".into(),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_err());
+        let settings = StatusSettings {
+            dnd_after_work: "+168h".into(),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+    }
+
+    #[test]
+    fn test_parse_blocks() {
+        let blocks = serde_json::json!([
+            {"type": "section", "text": {"type": "mrkdwn", "text": "*dkg* clocked in"}},
+            {"type": "divider"}
+        ]);
+        assert_eq!(2, parse_blocks(&blocks).unwrap().len());
+
+        let unknown = serde_json::json!([{"type": "no-such-block"}]);
+        assert!(parse_blocks(&unknown).is_err());
+    }
+
+    fn user(json: serde_json::Value) -> SlackUser {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_profile_from_user() {
+        let with_profile = user(serde_json::json!({
+            "id": "U0123ABCD",
+            "team_id": "T0123ABCD",
+            "name": "dkg",
+            "profile": {
+                "display_name": "Daniel",
+                "image_24": "https://example.com/24.png",
+                "image_48": "https://example.com/48.png"
+            }
+        }));
+        let profile = SlackProfile::from_user("name:dkg", &with_profile);
+
+        assert!(is_user(&with_profile, "dkg"));
+        assert!(!is_user(&with_profile, "Daniel"));
+        assert_eq!("U0123ABCD", profile.user_id);
+        assert_eq!(Some(String::from("Daniel")), profile.display_name);
+        assert_eq!(
+            Some(String::from("https://example.com/48.png")),
+            profile.icon_url
+        );
+
+        let without_profile = user(serde_json::json!({
+            "id": "U0123ABCD",
+            "team_id": "T0123ABCD",
+            "profile": { "display_name": "" }
+        }));
+        let profile = SlackProfile::from_user("id:U0123ABCD", &without_profile);
+
+        assert_eq!(None, profile.display_name);
+        assert_eq!(None, profile.icon_url);
+    }
+
+    #[test]
+    fn test_user_key() {
+        let mut config = Configuration {
+            slack_user_name: "dkg".into(),
+            ..Default::default()
+        };
+        assert_eq!("name:dkg", user_key(&config));
+
+        config.slack_user_id = "U0123ABCD".into();
+        assert_eq!("id:U0123ABCD", user_key(&config));
+    }
+
+    #[test]
+    fn test_daily_thread() {
+        let dir = std::env::temp_dir().join(format!("jobcan-bot-thread-{}", std::process::id()));
+        let thread = DailyThread {
+            date: "2021-10-14".into(),
+            posted: PostedMessage {
+                channel: "C0123ABCD".into(),
+                ts: "1634176800.000100".into(),
+            },
+            message: RenderedMessage::text("good morning"),
+        };
+
+        assert_eq!(None, DailyThread::load(&dir, "2021-10-14"));
+        thread.save(&dir).unwrap();
+        assert_eq!(Some(thread), DailyThread::load(&dir, "2021-10-14"));
+        assert_eq!(None, DailyThread::load(&dir, "2021-10-15"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_profile_cache() {
+        let dir = std::env::temp_dir().join(format!("jobcan-bot-slack-{}", std::process::id()));
+        let profile = SlackProfile {
+            key: "name:dkg".into(),
+            user_id: "U0123ABCD".into(),
+            display_name: Some("Daniel".into()),
+            icon_url: None,
+        };
+        profile.save(&dir).unwrap();
+
+        let loaded = SlackProfile::load(&dir, "name:dkg");
+        let other_user = SlackProfile::load(&dir, "name:someone-else");
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(Some(profile), loaded);
+        assert_eq!(None, other_user);
+        assert_eq!(None, SlackProfile::load(&dir, "name:dkg"));
+    }
+}
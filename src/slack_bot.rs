@@ -0,0 +1,774 @@
+//! `serve-slack`: punch, check the status, revise and list from Slack with a slash command like `/jobcan in`.
+//!
+//! Slack wants an answer within 3 seconds, but anything that needs the browser takes much longer. So the request is
+//! acknowledged right away and the result is sent to the `response_url` of the command later. Only the user who
+//! sent the command sees the replies.
+use chrono::prelude::*;
+use color_eyre::eyre::{bail, eyre};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use slack_morphism::signature_verifier::SlackEventSignatureVerifier;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::fs;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thirtyfour::prelude::*;
+use tokio::sync::Mutex;
+
+use crate::check_holiday;
+use crate::clock::ClockTime;
+use crate::config::{Configuration, ENVVAR_SLACK_SIGNING_SECRET};
+use crate::forecast::format_minutes;
+use crate::input;
+use crate::jobcan::{self, PunchKind};
+use crate::slack;
+use crate::status::{TodayStatus, WorkingStatus};
+use crate::template::RenderedMessage;
+use crate::timezone::JobcanTimezone;
+
+/// Jobcan accounts of the Slack users, in the state directory.
+const ACCOUNTS_FILE: &str = "slack_accounts.json";
+
+/// Every Slack user gets its own state directory below this one, for the cached Jobcan session.
+const USERS_DIR: &str = "slack-users";
+
+/// Requests older than this are rejected, so that a recorded request can't be sent again.
+const MAX_REQUEST_AGE_SECONDS: i64 = 5 * 60;
+
+const ACTION_CLOCK_IN: &str = "jobcan_clock_in";
+const ACTION_CLOCK_OUT: &str = "jobcan_clock_out";
+const ACTION_STATUS: &str = "jobcan_status";
+const ACTION_LIST: &str = "jobcan_list";
+
+const HELP: &str = "*Usage:*
+`/jobcan in [memo]` clock in
+`/jobcan out [memo]` clock out
+`/jobcan status` today's punches and worked time
+`/jobcan revise [date] <time> [memo]` add a missing punch, e.g. `/jobcan revise yesterday 18:30 forgot`
+`/jobcan list` this month's days and totals";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BotCommand {
+    ClockIn {
+        memo: String,
+    },
+    ClockOut {
+        memo: String,
+    },
+    Status,
+    Revise {
+        date: NaiveDate,
+        time: ClockTime,
+        memo: String,
+    },
+    List,
+    Help,
+}
+
+impl BotCommand {
+    /// The text after the slash command, e.g. "in remote start" or "revise yesterday 18:30 forgot".
+    pub fn parse(text: &str, now: NaiveDateTime) -> color_eyre::Result<Self> {
+        let mut words = text.split_whitespace();
+        let command = words.next().unwrap_or("help").to_lowercase();
+        let mut rest: Vec<&str> = words.collect();
+        let memo = |rest: &[&str], default: &str| match rest.join(" ") {
+            memo if memo.is_empty() => default.to_string(),
+            memo => memo,
+        };
+
+        let command = match command.as_str() {
+            "in" | "clock-in" | "start" => BotCommand::ClockIn {
+                memo: memo(&rest, "work start"),
+            },
+            "out" | "clock-out" | "end" => BotCommand::ClockOut {
+                memo: memo(&rest, "work end"),
+            },
+            "status" => BotCommand::Status,
+            "list" => BotCommand::List,
+            "help" => BotCommand::Help,
+            "revise" => {
+                if rest.is_empty() {
+                    bail!("Please give the time to add, e.g. `/jobcan revise yesterday 18:30`.");
+                }
+                // NOTE(dkg): The date is optional, so only take the first word if it is one.
+                let date = match input::parse_date(rest[0], now.date()) {
+                    Ok(date) if rest.len() > 1 => {
+                        rest.remove(0);
+                        Some(date)
+                    }
+                    _ => None,
+                };
+                let time = input::parse_time(rest.remove(0))?;
+                let (date, time) = input::resolve(date, time, now)?;
+                BotCommand::Revise {
+                    date,
+                    time,
+                    memo: memo(&rest, ""),
+                }
+            }
+            other => bail!("Unknown command '{}'. Try `/jobcan help`.", other),
+        };
+        Ok(command)
+    }
+
+    /// A click on one of the buttons of a reply.
+    pub fn from_action(action_id: &str) -> Option<Self> {
+        let command = match action_id {
+            ACTION_CLOCK_IN => BotCommand::ClockIn {
+                memo: "work start".into(),
+            },
+            ACTION_CLOCK_OUT => BotCommand::ClockOut {
+                memo: "work end".into(),
+            },
+            ACTION_STATUS => BotCommand::Status,
+            ACTION_LIST => BotCommand::List,
+            _ => return None,
+        };
+        Some(command)
+    }
+
+    /// Whether the browser is needed, i.e. whether the answer comes later.
+    fn needs_jobcan(&self) -> bool {
+        *self != BotCommand::Help
+    }
+}
+
+/// A slash command or a button click, as far as the bot cares.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlackRequest {
+    pub user_id: String,
+    pub response_url: String,
+    pub kind: RequestKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestKind {
+    Command { text: String },
+    Action { action_id: String },
+}
+
+impl SlackRequest {
+    /// Slash commands are sent as form fields, button clicks as JSON in the `payload` field.
+    pub fn from_form(form: &HashMap<String, String>) -> Option<Self> {
+        if let Some(payload) = form.get("payload") {
+            let payload: Value = serde_json::from_str(payload).ok()?;
+            return Some(SlackRequest {
+                user_id: payload["user"]["id"].as_str()?.to_string(),
+                response_url: payload["response_url"].as_str()?.to_string(),
+                kind: RequestKind::Action {
+                    action_id: payload["actions"][0]["action_id"].as_str()?.to_string(),
+                },
+            });
+        }
+
+        Some(SlackRequest {
+            user_id: form.get("user_id")?.clone(),
+            response_url: form.get("response_url")?.clone(),
+            kind: RequestKind::Command {
+                text: form.get("text").cloned().unwrap_or_default(),
+            },
+        })
+    }
+}
+
+/// The Jobcan login of a Slack user.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobcanAccount {
+    pub login: String,
+    pub password: String,
+}
+
+/// Slack user IDs and their Jobcan accounts, set up with `slack link`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SlackAccounts {
+    pub accounts: BTreeMap<String, JobcanAccount>,
+}
+
+impl SlackAccounts {
+    /// No accounts when nothing was linked yet.
+    pub fn load(state_dir: &Path) -> color_eyre::Result<Self> {
+        match fs::read_to_string(state_dir.join(ACCOUNTS_FILE)) {
+            Ok(data) => Ok(serde_json::from_str(&data)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(SlackAccounts::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// NOTE(dkg): The passwords are stored as they are, like in .env, so only the owner may read the file.
+    pub fn save(&self, state_dir: &Path) -> color_eyre::Result<()> {
+        fs::create_dir_all(state_dir)?;
+        let path = state_dir.join(ACCOUNTS_FILE);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // NOTE(dkg): The passwords must not be readable by others, not even for a moment.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(&path)?
+            .write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// The configuration for a Slack user: the bot's one, with the user's Jobcan login and state directory.
+pub fn config_for(config: &Configuration, user_id: &str, account: &JobcanAccount) -> Configuration {
+    Configuration {
+        login: account.login.clone(),
+        password: account.password.clone(),
+        state_dir: user_state_dir(&config.state_dir, user_id),
+        // NOTE(dkg): Nobody would see them on the server.
+        desktop_notifications: false,
+        ..config.clone()
+    }
+}
+
+fn user_state_dir(state_dir: &Path, user_id: &str) -> PathBuf {
+    state_dir.join(USERS_DIR).join(user_id)
+}
+
+/// Whether the timestamp Slack signed the request with is recent enough.
+fn is_fresh(timestamp: &str, now: i64) -> bool {
+    match timestamp.parse::<i64>() {
+        Ok(timestamp) => (now - timestamp).abs() <= MAX_REQUEST_AGE_SECONDS,
+        Err(_) => false,
+    }
+}
+
+struct BotState {
+    config: Configuration,
+    timezone: JobcanTimezone,
+    verifier: SlackEventSignatureVerifier,
+    visible: bool,
+    /// NOTE(dkg): One browser at a time, the commands of several users are handled one after the other.
+    browser: Mutex<()>,
+}
+
+/// Run the Slack bot until the process is terminated.
+pub async fn serve(
+    config: &Configuration,
+    addr: SocketAddr,
+    visible: bool,
+) -> color_eyre::Result<()> {
+    if config.slack_signing_secret.is_empty() {
+        bail!(
+            "Please set {} to the signing secret of your Slack app.",
+            ENVVAR_SLACK_SIGNING_SECRET
+        );
+    }
+    let accounts = SlackAccounts::load(&config.state_dir)?;
+    if accounts.accounts.is_empty() {
+        warn!("No Slack users are linked to a Jobcan account yet, see `slack link`.");
+    }
+
+    let state = Arc::new(BotState {
+        config: config.clone(),
+        timezone: config.jobcan_timezone()?,
+        verifier: SlackEventSignatureVerifier::new(&config.slack_signing_secret),
+        visible,
+        browser: Mutex::new(()),
+    });
+    let make_svc = make_service_fn(move |_conn| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let state = state.clone();
+                async move { Ok::<_, Infallible>(handle(state, req).await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)?.serve(make_svc);
+    info!(
+        "Listening for Slack commands on http://{}",
+        server.local_addr()
+    );
+    server.await?;
+
+    Ok(())
+}
+
+async fn handle(state: Arc<BotState>, req: Request<Body>) -> Response<Body> {
+    if req.method() != Method::POST {
+        return status_response(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from)
+    };
+    let signature = header(SlackEventSignatureVerifier::SLACK_SIGNED_HASH_HEADER);
+    let timestamp = header(SlackEventSignatureVerifier::SLACK_SIGNED_TIMESTAMP);
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+        Err(_) => return status_response(StatusCode::BAD_REQUEST),
+    };
+
+    let verified = match (&signature, &timestamp) {
+        (Some(signature), Some(timestamp)) => {
+            is_fresh(timestamp, Utc::now().timestamp())
+                && state.verifier.verify(signature, &body, timestamp).is_ok()
+        }
+        _ => false,
+    };
+    if !verified {
+        warn!("Rejecting a request that was not signed by Slack.");
+        return status_response(StatusCode::UNAUTHORIZED);
+    }
+
+    let form: HashMap<String, String> = url::form_urlencoded::parse(body.as_bytes())
+        .into_owned()
+        .collect();
+    let request = match SlackRequest::from_form(&form) {
+        Some(request) => request,
+        None => return status_response(StatusCode::BAD_REQUEST),
+    };
+    debug!("Slack request: {:?}", request);
+
+    let command = match &request.kind {
+        RequestKind::Command { text } => BotCommand::parse(text, state.timezone.now()),
+        RequestKind::Action { action_id } => BotCommand::from_action(action_id)
+            .ok_or_else(|| eyre!("Unknown button '{}'.", action_id)),
+    };
+    let command = match command {
+        Ok(command) if command.needs_jobcan() => command,
+        Ok(_) => return reply(&RenderedMessage::text(HELP)),
+        Err(err) => return reply(&RenderedMessage::text(&format!(":warning: {}", err))),
+    };
+
+    let account = SlackAccounts::load(&state.config.state_dir)
+        .ok()
+        .and_then(|accounts| accounts.accounts.get(&request.user_id).cloned());
+    let account = match account {
+        Some(account) => account,
+        None => {
+            return reply(&RenderedMessage::text(&format!(
+                ":warning: Your Slack user is not linked to a Jobcan account. Please ask the admin of the bot to run `jobcan-bot slack link {}`.",
+                request.user_id
+            )))
+        }
+    };
+
+    let ack = match request.kind {
+        RequestKind::Command { .. } => {
+            reply(&RenderedMessage::text(":hourglass: Asking Jobcan ..."))
+        }
+        // NOTE(dkg): Button clicks only need a 200, the answer comes through the response URL.
+        RequestKind::Action { .. } => status_response(StatusCode::OK),
+    };
+    tokio::spawn(async move {
+        let config = config_for(&state.config, &request.user_id, &account);
+        let message = {
+            let _browser = state.browser.lock().await;
+            match run(&config, &state.timezone, state.visible, command).await {
+                Ok(message) => message,
+                Err(err) => RenderedMessage::text(&format!(":x: {}", err)),
+            }
+        };
+        if let Err(err) = slack::respond(&request.response_url, &message).await {
+            warn!("Could not answer the Slack command: {}", err);
+        }
+    });
+
+    ack
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// An answer right in the HTTP response, only visible to the user.
+fn reply(message: &RenderedMessage) -> Response<Body> {
+    let mut body = json!({
+        "response_type": "ephemeral",
+        "text": message.text,
+    });
+    if let Some(blocks) = &message.blocks {
+        body["blocks"] = blocks.clone();
+    }
+    Response::builder()
+        .header("content-type", "application/json; charset=utf-8")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+async fn run(
+    config: &Configuration,
+    timezone: &JobcanTimezone,
+    visible: bool,
+    command: BotCommand,
+) -> color_eyre::Result<RenderedMessage> {
+    let driver = jobcan::start_webdriver(visible).await?;
+    let result = run_with_driver(&driver, config, timezone, command).await;
+    if let Err(err) = driver.quit().await {
+        warn!("Could not close the browser: {}", err);
+    }
+
+    result
+}
+
+async fn run_with_driver(
+    driver: &WebDriver,
+    config: &Configuration,
+    timezone: &JobcanTimezone,
+    command: BotCommand,
+) -> color_eyre::Result<RenderedMessage> {
+    jobcan::login(driver, config).await?;
+    let now = timezone.now();
+
+    let message = match command {
+        BotCommand::ClockIn { memo } => {
+            check_holiday(&config.holiday_calendar()?, now.date(), false)?;
+            let before = jobcan::read_working_status(driver, config).await?;
+            if before != WorkingStatus::NotStarted {
+                bail!("You can't clock in, your status is '{}'.", before);
+            }
+            jobcan::push(
+                driver,
+                config,
+                PunchKind::Work,
+                &memo,
+                config.default_group(),
+            )
+            .await?;
+            jobcan::verify_punch(driver, config, before).await?;
+            RenderedMessage::text(&format!(
+                ":sunrise: Clocked in at {} ({}).",
+                now.format("%H:%M"),
+                memo
+            ))
+        }
+        BotCommand::ClockOut { memo } => {
+            let before = jobcan::read_working_status(driver, config).await?;
+            if before != WorkingStatus::Working {
+                bail!("You can't clock out, your status is '{}'.", before);
+            }
+            jobcan::push(
+                driver,
+                config,
+                PunchKind::Work,
+                &memo,
+                config.default_group(),
+            )
+            .await?;
+            jobcan::verify_punch(driver, config, before).await?;
+            let today = read_today(driver, config, now).await?;
+            RenderedMessage::text(&format!(
+                ":wave: Clocked out at {} after {} of work ({}).",
+                now.format("%H:%M"),
+                format_minutes(today.worked_minutes as i64),
+                memo
+            ))
+        }
+        BotCommand::Status => status_message(&read_today(driver, config, now).await?),
+        BotCommand::Revise { date, time, memo } => {
+            check_holiday(&config.holiday_calendar()?, date, false)?;
+            let jobcan_time = time.to_jobcan();
+            if !jobcan::revise(driver, config, Some(date), &jobcan_time, &memo).await? {
+                bail!("Jobcan did not accept {} on {}.", time, date);
+            }
+            RenderedMessage::text(&format!(
+                ":pencil2: Added {} on {} ({}).",
+                time,
+                date,
+                date.weekday()
+            ))
+        }
+        BotCommand::List => {
+            let month = now.date().with_day(1).unwrap();
+            let days = jobcan::read_attendance(driver, config, month).await?;
+            let totals = jobcan::read_monthly_totals(driver, config, month).await?;
+            let mut lines = vec![format!("*{}*", month.format("%Y-%m"))];
+            for day in days.iter().filter(|day| !day.record.start.is_empty()) {
+                lines.push(format!(
+                    "{} {} {}-{}",
+                    day.date.format("%m/%d"),
+                    day.date.weekday(),
+                    day.record.start,
+                    day.record.end
+                ));
+            }
+            let total = |minutes: Option<u32>| {
+                minutes.map_or_else(|| "-".to_string(), |minutes| format_minutes(minutes as i64))
+            };
+            lines.push(format!(
+                "Worked {} of {}, overtime {}",
+                total(totals.worked),
+                total(totals.required),
+                total(totals.overtime)
+            ));
+            RenderedMessage::text(&lines.join("\n"))
+        }
+        BotCommand::Help => RenderedMessage::text(HELP),
+    };
+
+    Ok(message)
+}
+
+async fn read_today(
+    driver: &WebDriver,
+    config: &Configuration,
+    now: NaiveDateTime,
+) -> color_eyre::Result<TodayStatus> {
+    let working_status = jobcan::read_working_status(driver, config).await?;
+    let punches = jobcan::read_punches(driver, config, now.date()).await?;
+    Ok(TodayStatus::new(
+        working_status,
+        punches,
+        now.hour() * 60 + now.minute(),
+    ))
+}
+
+/// Today's punches, with buttons for what can be done next.
+fn status_message(today: &TodayStatus) -> RenderedMessage {
+    let mut lines = vec![format!("*{}*", today.status)];
+    for punch in &today.punches {
+        lines.push(format!("{} {} {}", punch.time, punch.kind, punch.notice));
+    }
+    lines.push(format!(
+        "Worked: {}",
+        format_minutes(today.worked_minutes as i64)
+    ));
+    let text = lines.join("\n");
+
+    let button = |text: &str, action_id: &str| {
+        json!({
+            "type": "button",
+            "text": {"type": "plain_text", "text": text},
+            "action_id": action_id,
+        })
+    };
+    let mut buttons = vec![];
+    match today.status {
+        WorkingStatus::NotStarted => buttons.push(button("Clock in", ACTION_CLOCK_IN)),
+        WorkingStatus::Working => buttons.push(button("Clock out", ACTION_CLOCK_OUT)),
+        _ => (),
+    }
+    buttons.push(button("Refresh", ACTION_STATUS));
+    buttons.push(button("This month", ACTION_LIST));
+
+    RenderedMessage {
+        blocks: Some(json!([
+            {"type": "section", "text": {"type": "mrkdwn", "text": text}},
+            {"type": "actions", "elements": buttons},
+        ])),
+        text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status::Punch;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let now = at("2021-10-14 09:30");
+
+        assert_eq!(
+            BotCommand::ClockIn {
+                memo: "remote start".into()
+            },
+            BotCommand::parse("in remote start", now).unwrap()
+        );
+        assert_eq!(
+            BotCommand::ClockOut {
+                memo: "work end".into()
+            },
+            BotCommand::parse(" OUT ", now).unwrap()
+        );
+        assert_eq!(
+            BotCommand::Status,
+            BotCommand::parse("status", now).unwrap()
+        );
+        assert_eq!(BotCommand::Help, BotCommand::parse("", now).unwrap());
+        assert!(BotCommand::parse("dance", now).is_err());
+    }
+
+    #[test]
+    fn test_parse_revise() {
+        let now = at("2021-10-14 09:30");
+
+        assert_eq!(
+            BotCommand::Revise {
+                date: NaiveDate::from_ymd(2021, 10, 13),
+                time: "18:30".parse().unwrap(),
+                memo: "forgot".into()
+            },
+            BotCommand::parse("revise yesterday 18:30 forgot", now).unwrap()
+        );
+        assert_eq!(
+            BotCommand::Revise {
+                date: NaiveDate::from_ymd(2021, 10, 14),
+                time: "09:15".parse().unwrap(),
+                memo: String::new()
+            },
+            BotCommand::parse("revise now-15m", now).unwrap()
+        );
+        assert!(BotCommand::parse("revise", now).is_err());
+        assert!(BotCommand::parse("revise yesterday", now).is_err());
+        // Errors for the :warning: reply, no panic in the request handler
+        assert!(BotCommand::parse("revise now-99999999999999", now).is_err());
+        assert!(BotCommand::parse("revise yesterday now-1000000000000000000h", now).is_err());
+        assert!(BotCommand::parse("revise now-153722867280912930 oops", now).is_err());
+    }
+
+    #[test]
+    fn test_from_action() {
+        assert_eq!(
+            Some(BotCommand::Status),
+            BotCommand::from_action(ACTION_STATUS)
+        );
+        assert_eq!(None, BotCommand::from_action("something_else"));
+    }
+
+    #[test]
+    fn test_request_from_form() {
+        let form: HashMap<String, String> = vec![
+            ("command", "/jobcan"),
+            ("text", "in"),
+            ("user_id", "U0123ABCD"),
+            ("response_url", "https://hooks.slack.com/commands/1"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+        assert_eq!(
+            Some(SlackRequest {
+                user_id: "U0123ABCD".into(),
+                response_url: "https://hooks.slack.com/commands/1".into(),
+                kind: RequestKind::Command { text: "in".into() },
+            }),
+            SlackRequest::from_form(&form)
+        );
+
+        let payload = json!({
+            "type": "block_actions",
+            "user": {"id": "U0123ABCD"},
+            "response_url": "https://hooks.slack.com/actions/1",
+            "actions": [{"action_id": ACTION_CLOCK_OUT}],
+        });
+        let mut form = HashMap::new();
+        form.insert("payload".to_string(), payload.to_string());
+        assert_eq!(
+            Some(SlackRequest {
+                user_id: "U0123ABCD".into(),
+                response_url: "https://hooks.slack.com/actions/1".into(),
+                kind: RequestKind::Action {
+                    action_id: ACTION_CLOCK_OUT.into()
+                },
+            }),
+            SlackRequest::from_form(&form)
+        );
+
+        assert_eq!(None, SlackRequest::from_form(&HashMap::new()));
+    }
+
+    #[test]
+    fn test_is_fresh() {
+        assert!(is_fresh("1634176800", 1634176800 + 60));
+        assert!(!is_fresh("1634176800", 1634176800 + 10 * 60));
+        assert!(!is_fresh("yesterday", 1634176800));
+    }
+
+    #[test]
+    fn test_accounts() {
+        let dir = std::env::temp_dir().join(format!("jobcan-bot-accounts-{}", std::process::id()));
+        assert_eq!(SlackAccounts::default(), SlackAccounts::load(&dir).unwrap());
+
+        let mut accounts = SlackAccounts::default();
+        accounts.accounts.insert(
+            "U0123ABCD".into(),
+            JobcanAccount {
+                login: "dkg@example.com".into(),
+                password: "secret".into(),
+            },
+        );
+        accounts.save(&dir).unwrap();
+        assert_eq!(accounts, SlackAccounts::load(&dir).unwrap());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = fs::metadata(dir.join(ACCOUNTS_FILE)).unwrap();
+            assert_eq!(0o600, metadata.permissions().mode() & 0o777);
+        }
+
+        let config = Configuration {
+            state_dir: dir.clone(),
+            login: "bot@example.com".into(),
+            ..Default::default()
+        };
+        let user_config = config_for(&config, "U0123ABCD", &accounts.accounts["U0123ABCD"]);
+        assert_eq!("dkg@example.com", user_config.login);
+        assert_eq!(
+            dir.join("slack-users").join("U0123ABCD"),
+            user_config.state_dir
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_status_message_buttons() {
+        let today = TodayStatus::new(
+            WorkingStatus::Working,
+            vec![Punch {
+                time: "09:00".into(),
+                kind: "出勤".into(),
+                notice: "work start".into(),
+            }],
+            10 * 60,
+        );
+        let message = status_message(&today);
+
+        assert!(message.text.contains("09:00 出勤 work start"));
+        assert!(message.text.contains("Worked: 01:00"));
+        let blocks = message.blocks.unwrap();
+        assert_eq!(ACTION_CLOCK_OUT, blocks[1]["elements"][0]["action_id"]);
+        assert!(slack::parse_blocks(&blocks).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unsigned_requests() {
+        let state = Arc::new(BotState {
+            config: Configuration::default(),
+            timezone: JobcanTimezone::default(),
+            verifier: SlackEventSignatureVerifier::new("secret"),
+            visible: false,
+            browser: Mutex::new(()),
+        });
+        let request = Request::builder()
+            .method(Method::POST)
+            .header(
+                SlackEventSignatureVerifier::SLACK_SIGNED_HASH_HEADER,
+                "v0=0000",
+            )
+            .header(
+                SlackEventSignatureVerifier::SLACK_SIGNED_TIMESTAMP,
+                Utc::now().timestamp().to_string(),
+            )
+            .body(Body::from("command=%2Fjobcan&text=in&user_id=U0123ABCD"))
+            .unwrap();
+
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            handle(state, request).await.status()
+        );
+    }
+}
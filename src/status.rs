@@ -0,0 +1,253 @@
+use serde::Serialize;
+use std::fmt;
+
+use crate::clock::ClockTime;
+
+/// The state Jobcan shows next to the "PUSH" button.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkingStatus {
+    /// 未出勤
+    NotStarted,
+    /// 勤務中
+    Working,
+    /// 休憩中
+    OnBreak,
+    /// 退室中
+    Finished,
+    Unknown,
+}
+
+impl WorkingStatus {
+    /// Parse the label from the Jobcan page, either in Japanese or in English.
+    /// NOTE(dkg): Only the exact labels, the status decides whether a punch is a clock-in or a clock-out.
+    pub fn from_label(label: &str) -> Self {
+        const LABELS: [(&str, WorkingStatus); 10] = [
+            ("未出勤", WorkingStatus::NotStarted),
+            ("not attending work", WorkingStatus::NotStarted),
+            ("not started", WorkingStatus::NotStarted),
+            ("勤務中", WorkingStatus::Working),
+            ("working", WorkingStatus::Working),
+            ("休憩中", WorkingStatus::OnBreak),
+            ("on break", WorkingStatus::OnBreak),
+            ("退室中", WorkingStatus::Finished),
+            ("left work", WorkingStatus::Finished),
+            ("finished", WorkingStatus::Finished),
+        ];
+
+        let label = label.trim().to_lowercase();
+        LABELS
+            .iter()
+            .find(|(known, _)| *known == label)
+            .map(|(_, status)| *status)
+            .unwrap_or(WorkingStatus::Unknown)
+    }
+}
+
+impl fmt::Display for WorkingStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            WorkingStatus::NotStarted => "not started",
+            WorkingStatus::Working => "working",
+            WorkingStatus::OnBreak => "on break",
+            WorkingStatus::Finished => "finished",
+            WorkingStatus::Unknown => "unknown",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// A single entry of the day, as listed on the "revise clocking data" page.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Punch {
+    /// hh:mm
+    pub time: String,
+    /// e.g. 出勤 or 退勤
+    pub kind: String,
+    pub notice: String,
+}
+
+impl Punch {
+    /// Whether this is a 休憩開始/休憩終了 entry rather than clocking in or out.
+    pub fn is_break(&self) -> bool {
+        let lower = self.kind.to_lowercase();
+        self.kind.contains("休憩") || lower.contains("break") || lower.contains("rest")
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TodayStatus {
+    pub status: WorkingStatus,
+    pub punches: Vec<Punch>,
+    pub worked_minutes: u32,
+}
+
+impl TodayStatus {
+    pub fn new(status: WorkingStatus, punches: Vec<Punch>, now_minutes: u32) -> Self {
+        let worked_minutes = worked_minutes(&punches, now_minutes);
+        TodayStatus {
+            status,
+            punches,
+            worked_minutes,
+        }
+    }
+
+    pub fn print(&self) {
+        println!("Status : {}", self.status);
+        for punch in &self.punches {
+            println!("Punch  : {} {} {}", punch.time, punch.kind, punch.notice);
+        }
+        println!(
+            "Worked : {:02}:{:02}",
+            self.worked_minutes / 60,
+            self.worked_minutes % 60
+        );
+    }
+}
+
+/// Sum up the time between every start and the following end punch, minus the breaks.
+/// When the last start has no end yet (still working or on break), the time until `now_minutes` is counted.
+pub fn worked_minutes(punches: &[Punch], now_minutes: u32) -> u32 {
+    let (breaks, work): (Vec<&Punch>, Vec<&Punch>) = punches.iter().partition(|p| p.is_break());
+
+    span_minutes(&work, now_minutes).saturating_sub(span_minutes(&breaks, now_minutes))
+}
+
+fn span_minutes(punches: &[&Punch], now_minutes: u32) -> u32 {
+    let mut minutes: Vec<u32> = punches
+        .iter()
+        .filter_map(|p| p.time.parse::<ClockTime>().ok())
+        .map(ClockTime::minutes)
+        .collect();
+    minutes.sort_unstable();
+    if minutes.len() % 2 == 1 {
+        minutes.push(now_minutes.max(*minutes.last().unwrap()));
+    }
+
+    minutes
+        .chunks(2)
+        .map(|pair| pair[1].saturating_sub(pair[0]))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn punch(time: &str) -> Punch {
+        Punch {
+            time: time.into(),
+            kind: String::new(),
+            notice: String::new(),
+        }
+    }
+
+    fn break_punch(time: &str) -> Punch {
+        Punch {
+            time: time.into(),
+            kind: String::from("休憩開始"),
+            notice: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_working_status_from_label() {
+        assert_eq!(
+            WorkingStatus::NotStarted,
+            WorkingStatus::from_label("未出勤")
+        );
+        assert_eq!(
+            WorkingStatus::Working,
+            WorkingStatus::from_label(" 勤務中 ")
+        );
+        assert_eq!(WorkingStatus::OnBreak, WorkingStatus::from_label("休憩中"));
+        assert_eq!(WorkingStatus::Finished, WorkingStatus::from_label("退室中"));
+        assert_eq!(WorkingStatus::Working, WorkingStatus::from_label("Working"));
+        assert_eq!(WorkingStatus::Unknown, WorkingStatus::from_label(""));
+        assert_eq!(
+            WorkingStatus::NotStarted,
+            WorkingStatus::from_label("Not attending work")
+        );
+        assert_eq!(
+            WorkingStatus::OnBreak,
+            WorkingStatus::from_label("On break")
+        );
+        assert_eq!(
+            WorkingStatus::Finished,
+            WorkingStatus::from_label("Left work")
+        );
+    }
+
+    #[test]
+    fn test_working_status_near_misses() {
+        for label in [
+            "Interest",
+            "Notes",
+            "Working hours",
+            "休憩",
+            "勤務中止",
+            "Breakfast",
+            "Not working",
+        ] {
+            assert_eq!(
+                WorkingStatus::Unknown,
+                WorkingStatus::from_label(label),
+                "{}",
+                label
+            );
+        }
+    }
+
+    #[test]
+    fn test_worked_minutes_finished_day() {
+        let punches = vec![
+            punch("09:00"),
+            punch("12:00"),
+            punch("13:00"),
+            punch("18:30"),
+        ];
+
+        assert_eq!(3 * 60 + 5 * 60 + 30, worked_minutes(&punches, 20 * 60));
+    }
+
+    #[test]
+    fn test_worked_minutes_still_working() {
+        let punches = vec![punch("09:00")];
+
+        assert_eq!(90, worked_minutes(&punches, 10 * 60 + 30));
+    }
+
+    #[test]
+    fn test_worked_minutes_without_breaks() {
+        let punches = vec![
+            punch("09:00"),
+            break_punch("12:00"),
+            break_punch("12:45"),
+            punch("18:00"),
+        ];
+
+        assert_eq!(9 * 60 - 45, worked_minutes(&punches, 20 * 60));
+    }
+
+    #[test]
+    fn test_worked_minutes_on_break() {
+        let punches = vec![punch("09:00"), break_punch("12:00")];
+
+        assert_eq!(3 * 60, worked_minutes(&punches, 12 * 60 + 30));
+    }
+
+    #[test]
+    fn test_worked_minutes_no_punches() {
+        assert_eq!(0, worked_minutes(&[], 10 * 60));
+    }
+
+    #[test]
+    fn test_today_status_as_json() {
+        let status = TodayStatus::new(WorkingStatus::Working, vec![punch("09:00")], 9 * 60 + 5);
+
+        assert_eq!(
+            r#"{"status":"working","punches":[{"time":"09:00","kind":"","notice":""}],"worked_minutes":5}"#,
+            serde_json::to_string(&status).unwrap()
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! Slack messages built from templates like "{emoji} {name} started work at {time} ({group}) — {memo}".
+//!
+//! A template is either plain text or, when it starts with "[", a Block Kit layout (a JSON array of blocks)
+//! with the placeholders in its strings.
+use chrono::NaiveDate;
+use color_eyre::eyre::eyre;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+use crate::clock::ClockTime;
+use crate::config::{
+    ENVVAR_SLACK_TEMPLATE_BREAK_END, ENVVAR_SLACK_TEMPLATE_BREAK_START,
+    ENVVAR_SLACK_TEMPLATE_CLOCK_IN, ENVVAR_SLACK_TEMPLATE_CLOCK_OUT, ENVVAR_SLACK_TEMPLATE_REVISE,
+};
+use crate::forecast::format_minutes;
+
+/// Used for clock-in and clock-out when no template is set: just the memo, like before templates existed.
+pub const DEFAULT_TEMPLATE: &str = "{memo}";
+
+/// The notification text for Block Kit messages, which Slack shows e.g. in push notifications. Also what webhooks get
+/// for events without a template.
+pub const BLOCKS_FALLBACK_TEMPLATE: &str = "{emoji} {name}: {event} at {time}";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PunchEvent {
+    ClockIn,
+    ClockOut,
+    BreakStart,
+    BreakEnd,
+    Revise,
+}
+
+impl PunchEvent {
+    pub fn emoji(&self) -> &'static str {
+        match self {
+            PunchEvent::ClockIn => ":sunrise:",
+            PunchEvent::ClockOut => ":wave:",
+            PunchEvent::BreakStart => ":coffee:",
+            PunchEvent::BreakEnd => ":computer:",
+            PunchEvent::Revise => ":pencil2:",
+        }
+    }
+
+    /// The name in settings like JC_WEBHOOKS, e.g. "break-start".
+    pub fn key(&self) -> &'static str {
+        match self {
+            PunchEvent::ClockIn => "clock-in",
+            PunchEvent::ClockOut => "clock-out",
+            PunchEvent::BreakStart => "break-start",
+            PunchEvent::BreakEnd => "break-end",
+            PunchEvent::Revise => "revise",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        [
+            PunchEvent::ClockIn,
+            PunchEvent::ClockOut,
+            PunchEvent::BreakStart,
+            PunchEvent::BreakEnd,
+            PunchEvent::Revise,
+        ]
+        .iter()
+        .copied()
+        .find(|event| event.key() == key)
+    }
+}
+
+impl fmt::Display for PunchEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PunchEvent::ClockIn => "clock-in",
+            PunchEvent::ClockOut => "clock-out",
+            PunchEvent::BreakStart => "break start",
+            PunchEvent::BreakEnd => "break end",
+            PunchEvent::Revise => "revised punch",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The templates from the SLACK_TEMPLATE_* environment variables. Empty means not set.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SlackTemplates {
+    pub clock_in: String,
+    pub clock_out: String,
+    pub break_start: String,
+    pub break_end: String,
+    pub revise: String,
+}
+
+impl SlackTemplates {
+    /// The template for the event. Clock-in and clock-out fall back to the memo, breaks and revisions
+    /// are only posted when a template is set.
+    pub fn for_event(&self, event: PunchEvent) -> Option<&str> {
+        let template = match event {
+            PunchEvent::ClockIn => &self.clock_in,
+            PunchEvent::ClockOut => &self.clock_out,
+            PunchEvent::BreakStart => &self.break_start,
+            PunchEvent::BreakEnd => &self.break_end,
+            PunchEvent::Revise => &self.revise,
+        };
+        match (template.trim().is_empty(), event) {
+            (false, _) => Some(template),
+            (true, PunchEvent::ClockIn) | (true, PunchEvent::ClockOut) => Some(DEFAULT_TEMPLATE),
+            (true, _) => None,
+        }
+    }
+
+    /// Make sure the Block Kit templates are valid JSON, so a typo shows up before punching.
+    pub fn validate(&self) -> color_eyre::Result<()> {
+        let templates = [
+            (ENVVAR_SLACK_TEMPLATE_CLOCK_IN, &self.clock_in),
+            (ENVVAR_SLACK_TEMPLATE_CLOCK_OUT, &self.clock_out),
+            (ENVVAR_SLACK_TEMPLATE_BREAK_START, &self.break_start),
+            (ENVVAR_SLACK_TEMPLATE_BREAK_END, &self.break_end),
+            (ENVVAR_SLACK_TEMPLATE_REVISE, &self.revise),
+        ];
+        for (name, template) in templates.iter() {
+            render(template, &PunchDetails::default())
+                .map_err(|err| eyre!("Please check {}: {}", name, err))?;
+        }
+        Ok(())
+    }
+}
+
+/// What the placeholders are filled with.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PunchDetails {
+    pub event: Option<PunchEvent>,
+    pub name: String,
+    /// yyyy-mm-dd
+    pub date: String,
+    pub time: Option<ClockTime>,
+    pub group: String,
+    pub memo: String,
+    pub worked_today_minutes: Option<u32>,
+    /// Negative once the required hours of the month are done.
+    pub remaining_month_minutes: Option<i64>,
+}
+
+impl PunchDetails {
+    pub fn new(event: PunchEvent, date: NaiveDate, time: ClockTime, memo: &str) -> Self {
+        PunchDetails {
+            event: Some(event),
+            date: date.format("%Y-%m-%d").to_string(),
+            time: Some(time),
+            memo: memo.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn value(&self, placeholder: &str) -> Option<String> {
+        let value = match placeholder {
+            "emoji" => self.event.map(|event| event.emoji().to_string())?,
+            "name" => self.name.clone(),
+            "event" => self.event.map(|event| event.to_string())?,
+            "date" => self.date.clone(),
+            "time" => self.time.map(|time| time.to_string())?,
+            "group" => self.group.clone(),
+            "memo" => self.memo.clone(),
+            "worked_today" => format_minutes(self.worked_today_minutes? as i64),
+            "remaining_month" => format_minutes(self.remaining_month_minutes?),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+/// A filled template, ready to be posted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RenderedMessage {
+    pub text: String,
+    /// The Block Kit blocks, if the template is a layout.
+    pub blocks: Option<Value>,
+}
+
+impl RenderedMessage {
+    pub fn text(text: &str) -> Self {
+        RenderedMessage {
+            text: text.to_string(),
+            blocks: None,
+        }
+    }
+
+    /// The same message with a line added at the end, e.g. the hours worked. Block Kit layouts get a context block.
+    pub fn with_footer(&self, footer: &str) -> Self {
+        let blocks = self.blocks.clone().map(|mut blocks| {
+            if let Value::Array(blocks) = &mut blocks {
+                blocks.push(serde_json::json!({
+                    "type": "context",
+                    "elements": [{"type": "mrkdwn", "text": footer}],
+                }));
+            }
+            blocks
+        });
+        RenderedMessage {
+            text: format!("{}\n{}", self.text, footer),
+            blocks,
+        }
+    }
+}
+
+/// Whether the template needs the given placeholder, e.g. to skip reading the monthly totals from Jobcan.
+pub fn uses(template: &str, placeholder: &str) -> bool {
+    template.contains(&format!("{{{}}}", placeholder))
+}
+
+pub fn render(template: &str, details: &PunchDetails) -> color_eyre::Result<RenderedMessage> {
+    if !template.trim_start().starts_with('[') {
+        return Ok(RenderedMessage::text(&fill(template, details)));
+    }
+
+    let mut blocks: Value = serde_json::from_str(template)
+        .map_err(|err| eyre!("The Block Kit template is not valid JSON: {}", err))?;
+    if !blocks.is_array() {
+        return Err(eyre!(
+            "The Block Kit template must be a JSON array of blocks."
+        ));
+    }
+    fill_json(&mut blocks, details);
+
+    Ok(RenderedMessage {
+        text: fill(BLOCKS_FALLBACK_TEMPLATE, details),
+        blocks: Some(blocks),
+    })
+}
+
+/// Replace the known placeholders. Unknown ones and placeholders without a value are left as they are.
+/// NOTE(dkg): One pass, so a memo that contains e.g. "{time}" is not filled in again.
+fn fill(template: &str, details: &PunchDetails) -> String {
+    let mut text = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        let candidate = &rest[start..];
+        let filled = candidate
+            .find('}')
+            .and_then(|end| details.value(&candidate[1..end]).map(|value| (end, value)));
+        match filled {
+            Some((end, value)) => {
+                text.push_str(&value);
+                rest = &candidate[end + 1..];
+            }
+            None => {
+                text.push('{');
+                rest = &candidate[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+fn fill_json(value: &mut Value, details: &PunchDetails) {
+    match value {
+        Value::String(text) => *text = fill(text, details),
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| fill_json(value, details)),
+        Value::Object(map) => map.values_mut().for_each(|value| fill_json(value, details)),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn details() -> PunchDetails {
+        PunchDetails {
+            event: Some(PunchEvent::ClockIn),
+            name: "dkg".into(),
+            date: "2021-10-14".into(),
+            time: ClockTime::from_hm(9, 5),
+            group: "Office".into(),
+            memo: "work start".into(),
+            worked_today_minutes: Some(7 * 60 + 30),
+            remaining_month_minutes: Some(-90),
+        }
+    }
+
+    #[test]
+    fn test_render_text() {
+        let message = render(
+            "{emoji} {name} started work at {time} ({group}) — {memo}",
+            &details(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            ":sunrise: dkg started work at 09:05 (Office) — work start",
+            message.text
+        );
+        assert_eq!(None, message.blocks);
+        assert_eq!(
+            "07:30 today, -01:30 left, {unknown}",
+            render(
+                "{worked_today} today, {remaining_month} left, {unknown}",
+                &details()
+            )
+            .unwrap()
+            .text
+        );
+    }
+
+    #[test]
+    fn test_render_fills_only_once() {
+        let details = PunchDetails {
+            memo: "{name} {{time}}".into(),
+            name: "dkg".into(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            "{name} {{time}}, dkg",
+            render("{memo}, {name}", &details).unwrap().text
+        );
+    }
+
+    #[test]
+    fn test_render_keeps_placeholders_without_value() {
+        let details = PunchDetails {
+            memo: "work start".into(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            "work start {time} {worked_today}",
+            render("{memo} {time} {worked_today}", &details)
+                .unwrap()
+                .text
+        );
+    }
+
+    #[test]
+    fn test_render_blocks() {
+        let template = r#"[{"type": "section", "text": {"type": "mrkdwn", "text": "*{name}* {event} at {time}"}}]"#;
+        let message = render(template, &details()).unwrap();
+
+        assert_eq!(":sunrise: dkg: clock-in at 09:05", message.text);
+        assert_eq!(
+            serde_json::json!([{"type": "section", "text": {"type": "mrkdwn", "text": "*dkg* clock-in at 09:05"}}]),
+            message.blocks.unwrap()
+        );
+
+        assert!(render("[{\"type\": ", &details()).is_err());
+    }
+
+    #[test]
+    fn test_with_footer() {
+        let message = RenderedMessage::text("good morning").with_footer("Worked: 08:15");
+        assert_eq!("good morning\nWorked: 08:15", message.text);
+        assert_eq!(None, message.blocks);
+
+        let template = r#"[{"type": "section", "text": {"type": "mrkdwn", "text": "{name}"}}]"#;
+        let message = render(template, &details())
+            .unwrap()
+            .with_footer("Worked: 08:15");
+        assert_eq!(
+            serde_json::json!([
+                {"type": "section", "text": {"type": "mrkdwn", "text": "dkg"}},
+                {"type": "context", "elements": [{"type": "mrkdwn", "text": "Worked: 08:15"}]},
+            ]),
+            message.blocks.unwrap()
+        );
+    }
+
+    #[test]
+    fn test_template_for_event() {
+        let templates = SlackTemplates {
+            clock_out: "{emoji} bye".into(),
+            ..Default::default()
+        };
+
+        assert_eq!(Some("{memo}"), templates.for_event(PunchEvent::ClockIn));
+        assert_eq!(
+            Some("{emoji} bye"),
+            templates.for_event(PunchEvent::ClockOut)
+        );
+        assert_eq!(None, templates.for_event(PunchEvent::BreakStart));
+        assert_eq!(None, templates.for_event(PunchEvent::Revise));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut templates = SlackTemplates::default();
+        assert!(templates.validate().is_ok());
+
+        templates.revise = "[not json".into();
+        assert!(templates.validate().is_err());
+    }
+
+    #[test]
+    fn test_uses() {
+        assert!(uses("{memo} ({remaining_month})", "remaining_month"));
+        assert!(!uses("{memo}", "remaining_month"));
+    }
+}
@@ -0,0 +1,151 @@
+//! Jobcan shows and expects all dates and times in the timezone of the tenant, not the one of the machine the bot
+//! runs on.
+use chrono::prelude::*;
+use chrono::Duration;
+use chrono_tz::Tz;
+use color_eyre::eyre::eyre;
+
+use crate::clock::ClockTime;
+
+pub const DEFAULT_TIMEZONE: Tz = chrono_tz::Asia::Tokyo;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JobcanTimezone(Tz);
+
+impl Default for JobcanTimezone {
+    fn default() -> Self {
+        JobcanTimezone(DEFAULT_TIMEZONE)
+    }
+}
+
+impl JobcanTimezone {
+    /// An IANA name like "Asia/Tokyo". Empty means the default.
+    pub fn new(name: &str) -> color_eyre::Result<Self> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Ok(JobcanTimezone::default());
+        }
+        name.parse::<Tz>().map(JobcanTimezone).map_err(|_| {
+            eyre!(
+                "Unknown timezone '{}'. Use a name like Asia/Tokyo or Europe/Berlin.",
+                name
+            )
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    /// The current date and time on Jobcan's clock.
+    pub fn now(&self) -> NaiveDateTime {
+        self.at(Utc::now())
+    }
+
+    /// The current date on Jobcan's clock.
+    pub fn today(&self) -> NaiveDate {
+        self.now().date()
+    }
+
+    /// The given moment on Jobcan's clock.
+    pub fn at(&self, at: DateTime<Utc>) -> NaiveDateTime {
+        at.with_timezone(&self.0).naive_local()
+    }
+
+    /// Seconds since the epoch for the given time on Jobcan's clock, e.g. for Slack's status expiration.
+    pub fn timestamp(&self, at: NaiveDateTime) -> Option<i64> {
+        self.0
+            .from_local_datetime(&at)
+            .earliest()
+            .map(|at| at.timestamp())
+    }
+
+    /// The time on Jobcan's clock, together with the local time of this machine if that is different,
+    /// e.g. "2021-10-14 18:00 Asia/Tokyo (local: 2021-10-14 11:00)".
+    pub fn describe(&self, at: NaiveDateTime) -> String {
+        self.describe_for(at, &Local)
+    }
+
+    /// Same as `describe`, but for a time of the working day, which can be 24:00 or later.
+    pub fn describe_clock(&self, date: NaiveDate, time: ClockTime) -> String {
+        self.describe(date.and_hms(0, 0, 0) + Duration::minutes(time.minutes() as i64))
+    }
+
+    fn describe_for<L: TimeZone>(&self, at: NaiveDateTime, local: &L) -> String {
+        let jobcan = format!("{} {}", at.format("%Y-%m-%d %H:%M"), self.name());
+        let zoned = match self.0.from_local_datetime(&at).earliest() {
+            Some(zoned) => zoned,
+            None => return jobcan,
+        };
+        let local = zoned.with_timezone(local);
+        if local.offset().fix() == zoned.offset().fix() {
+            jobcan
+        } else {
+            format!(
+                "{} (local: {})",
+                jobcan,
+                local.naive_local().format("%Y-%m-%d %H:%M")
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_new() {
+        assert_eq!(JobcanTimezone::default(), JobcanTimezone::new("").unwrap());
+        assert_eq!("Asia/Tokyo", JobcanTimezone::new(" ").unwrap().name());
+        assert_eq!(
+            "Europe/Berlin",
+            JobcanTimezone::new("Europe/Berlin").unwrap().name()
+        );
+        assert!(JobcanTimezone::new("Mars/Olympus_Mons").is_err());
+    }
+
+    #[test]
+    fn test_at() {
+        let tokyo = JobcanTimezone::default();
+        let utc = Utc.ymd(2021, 10, 14).and_hms(16, 30, 0);
+
+        // Already the next day in Tokyo
+        assert_eq!(at("2021-10-15 01:30"), tokyo.at(utc));
+        assert_eq!(NaiveDate::from_ymd(2021, 10, 15), tokyo.at(utc).date());
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let tokyo = JobcanTimezone::default();
+
+        assert_eq!(
+            Some(Utc.ymd(2021, 10, 14).and_hms(9, 0, 0).timestamp()),
+            tokyo.timestamp(at("2021-10-14 18:00"))
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        let tokyo = JobcanTimezone::default();
+        let utc = FixedOffset::east(0);
+        let jst = FixedOffset::east(9 * 3600);
+
+        assert_eq!(
+            "2021-10-14 18:00 Asia/Tokyo",
+            tokyo.describe_for(at("2021-10-14 18:00"), &jst)
+        );
+        assert_eq!(
+            "2021-10-14 18:00 Asia/Tokyo (local: 2021-10-14 09:00)",
+            tokyo.describe_for(at("2021-10-14 18:00"), &utc)
+        );
+        assert_eq!(
+            "2021-10-15 02:00 Asia/Tokyo (local: 2021-10-14 17:00)",
+            tokyo.describe_for(at("2021-10-15 02:00"), &utc)
+        );
+    }
+}